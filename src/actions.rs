@@ -5,58 +5,147 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::num::NonZero;
+use std::{collections::HashMap, num::NonZero, path::PathBuf};
 
 use enumset::EnumSet;
 
 use crate::{
-	app::{App, ClipboardContents, PreFullscreenState},
-	canvas::{Canvas, Image, Operation, Stroke},
+	app::{App, ClipboardContents, ClipboardTexture, PreFullscreenState},
+	canvas::{centered_ruler, color_selector_center, Canvas, Confirmation, ExportKind, FindTarget, Image, Multicanvas, Operation, Page, Point, Stroke, Symmetry, SymmetryKind, TextCapture},
 	clipboard::ClipboardData,
-	file::{load_canvas_from_file, save_canvas_to_file},
+	config::stamp_directory_path,
+	file::{export_canvas_to_pdf, export_replay_to_gif, load_canvas_from_file, save_canvas_to_file, strokes_to_svg},
 	input::{
 		keymap::{Action, Keymap},
 		Key,
 	},
-	tools::TransientModeSwitch,
-	utility::{Px, Vex, Vx},
+	render::{texture::Texture, Renderer},
+	tools::{ColorTarget, Tool, ToolKind, TransientModeSwitch},
+	utility::{parse_gpl, trace_outlines, write_gpl, Px, Srgb8, Srgba8, Vex, Vx, Zero},
 };
 
-pub fn default_keymap() -> Keymap {
+// Bare undo keys bind undo and redo to Z and Shift-Z, alongside Ctrl-Z and Ctrl-Y.
+pub fn default_keymap(are_bare_undo_keys_on: bool, color_picker_key: Key) -> Keymap {
 	let mut keymap = Keymap::new();
 	const NONE: EnumSet<Key> = EnumSet::EMPTY;
 	use Key::*;
 
-	keymap.insert(NONE, F3, false, trigger(toggle_debug_mode));
-	keymap.insert(Control | Shift, S, false, trigger(save_as_file));
-	keymap.insert(Control, S, false, trigger(save_file));
-	keymap.insert(Control, O, false, trigger(load_from_file));
-	keymap.insert(Control, N, false, trigger(new_file));
-	keymap.insert(Control, W, false, trigger(close_tab));
-	keymap.insert(Control, LeftArrow, false, trigger(switch_tab_left));
-	keymap.insert(Control, RightArrow, false, trigger(switch_tab_right));
-	keymap.insert(NONE, B, false, trigger(choose_draw_tool));
-	keymap.insert(NONE, Backspace, false, trigger(delete_selected_items));
-	keymap.insert(Control | Shift, F, false, trigger(toggle_fullscreen));
-	keymap.insert(Control, F, false, trigger(toggle_maximized));
-	keymap.insert(Control, X, false, trigger(cut));
-	keymap.insert(Control, C, false, trigger(copy));
-	keymap.insert(Control, V, false, trigger(paste));
-	keymap.insert(NONE, A, false, trigger(select_all));
-	keymap.insert(Shift, A, false, trigger(select_none));
-	keymap.insert(Tab, R, false, trigger(recolor_selection));
-	keymap.insert(NONE, S, false, trigger(choose_select_tool));
-	keymap.insert(NONE, T, false, trigger(choose_move_tool));
-	keymap.insert(Shift, R, false, trigger(choose_rotate_tool));
-	keymap.insert(Control, R, false, trigger(choose_resize_tool));
-	keymap.insert(NONE, Z, true, trigger(undo));
-	keymap.insert(Shift, Z, true, trigger(redo));
-	keymap.insert(NONE, Escape, false, trigger(discard_draft));
-
-	keymap.insert(NONE, Space, false, discovery(hold_pan_tool, release_pan_tool));
-	keymap.insert(NONE, Control | Space, false, discovery(hold_zoom_tool, release_zoom_tool));
-	keymap.insert(NONE, Shift | Space, false, discovery(hold_orbit_tool, release_orbit_tool));
-	keymap.insert(NONE, Tab, false, discovery(hold_color_picker_tool, release_color_picker_tool));
+	keymap.set_category("Files");
+	keymap.insert(Control | Shift, S, false, "Save as", trigger(|app| app.execute(Command::SaveAs { file_path: None })));
+	keymap.insert(Control, S, false, "Save", trigger(|app| app.execute(Command::Save)));
+	keymap.insert(Control, O, false, "Open", trigger(|app| app.execute(Command::Open { file_path: None })));
+	keymap.insert(Control | Shift, E, false, "Export selection", trigger(export_selection));
+	keymap.insert(Control | Shift, P, false, "Export PDF", trigger(export_pdf));
+	keymap.insert(Control, E, false, "Quick export", trigger(quick_export));
+
+	keymap.set_category("Tabs");
+	keymap.insert(Control, N, false, "New tab", trigger(|app| app.execute(Command::New)));
+	keymap.insert(Control | Shift, N, false, "New tab from selection", trigger(new_file_from_selection));
+	keymap.insert(Control, D, false, "Duplicate tab", trigger(duplicate_tab));
+	keymap.insert(Control, W, false, "Close tab", trigger(|app| app.execute(Command::CloseTab)));
+	keymap.insert(Control, LeftArrow, false, "Previous tab", trigger(switch_tab_left));
+	keymap.insert(Control, RightArrow, false, "Next tab", trigger(switch_tab_right));
+	keymap.insert(Control | Shift, LeftArrow, false, "Move tab left", trigger(move_tab_left));
+	keymap.insert(Control | Shift, RightArrow, false, "Move tab right", trigger(move_tab_right));
+
+	keymap.set_category("View");
+	keymap.insert(NONE, Space, false, "Pan", discovery(hold_pan_tool, release_pan_tool));
+	keymap.insert(NONE, Control | Space, false, "Zoom", discovery(hold_zoom_tool, release_zoom_tool));
+	keymap.insert(NONE, Shift | Space, false, "Tilt", discovery(hold_orbit_tool, release_orbit_tool));
+	keymap.insert(NONE, LeftArrow, true, "Navigate back", trigger(navigate_back));
+	keymap.insert(NONE, RightArrow, true, "Navigate forward", trigger(navigate_forward));
+	keymap.insert(NONE, G, false, "Next image", trigger(find_next_image));
+	keymap.insert(Shift, G, false, "Next stroke of color", trigger(find_next_stroke_of_color));
+	keymap.insert(Control | Shift, F, false, "Toggle full screen", trigger(toggle_fullscreen));
+	keymap.insert(Control, F, false, "Toggle maximized", trigger(toggle_maximized));
+	keymap.insert(NONE, F1, false, "Shortcuts", trigger(toggle_shortcut_overlay));
+	keymap.insert(NONE, F2, false, "Status bar", trigger(toggle_status_bar));
+	keymap.insert(NONE, F3, false, "Debug view", trigger(toggle_debug_mode));
+	keymap.insert(Shift, F3, false, "Stroke order", trigger(toggle_stroke_order_overlay));
+	keymap.insert(NONE, F4, false, "Scale bar", trigger(toggle_scale_bar));
+	keymap.insert(Shift, F4, false, "Checkerboard", trigger(toggle_checkerboard));
+	keymap.insert(NONE, F5, false, "Presentation mode", trigger(toggle_presentation_mode));
+	keymap.insert(NONE, F6, false, "Replay canvas", trigger(replay_canvas));
+	keymap.insert(Shift, F6, false, "Export replay", trigger(export_replay));
+
+	keymap.set_category("Tools");
+	keymap.insert(NONE, S, false, "Select", trigger(|app| app.execute(EditCommand::SetTool(ToolKind::Select))));
+	keymap.insert(NONE, B, false, "Pen", trigger(|app| app.execute(EditCommand::SetTool(ToolKind::Draw))));
+	keymap.insert(NONE, E, false, "Eraser", trigger(|app| app.execute(EditCommand::SetTool(ToolKind::Erase))));
+	keymap.insert(NONE, T, false, "Translate", trigger(|app| app.execute(EditCommand::SetTool(ToolKind::Move))));
+	keymap.insert(Control, R, false, "Scale", trigger(|app| app.execute(EditCommand::SetTool(ToolKind::Resize))));
+	keymap.insert(Shift, R, false, "Rotate", trigger(|app| app.execute(EditCommand::SetTool(ToolKind::Rotate))));
+	keymap.insert(Shift, B, false, "Toggle screen-width pen", trigger(toggle_screen_width_pen));
+	keymap.insert(Control, B, false, "Toggle constant pressure", trigger(toggle_constant_pressure_pen));
+	keymap.insert(Control | Shift, B, false, "Toggle brush outline", trigger(toggle_brush_outline));
+	keymap.insert(Control, P, false, "Toggle page", trigger(toggle_page));
+	keymap.insert(NONE, L, false, "Toggle ruler", trigger(toggle_ruler));
+	keymap.insert(Shift, L, false, "Toggle baselines", trigger(toggle_baselines));
+	keymap.insert(Control, Semicolon, false, "Toggle snapping", trigger(toggle_snapping));
+	keymap.insert(NONE, M, false, "Cycle symmetry", trigger(cycle_symmetry));
+	keymap.insert(NONE, F8, false, "Calibrate pen", trigger(calibrate_pen));
+
+	keymap.set_category("Color");
+	keymap.insert(NONE, color_picker_key, false, "Pick color", discovery(hold_color_picker_tool, release_color_picker_tool));
+	keymap.insert(NONE, Shift | color_picker_key, false, "Pick background", discovery(hold_background_picker_tool, release_background_picker_tool));
+	keymap.insert(NONE, Q, false, "Swatch ring", discovery(hold_swatch_tool, release_swatch_tool));
+	keymap.insert(Shift, Q, false, "Import palette", trigger(import_palette));
+	keymap.insert(Control | Shift, Q, false, "Export palette", trigger(export_palette));
+	keymap.insert(Tab, S, false, "Cycle color scheme", trigger(cycle_color_scheme));
+	keymap.insert(Tab, R, false, "Recolor selection", trigger(|app| app.execute(EditCommand::RecolorSelection)));
+	keymap.insert(Tab, C, false, "Copy stroke style", trigger(copy_stroke_style));
+	keymap.insert(Tab, V, false, "Paste stroke style", trigger(paste_stroke_style));
+
+	keymap.set_category("Editing");
+	keymap.insert(Control, Z, true, "Undo", trigger(|app| app.execute(EditCommand::Undo)));
+	keymap.insert(Control, Y, true, "Redo", trigger(|app| app.execute(EditCommand::Redo)));
+	keymap.insert(Control | Shift, Y, false, "Switch redo branch", trigger(switch_redo_branch));
+	if are_bare_undo_keys_on {
+		keymap.insert(NONE, Z, true, "Undo", trigger(|app| app.execute(EditCommand::Undo)));
+		keymap.insert(Shift, Z, true, "Redo", trigger(|app| app.execute(EditCommand::Redo)));
+	}
+	keymap.insert(Control, X, false, "Cut", trigger(|app| app.execute(Command::Cut)));
+	keymap.insert(Control, C, false, "Copy", trigger(|app| app.execute(Command::Copy)));
+	keymap.insert(Control | Shift, C, false, "Copy as SVG", trigger(copy_as_svg));
+	keymap.insert(Control, V, false, "Paste", trigger(|app| app.execute(Command::Paste)));
+	keymap.insert(NONE, Backspace, false, "Delete selection", trigger(|app| app.execute(EditCommand::DeleteSelection)));
+	keymap.insert(NONE, Escape, false, "Discard draft", trigger(discard_draft));
+
+	keymap.set_category("Selection");
+	keymap.insert(NONE, A, false, "Select all", trigger(|app| app.execute(EditCommand::SelectAll)));
+	keymap.insert(Shift, A, false, "Select none", trigger(|app| app.execute(EditCommand::SelectNone)));
+	keymap.insert(NONE, C, true, "Cycle selection", trigger(cycle_selection));
+	keymap.insert(NONE, N, false, "Annotate", trigger(annotate_selection));
+	keymap.insert(Tab, F, false, "Frame images", trigger(frame_selection));
+	keymap.insert(Tab, P, false, "Toggle image filtering", trigger(pixelate_selection));
+	keymap.insert(Tab, T, false, "Trace images", trigger(trace_selection));
+	keymap.insert(NONE, H, false, "Hide selection", trigger(|app| app.execute(EditCommand::HideSelection)));
+	keymap.insert(Shift, H, false, "Show hidden objects", trigger(|app| app.execute(EditCommand::ShowHiddenObjects)));
+	keymap.insert(Shift, K, false, "Save as stamp", trigger(save_stamp));
+	keymap.insert(NONE, K, false, "Place stamp", trigger(place_stamp));
+
+	keymap
+}
+
+// Only navigation is available while presenting.
+pub fn presentation_keymap() -> Keymap {
+	let mut keymap = Keymap::new();
+	const NONE: EnumSet<Key> = EnumSet::EMPTY;
+	use Key::*;
+
+	keymap.set_category("Presentation");
+	keymap.insert(NONE, F5, false, "Exit presentation", trigger(toggle_presentation_mode));
+	keymap.insert(NONE, Escape, false, "Exit presentation", trigger(toggle_presentation_mode));
+	keymap.insert(Control, LeftArrow, false, "Previous tab", trigger(switch_tab_left));
+	keymap.insert(Control, RightArrow, false, "Next tab", trigger(switch_tab_right));
+	keymap.insert(NONE, LeftArrow, true, "Navigate back", trigger(navigate_back));
+	keymap.insert(NONE, RightArrow, true, "Navigate forward", trigger(navigate_forward));
+	keymap.insert(Control | Shift, F, false, "Toggle full screen", trigger(toggle_fullscreen));
+	keymap.insert(Control, F, false, "Toggle maximized", trigger(toggle_maximized));
+
+	keymap.insert(NONE, Space, false, "Pan", discovery(hold_pan_tool, release_pan_tool));
+	keymap.insert(NONE, Control | Space, false, "Zoom", discovery(hold_zoom_tool, release_zoom_tool));
+	keymap.insert(NONE, Shift | Space, false, "Tilt", discovery(hold_orbit_tool, release_orbit_tool));
 
 	keymap
 }
@@ -69,42 +158,173 @@ pub fn discovery(on_press: fn(&mut App), on_release: fn(&mut App)) -> Action {
 	Action::Discovery { on_press, on_release }
 }
 
+// What an action does, apart from the input that invokes it, so that it can also be issued without input, e.g. from a script.
+pub enum Command {
+	New,
+	// Opens or saves to the given file, or to one picked in a dialog if there is none.
+	Open { file_path: Option<PathBuf> },
+	Save,
+	SaveAs { file_path: Option<PathBuf> },
+	CloseTab,
+	Cut,
+	Copy,
+	Paste,
+	Edit(EditCommand),
+}
+
+// What a command does to the tabs and tools alone, without the window, renderer, or clipboard, so that it can be executed without them, as in tests.
+pub enum EditCommand {
+	SetTool(ToolKind),
+	SetColor(Srgba8),
+	RecolorSelection,
+	DeleteSelection,
+	SelectAll,
+	SelectNone,
+	HideSelection,
+	ShowHiddenObjects,
+	Undo,
+	Redo,
+}
+
+impl From<EditCommand> for Command {
+	fn from(command: EditCommand) -> Self {
+		Self::Edit(command)
+	}
+}
+
+impl App<'_> {
+	pub fn execute(&mut self, command: impl Into<Command>) {
+		match command.into() {
+			Command::New => new_file(self),
+			Command::Open { file_path } => load_from_file(self, file_path),
+			Command::Save => save_file(self),
+			Command::SaveAs { file_path } => save_as_file(self, file_path),
+			Command::CloseTab => close_tab(self),
+			Command::Cut => cut(self),
+			Command::Copy => copy(self),
+			Command::Paste => paste(self),
+			Command::Edit(command) => self.multicanvas.execute(command),
+		}
+	}
+}
+
+impl Multicanvas {
+	pub fn execute(&mut self, command: EditCommand) {
+		match command {
+			EditCommand::SetTool(kind) => {
+				if !self.set_tool_by_name(kind.key()) {
+					log::warn!("The {} tool is only held, and can't be switched to.", kind.key());
+				}
+			},
+			EditCommand::SetColor(Srgba8([r, g, b, a])) => {
+				if let Some(canvas) = self.current_canvas_mut() {
+					canvas.stroke_color = Srgb8([r, g, b]).to_hsva(f32::from(a) / 255.);
+				}
+			},
+			EditCommand::RecolorSelection => recolor_selection(self),
+			EditCommand::DeleteSelection => delete_selected_items(self),
+			EditCommand::SelectAll => select_all(self),
+			EditCommand::SelectNone => select_none(self),
+			EditCommand::HideSelection => hide_selection(self),
+			EditCommand::ShowHiddenObjects => show_hidden_objects(self),
+			EditCommand::Undo => undo(self),
+			EditCommand::Redo => redo(self),
+		}
+	}
+}
+
 // Actions:
 
 fn toggle_debug_mode(app: &mut App) {
 	app.multicanvas.is_debug_mode_on ^= true;
 }
 
-fn save_as_file(app: &mut App) {
-	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
-		if let Some(file_path) = rfd::FileDialog::new().add_filter("Inksy", &["inksy"]).save_file() {
-			if save_canvas_to_file(canvas, &app.renderer, &file_path).is_some() {
+fn toggle_stroke_order_overlay(app: &mut App) {
+	app.multicanvas.is_stroke_order_overlay_on ^= true;
+}
+
+fn toggle_status_bar(app: &mut App) {
+	app.multicanvas.is_status_bar_on ^= true;
+}
+
+fn toggle_scale_bar(app: &mut App) {
+	app.multicanvas.is_scale_bar_on ^= true;
+}
+
+fn toggle_checkerboard(app: &mut App) {
+	app.multicanvas.is_checkerboard_on ^= true;
+}
+
+fn toggle_brush_outline(app: &mut App) {
+	app.multicanvas.is_brush_outline_on ^= true;
+}
+
+fn toggle_baselines(app: &mut App) {
+	app.multicanvas.are_baselines_on ^= true;
+}
+
+fn toggle_snapping(app: &mut App) {
+	app.multicanvas.is_snapping_on ^= true;
+}
+
+fn toggle_presentation_mode(app: &mut App) {
+	if app.multicanvas.presentation.is_some() {
+		app.multicanvas.stop_presentation();
+	} else {
+		app.multicanvas.start_presentation(app.cursor_physical_position);
+	}
+	std::mem::swap(&mut app.keymap, &mut app.inactive_keymap);
+	app.multicanvas.update_cursor_visibility(app.window);
+}
+
+fn save_as_file(app: &mut App, file_path: Option<PathBuf>) {
+	if app.multicanvas.current_canvas().is_none() {
+		return;
+	}
+	if let Some(file_path) = file_path.or_else(|| rfd::FileDialog::new().add_filter("Inksy", &["inksy"]).save_file()) {
+		app.show_busy_indicator("Saving...");
+		let Some(canvas) = app.multicanvas.current_canvas_mut() else { return };
+		// A replay holds the strokes aside, so it is ended before they are written.
+		canvas.stop_replay();
+		match save_canvas_to_file(canvas, &app.renderer, &file_path) {
+			Ok(()) => {
 				canvas.file_path = Some(file_path).into();
 				canvas.set_retraction_count_at_save();
-			}
+			},
+			Err(error) => app.multicanvas.show_banner(error.to_string()),
 		}
 	}
 }
 
 fn save_file(app: &mut App) {
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	if canvas.file_path.is_none() {
+		save_as_file(app, None);
+		return;
+	}
+	app.show_busy_indicator("Saving...");
 	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		canvas.stop_replay();
 		if let Some(file_path) = canvas.file_path.as_ref().as_ref() {
-			if save_canvas_to_file(canvas, &app.renderer, file_path).is_some() {
-				canvas.set_retraction_count_at_save();
+			match save_canvas_to_file(canvas, &app.renderer, file_path) {
+				Ok(()) => canvas.set_retraction_count_at_save(),
+				Err(error) => app.multicanvas.show_banner(error.to_string()),
 			}
-		} else {
-			save_as_file(app);
 		}
 	}
 }
 
-fn load_from_file(app: &mut App) {
+fn load_from_file(app: &mut App, file_path: Option<PathBuf>) {
 	app.multicanvas.current_canvas_index.map(|current_canvas_index| app.multicanvas.canvases.get_mut(current_canvas_index).map(Canvas::invalidate));
-	if let Some(file_path) = rfd::FileDialog::new().add_filter("Inksy", &["inksy"]).pick_file() {
-		if let Some(canvas) = load_canvas_from_file(&mut app.renderer, file_path) {
-			let new_canvas_index = app.multicanvas.current_canvas_index.map_or(0, |x| x + 1);
-			app.multicanvas.canvases.insert(new_canvas_index, canvas);
-			app.multicanvas.current_canvas_index = Some(new_canvas_index);
+	if let Some(file_path) = file_path.or_else(|| rfd::FileDialog::new().add_filter("Inksy", &["inksy"]).pick_file()) {
+		app.show_busy_indicator("Loading...");
+		match load_canvas_from_file(&mut app.renderer, file_path) {
+			Ok(canvas) => {
+				let new_canvas_index = app.multicanvas.current_canvas_index.map_or(0, |x| x + 1);
+				app.multicanvas.canvases.insert(new_canvas_index, canvas);
+				app.multicanvas.current_canvas_index = Some(new_canvas_index);
+			},
+			Err(error) => app.multicanvas.show_banner(error.to_string()),
 		}
 	}
 	app.update_window_title();
@@ -118,7 +338,181 @@ fn new_file(app: &mut App) {
 	app.update_window_title();
 }
 
+fn new_file_from_selection(app: &mut App) {
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	let Some(new_canvas) = canvas_from_selection(canvas, app) else { return };
+
+	app.multicanvas.current_canvas_index.map(|current_canvas_index| app.multicanvas.canvases.get_mut(current_canvas_index).map(Canvas::invalidate));
+	let new_canvas_index = app.multicanvas.current_canvas_index.map_or(0, |x| x + 1);
+	app.multicanvas.canvases.insert(new_canvas_index, new_canvas);
+	app.multicanvas.current_canvas_index = Some(new_canvas_index);
+	app.update_window_title();
+}
+
+fn duplicate_tab(app: &mut App) {
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	let new_canvas = duplicate_canvas(canvas, app);
+
+	app.multicanvas.current_canvas_index.map(|current_canvas_index| app.multicanvas.canvases.get_mut(current_canvas_index).map(Canvas::invalidate));
+	let new_canvas_index = app.multicanvas.current_canvas_index.map_or(0, |x| x + 1);
+	app.multicanvas.canvases.insert(new_canvas_index, new_canvas);
+	app.multicanvas.current_canvas_index = Some(new_canvas_index);
+	app.update_window_title();
+}
+
+// Copies the contents and view of a canvas into a new, unsaved canvas without a file path or history.
+fn duplicate_canvas(canvas: &Canvas, app: &App) -> Canvas {
+	// Re-upload each referenced texture to the new canvas once; images whose textures can't be copied are left out.
+	let mut images = canvas.images().iter().map(|image| (**image).clone()).collect::<Vec<_>>();
+	let textures = gather_textures(&mut images, |image| image, |texture_index| reupload_texture(canvas, &app.renderer, texture_index));
+	let images = images.into_iter().map(Into::into).collect();
+
+	let strokes = canvas.strokes().iter().map(|stroke| Stroke::clone(stroke).into()).collect();
+
+	let mut new_canvas = Canvas::from_file(None, canvas.background_color, canvas.stroke_color.to_srgb().to_srgb8(), canvas.stroke_radius, *canvas.view, canvas.page, images, strokes, textures);
+	new_canvas.stroke_color = canvas.stroke_color;
+	new_canvas.symmetry = canvas.symmetry;
+	new_canvas.retraction_count_at_save = None;
+	new_canvas
+}
+
+fn export_selection(app: &mut App) {
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	if !canvas.strokes().iter().any(|stroke| stroke.is_selected) && !canvas.images().iter().any(|image| image.is_selected) {
+		return;
+	}
+
+	if let Some(file_path) = rfd::FileDialog::new().add_filter("Inksy", &["inksy"]).save_file() {
+		export(app, ExportKind::Selection, file_path);
+	}
+}
+
+fn export_pdf(app: &mut App) {
+	if app.multicanvas.current_canvas().is_none() {
+		return;
+	}
+
+	if let Some(file_path) = rfd::FileDialog::new().add_filter("PDF", &["pdf"]).save_file() {
+		export(app, ExportKind::Pdf, file_path);
+	}
+}
+
+// Exports the current canvas to a file, remembering how for quick export if it succeeds.
+fn export(app: &mut App, kind: ExportKind, file_path: PathBuf) {
+	if let ExportKind::Replay = kind {
+		app.show_busy_indicator("Exporting...");
+	}
+
+	app.multicanvas.current_canvas_mut().map(Canvas::stop_replay);
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	let result = match kind {
+		ExportKind::Selection => {
+			let Some(new_canvas) = canvas_from_selection(canvas, app) else {
+				app.multicanvas.show_banner("Nothing is selected to export.".to_owned());
+				return;
+			};
+			save_canvas_to_file(&new_canvas, &app.renderer, &file_path)
+		},
+		ExportKind::Pdf => export_canvas_to_pdf(canvas, &app.renderer, &file_path),
+		ExportKind::Replay => export_replay_to_gif(canvas, &app.renderer, &file_path, app.config.replay_export),
+	};
+
+	match result {
+		Ok(()) => {
+			if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+				canvas.last_export = Some((kind, file_path));
+			}
+		},
+		Err(error) => app.multicanvas.show_banner(error.to_string()),
+	}
+}
+
+// Repeats the last export of the current canvas to the same file, without prompting.
+fn quick_export(app: &mut App) {
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	match canvas.last_export.clone() {
+		Some((kind, file_path)) => export(app, kind, file_path),
+		None => app.multicanvas.show_banner("Nothing has been exported from this canvas yet.".to_owned()),
+	}
+}
+
+// Copies the selected objects into a new canvas, positioned relative to their centroid.
+fn canvas_from_selection(canvas: &Canvas, app: &App) -> Option<Canvas> {
+	// Compute the centroid.
+	let (sum, count) = canvas.strokes().iter().fold((Vex::ZERO, 0), |(sum, count), stroke| if stroke.is_selected { (sum + stroke.position, count + 1) } else { (sum, count) });
+	let (sum, count) = canvas.images().iter().fold((sum, count), |(sum, count), image| if image.is_selected { (sum + image.position, count + 1) } else { (sum, count) });
+	if count == 0 {
+		return None;
+	}
+	let center = sum / count as f32;
+
+	let mut new_canvas = Canvas::new(&app.config);
+
+	// Re-upload each referenced texture to the new canvas once.
+	let mut images = canvas
+		.images()
+		.iter()
+		.filter(|image| image.is_selected)
+		.map(|image| Image {
+			position: image.position - center,
+			is_selected: false,
+			..(*image).clone()
+		})
+		.collect::<Vec<_>>();
+	let textures = gather_textures(&mut images, |image| image, |texture_index| reupload_texture(canvas, &app.renderer, texture_index));
+	new_canvas.textures.extend(textures.into_iter().map(Some));
+
+	let strokes = canvas
+		.strokes()
+		.iter()
+		.filter(|stroke| stroke.is_selected)
+		.map(|stroke| {
+			Stroke {
+				position: stroke.position - center,
+				is_selected: false,
+				..(*stroke).clone()
+			}
+			.into()
+		})
+		.collect::<Vec<_>>();
+
+	if !images.is_empty() {
+		new_canvas.perform_operation(Operation::CommitImages {
+			images: images.into_iter().map(Into::into).collect(),
+		});
+	}
+
+	if !strokes.is_empty() {
+		new_canvas.perform_operation(Operation::CommitStrokes { strokes });
+	}
+
+	Some(new_canvas)
+}
+
 fn close_tab(app: &mut App) {
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	if !canvas.has_unsaved_changes() {
+		discard_tab(app);
+	} else if app.multicanvas.should_auto_save_on_close && canvas.file_path.is_some() {
+		save_and_close_tab(app);
+	} else {
+		app.multicanvas.confirmation = Some(Confirmation {
+			prompt: "This tab has unsaved changes.",
+			on_save: save_and_close_tab,
+			on_discard: discard_tab,
+		});
+	}
+}
+
+// Saves the current tab, then closes it unless saving failed or was cancelled.
+fn save_and_close_tab(app: &mut App) {
+	save_file(app);
+	if app.multicanvas.current_canvas().is_some_and(Canvas::is_saved) {
+		discard_tab(app);
+	}
+}
+
+fn discard_tab(app: &mut App) {
 	if let Some(current_canvas_index) = app.multicanvas.current_canvas_index {
 		app.multicanvas.canvases.get_mut(current_canvas_index).map(Canvas::invalidate);
 		app.multicanvas.canvases.remove(current_canvas_index);
@@ -131,6 +525,48 @@ fn close_tab(app: &mut App) {
 	app.update_window_title();
 }
 
+// Exits once no tab has unsaved changes, or asks whether to save or discard them.
+pub fn request_exit(app: &mut App) {
+	// Replays hold their strokes aside, so they are ended before anything is saved or closed.
+	app.multicanvas.canvases.iter_mut().for_each(Canvas::stop_replay);
+	if app.multicanvas.should_auto_save_on_close {
+		for index in 0..app.multicanvas.canvases.len() {
+			let canvas = &app.multicanvas.canvases[index];
+			if canvas.has_unsaved_changes() && canvas.file_path.is_some() {
+				focus_tab(app, index);
+				save_file(app);
+			}
+		}
+	}
+	if app.multicanvas.canvases.iter().any(Canvas::has_unsaved_changes) {
+		app.multicanvas.confirmation = Some(Confirmation {
+			prompt: "Some tabs have unsaved changes.",
+			on_save: save_all_and_exit,
+			on_discard: |app| app.is_exiting = true,
+		});
+	} else {
+		app.is_exiting = true;
+	}
+	app.update_window_title();
+}
+
+// Saves every tab with unsaved changes, then exits unless any of them failed or was cancelled.
+fn save_all_and_exit(app: &mut App) {
+	for index in 0..app.multicanvas.canvases.len() {
+		if app.multicanvas.canvases[index].has_unsaved_changes() {
+			focus_tab(app, index);
+			save_file(app);
+		}
+	}
+	app.is_exiting = !app.multicanvas.canvases.iter().any(Canvas::has_unsaved_changes);
+	app.update_window_title();
+}
+
+fn focus_tab(app: &mut App, index: usize) {
+	app.multicanvas.current_canvas_index.map(|current_canvas_index| app.multicanvas.canvases.get_mut(current_canvas_index).map(Canvas::invalidate));
+	app.multicanvas.current_canvas_index = Some(index);
+}
+
 fn switch_tab_left(app: &mut App) {
 	if let Some(current_canvas_index) = app.multicanvas.current_canvas_index {
 		app.multicanvas.canvases.get_mut(current_canvas_index).map(Canvas::invalidate);
@@ -151,28 +587,147 @@ fn switch_tab_right(app: &mut App) {
 	app.update_window_title();
 }
 
+fn move_tab_left(app: &mut App) {
+	if let Some(current_canvas_index) = app.multicanvas.current_canvas_index {
+		let canvas_count = app.multicanvas.canvases.len();
+		move_tab(app, current_canvas_index, current_canvas_index.checked_sub(1).unwrap_or(canvas_count - 1));
+	}
+}
+
+fn move_tab_right(app: &mut App) {
+	if let Some(current_canvas_index) = app.multicanvas.current_canvas_index {
+		let canvas_count = app.multicanvas.canvases.len();
+		move_tab(app, current_canvas_index, (current_canvas_index + 1) % canvas_count);
+	}
+}
+
+// Moves the current tab to a new index, wrapping around like switching tabs does.
+fn move_tab(app: &mut App, current_canvas_index: usize, new_canvas_index: usize) {
+	let mut canvas = app.multicanvas.canvases.remove(current_canvas_index);
+	canvas.invalidate();
+	app.multicanvas.canvases.insert(new_canvas_index, canvas);
+	app.multicanvas.current_canvas_index = Some(new_canvas_index);
+}
+
+fn navigate_back(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		canvas.navigate_back();
+	}
+}
+
+fn navigate_forward(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		canvas.navigate_forward();
+	}
+}
+
+fn replay_canvas(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		canvas.start_replay();
+	}
+}
+
+fn export_replay(app: &mut App) {
+	if app.multicanvas.current_canvas().is_none() {
+		return;
+	}
+	if let Some(file_path) = rfd::FileDialog::new().add_filter("GIF", &["gif"]).save_file() {
+		export(app, ExportKind::Replay, file_path);
+	}
+}
+
+fn find_next_image(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		canvas.find_next(FindTarget::Images);
+	}
+}
+
+fn find_next_stroke_of_color(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		let color = canvas.stroke_color.to_srgba8();
+		canvas.find_next(FindTarget::StrokesOfColor(color));
+	}
+}
+
+// Closes the shortcut overlay if it is open, or stops calibrating the pen, and otherwise discards the current draft.
 fn discard_draft(app: &mut App) {
-	app.multicanvas.mode_stack.discard_draft();
+	if app.multicanvas.shortcut_overlay.take().is_none() && !std::mem::take(&mut app.multicanvas.is_calibrating_pen) {
+		app.multicanvas.mode_stack.discard_draft();
+	}
 }
 
-fn choose_draw_tool(app: &mut App) {
-	app.multicanvas.mode_stack.switch_draw();
+fn calibrate_pen(app: &mut App) {
+	app.multicanvas.is_calibrating_pen = true;
 }
 
-fn choose_select_tool(app: &mut App) {
-	app.multicanvas.mode_stack.switch_select();
+fn toggle_shortcut_overlay(app: &mut App) {
+	app.multicanvas.shortcut_overlay = match app.multicanvas.shortcut_overlay {
+		Some(_) => None,
+		None => Some(app.keymap.binding_groups()),
+	};
 }
 
-fn choose_move_tool(app: &mut App) {
-	app.multicanvas.mode_stack.switch_move();
+fn toggle_screen_width_pen(app: &mut App) {
+	app.multicanvas.is_screen_width_pen_on ^= true;
 }
 
-fn choose_rotate_tool(app: &mut App) {
-	app.multicanvas.mode_stack.switch_rotate();
+fn toggle_constant_pressure_pen(app: &mut App) {
+	app.multicanvas.is_constant_pressure_pen_on ^= true;
 }
 
-fn choose_resize_tool(app: &mut App) {
-	app.multicanvas.mode_stack.switch_resize();
+// Adds a page centered on the view, or removes the page.
+fn toggle_page(app: &mut App) {
+	let page_size = app.config.page_size;
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		canvas.page = match canvas.page {
+			Some(_) => None,
+			None => Some(Page {
+				position: canvas.view.position - page_size / 2.,
+				dimensions: page_size,
+			}),
+		};
+	}
+}
+
+fn toggle_ruler(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		canvas.ruler = match canvas.ruler {
+			Some(_) => None,
+			None => Some(centered_ruler(&canvas.view)),
+		};
+	}
+}
+
+fn cycle_symmetry(app: &mut App) {
+	let segment_count = app.config.radial_symmetry_segments;
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		let kind = match canvas.symmetry.map(|symmetry| symmetry.kind) {
+			None => Some(SymmetryKind::Vertical),
+			Some(SymmetryKind::Vertical) => Some(SymmetryKind::Horizontal),
+			Some(SymmetryKind::Horizontal) => Some(SymmetryKind::Radial { segment_count }),
+			Some(SymmetryKind::Radial { .. }) => None,
+		};
+		canvas.symmetry = kind.map(|kind| Symmetry { kind, center: canvas.view.position });
+	}
+}
+
+// Switches to the next color scheme, applying it to the current canvas and the selection highlight.
+fn cycle_color_scheme(app: &mut App) {
+	let color_schemes = &app.config.color_schemes;
+	if color_schemes.is_empty() {
+		return;
+	}
+	let index = app.multicanvas.color_scheme_index.map_or(0, |index| (index + 1) % color_schemes.len());
+	let color_scheme = &color_schemes[index];
+	app.multicanvas.color_scheme_index = Some(index);
+	app.multicanvas.selection_color = color_scheme.selection_color;
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		if canvas.background_color.0 != color_scheme.background_color.0 {
+			canvas.perform_coalescing_operation(Operation::SetBackground { new_color: color_scheme.background_color }, app.config.undo_coalescing);
+		}
+		canvas.stroke_color = color_scheme.stroke_color.to_hsva(1.);
+	}
+	app.multicanvas.show_banner(format!("Color scheme: {}", color_scheme.name));
 }
 
 fn hold_pan_tool(app: &mut App) {
@@ -199,13 +754,26 @@ fn release_orbit_tool(app: &mut App) {
 	app.multicanvas.mode_stack.switch_transient(TransientModeSwitch::Orbit { should_orbit: false });
 }
 
+// Replaces the colors of the swatch ring with those of a GIMP palette file, until the app is closed.
+fn import_palette(app: &mut App) {
+	let Some(file_path) = rfd::FileDialog::new().add_filter("GIMP palette", &["gpl"]).pick_file() else { return };
+	match std::fs::read_to_string(file_path).ok().as_deref().and_then(parse_gpl) {
+		Some(palette) if !palette.is_empty() => app.multicanvas.palette = palette,
+		_ => app.multicanvas.show_banner("The palette could not be read.".to_owned()),
+	}
+}
+
+fn export_palette(app: &mut App) {
+	let Some(file_path) = rfd::FileDialog::new().add_filter("GIMP palette", &["gpl"]).save_file() else { return };
+	let name = file_path.file_stem().map_or_else(|| "Inksy".into(), |name| name.to_string_lossy());
+	if let Err(error) = std::fs::write(&file_path, write_gpl(&name, &app.multicanvas.palette)) {
+		app.multicanvas.show_banner(error.to_string());
+	}
+}
+
 fn hold_color_picker_tool(app: &mut App) {
 	app.multicanvas.mode_stack.switch_transient(TransientModeSwitch::Color {
-		center: Some(if app.is_cursor_relevant {
-			app.cursor_physical_position
-		} else {
-			Vex([app.renderer.config.width as f32 / 2., app.renderer.config.height as f32 / 2.].map(Px))
-		}),
+		center: Some((color_picker_center(app), ColorTarget::Stroke)),
 	});
 }
 
@@ -213,8 +781,61 @@ fn release_color_picker_tool(app: &mut App) {
 	app.multicanvas.mode_stack.switch_transient(TransientModeSwitch::Color { center: None });
 }
 
-fn delete_selected_items(app: &mut App) {
-	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+fn hold_background_picker_tool(app: &mut App) {
+	let center = color_picker_center(app);
+	if let Some(canvas) = app.multicanvas.current_canvas() {
+		let original = canvas.background_color;
+		app.multicanvas.mode_stack.switch_transient(TransientModeSwitch::Color {
+			center: Some((center, ColorTarget::Background { original, draft: original.to_hsva(1.) })),
+		});
+	}
+}
+
+fn release_background_picker_tool(app: &mut App) {
+	if let &Tool::PickColor {
+		target: ColorTarget::Background { original, draft },
+		..
+	} = app.multicanvas.mode_stack.get()
+	{
+		if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+			// Restore the original color so that the change is recorded as a single operation.
+			canvas.background_color = original;
+			let new_color = draft.to_srgb().to_srgb8();
+			if new_color.0 != original.0 {
+				canvas.perform_coalescing_operation(Operation::SetBackground { new_color }, app.config.undo_coalescing);
+			}
+		}
+	}
+	app.multicanvas.mode_stack.switch_transient(TransientModeSwitch::Color { center: None });
+}
+
+// The swatch ring always opens under the cursor, since it is picked from by direction.
+fn hold_swatch_tool(app: &mut App) {
+	let center = if app.is_cursor_relevant {
+		app.cursor_physical_position
+	} else {
+		Vex([app.renderer.config.width as f32 / 2., app.renderer.config.height as f32 / 2.].map(Px))
+	};
+	app.multicanvas.mode_stack.switch_transient(TransientModeSwitch::Swatch { center: Some(center) });
+}
+
+fn release_swatch_tool(app: &mut App) {
+	if let &Tool::PickSwatch { hovered_index: Some(index), .. } = app.multicanvas.mode_stack.get() {
+		if let Some(&color) = app.multicanvas.palette.get(index) {
+			app.execute(EditCommand::SetColor(color));
+		}
+	}
+	app.multicanvas.mode_stack.switch_transient(TransientModeSwitch::Swatch { center: None });
+}
+
+fn color_picker_center(app: &App) -> Vex<2, Px> {
+	let window_dimensions = Vex([app.renderer.config.width as f32, app.renderer.config.height as f32].map(Px));
+	let cursor_center = if app.is_cursor_relevant { app.cursor_physical_position } else { window_dimensions / 2. };
+	color_selector_center(app.config.color_picker_placement, cursor_center, window_dimensions, app.scale)
+}
+
+fn delete_selected_items(multicanvas: &mut Multicanvas) {
+	if let Some(canvas) = multicanvas.current_canvas_mut() {
 		let selected_image_indices = canvas.images().iter().enumerate().filter_map(|(index, image)| if image.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
 
 		let selected_stroke_indices = canvas.strokes().iter().enumerate().filter_map(|(index, stroke)| if stroke.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
@@ -258,29 +879,37 @@ fn toggle_maximized(app: &mut App) {
 	app.window.set_maximized(!app.window.is_maximized());
 }
 
-fn undo(app: &mut App) {
-	if app.multicanvas.mode_stack.is_drafting() {
-		app.multicanvas.mode_stack.discard_draft();
-	} else if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+fn undo(multicanvas: &mut Multicanvas) {
+	if multicanvas.mode_stack.is_drafting() {
+		multicanvas.mode_stack.discard_draft();
+	} else if let Some(canvas) = multicanvas.current_canvas_mut() {
 		canvas.undo();
 	}
 }
 
-fn redo(app: &mut App) {
-	if app.multicanvas.mode_stack.is_drafting() {
-		app.multicanvas.mode_stack.discard_draft();
-	} else if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+fn redo(multicanvas: &mut Multicanvas) {
+	if multicanvas.mode_stack.is_drafting() {
+		multicanvas.mode_stack.discard_draft();
+	} else if let Some(canvas) = multicanvas.current_canvas_mut() {
 		canvas.redo();
 	}
 }
 
+// Switches what would be redone to another line of edits that was set aside by editing after undoing to this point.
+fn switch_redo_branch(app: &mut App) {
+	let Some(canvas) = app.multicanvas.current_canvas_mut() else { return };
+	let text = match canvas.switch_redo_branch() {
+		Some(count) => format!("Switched to another of {} redo branches.", count + 1),
+		None => "No other redo branches fork here.".to_owned(),
+	};
+	app.multicanvas.show_banner(text);
+}
+
 fn cut(app: &mut App) {
 	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
-		let semidimensions = Vex([app.renderer.config.width as f32 / 2., app.renderer.config.height as f32 / 2.].map(Px)).s(app.scale).z(canvas.view.zoom);
-		let cursor_virtual_position = (app.cursor_physical_position.s(app.scale).z(canvas.view.zoom) - semidimensions).rotate(-canvas.view.tilt);
-		let offset = cursor_virtual_position + canvas.view.position;
+		let offset = canvas.view.canvas_position(app.cursor_physical_position, [app.renderer.config.width, app.renderer.config.height], app.scale);
 
-		let (image_indices, images): (Vec<_>, Vec<_>) = canvas
+		let mut index_image_pairs: Vec<_> = canvas
 			.images()
 			.iter()
 			.enumerate()
@@ -297,7 +926,7 @@ fn cut(app: &mut App) {
 					None
 				}
 			})
-			.unzip();
+			.collect();
 
 		let (stroke_indices, strokes): (Vec<_>, Vec<_>) = canvas
 			.strokes()
@@ -318,25 +947,36 @@ fn cut(app: &mut App) {
 			})
 			.unzip();
 
-		if !stroke_indices.is_empty() {
+		// Images whose textures can't be read back are left on the canvas rather than lost.
+		let textures = copy_textures(canvas, &app.renderer, &mut index_image_pairs, |(_, image)| image);
+		let (image_indices, images): (Vec<_>, Vec<_>) = index_image_pairs.into_iter().unzip();
+
+		if !image_indices.is_empty() || !stroke_indices.is_empty() {
 			canvas.perform_operation(Operation::DeleteObjects {
 				monotone_image_indices: image_indices,
 				monotone_stroke_indices: stroke_indices,
 			});
 		}
 
-		app.clipboard_contents = Some(ClipboardContents::Subcanvas(images, strokes));
-		app.clipboard.write(ClipboardData::Custom);
+		app.clipboard_contents = Some(ClipboardContents::Subcanvas(images, strokes, textures));
+		write_clipboard(app, ClipboardData::Custom);
+	}
+}
+
+// Places the selected strokes on the clipboard as SVG markup, for other applications; images are left out.
+fn copy_as_svg(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas() {
+		if let Some(svg) = strokes_to_svg(canvas.strokes().iter().filter(|stroke| stroke.is_selected).map(AsRef::as_ref)) {
+			write_clipboard(app, ClipboardData::Text(svg));
+		}
 	}
 }
 
 fn copy(app: &mut App) {
 	if let Some(canvas) = app.multicanvas.current_canvas_index.and_then(|x| app.multicanvas.canvases.get(x)) {
-		let semidimensions = Vex([app.renderer.config.width as f32 / 2., app.renderer.config.height as f32 / 2.].map(Px)).s(app.scale).z(canvas.view.zoom);
-		let cursor_virtual_position = (app.cursor_physical_position.s(app.scale).z(canvas.view.zoom) - semidimensions).rotate(-canvas.view.tilt);
-		let offset = cursor_virtual_position + canvas.view.position;
+		let offset = canvas.view.canvas_position(app.cursor_physical_position, [app.renderer.config.width, app.renderer.config.height], app.scale);
 
-		let images: Vec<_> = canvas
+		let mut images: Vec<_> = canvas
 			.images()
 			.iter()
 			.filter_map(|image| {
@@ -366,22 +1006,87 @@ fn copy(app: &mut App) {
 			})
 			.collect();
 
-		app.clipboard_contents = Some(ClipboardContents::Subcanvas(images, strokes));
-		app.clipboard.write(ClipboardData::Custom);
+		let textures = copy_textures(canvas, &app.renderer, &mut images, |image| image);
+
+		app.clipboard_contents = Some(ClipboardContents::Subcanvas(images, strokes, textures));
+		write_clipboard(app, ClipboardData::Custom);
+	}
+}
+
+// Reads back the textures of copied images once each, pointing the images at the copies; images whose textures can't be read are left out.
+fn copy_textures<I>(canvas: &Canvas, renderer: &Renderer, images: &mut Vec<I>, image: impl Fn(&mut I) -> &mut Image) -> Vec<ClipboardTexture> {
+	gather_textures(images, image, |texture_index| {
+		let Some(Some(texture)) = canvas.textures.get(texture_index) else { return None };
+		let (Some(width), Some(height)) = (NonZero::new(texture.extent.width), NonZero::new(texture.extent.height)) else { return None };
+		Some(ClipboardTexture {
+			source: texture.texture.global_id(),
+			dimensions: [width, height],
+			data: renderer.read_texture(texture)?,
+		})
+	})
+}
+
+// Reads back a texture of a canvas and uploads it again, for another canvas.
+fn reupload_texture(canvas: &Canvas, renderer: &Renderer, texture_index: usize) -> Option<Texture> {
+	let Some(Some(texture)) = canvas.textures.get(texture_index) else { return None };
+	let (Some(width), Some(height)) = (NonZero::new(texture.extent.width), NonZero::new(texture.extent.height)) else { return None };
+	Some(renderer.create_texture([width, height], renderer.read_texture(texture)?))
+}
+
+// Copies the texture at each index that images refer to once, and points the images at the copies.
+// Images are found in items by `image`, and items whose textures can't be copied, including those with indices the canvas doesn't hold, are left out.
+fn gather_textures<I, T>(items: &mut Vec<I>, image: impl Fn(&mut I) -> &mut Image, mut copy_texture: impl FnMut(usize) -> Option<T>) -> Vec<T> {
+	// Textures that fail to copy are remembered too, so that they aren't tried again.
+	let mut revised_texture_indices = HashMap::new();
+	let mut textures = Vec::new();
+	items.retain_mut(|item| {
+		let image = image(item);
+		let revised_texture_index = *revised_texture_indices.entry(image.texture_index).or_insert_with(|| {
+			let texture = copy_texture(image.texture_index)?;
+			textures.push(texture);
+			Some(textures.len() - 1)
+		});
+		revised_texture_index.map(|texture_index| image.texture_index = texture_index).is_some()
+	});
+	textures
+}
+
+// The canvas texture index of each copied texture: where the canvas still holds the texture it was read from, if it does, and otherwise that of a new upload.
+fn place_textures<T>(textures: &[T], held_indices: Vec<Option<usize>>, mut upload: impl FnMut(&T) -> usize) -> Vec<usize> {
+	textures.iter().zip(held_indices).map(|(texture, held_index)| held_index.unwrap_or_else(|| upload(texture))).collect()
+}
+
+fn write_clipboard(app: &App, data: ClipboardData) {
+	if let Some(clipboard) = &app.clipboard {
+		clipboard.write(data);
+	}
+}
+
+// Without a system clipboard, whatever was last copied in process is pasted.
+fn read_clipboard(app: &App) -> Option<ClipboardData> {
+	match &app.clipboard {
+		Some(clipboard) => clipboard.read(),
+		None => app.clipboard_contents.as_ref().map(|_| ClipboardData::Custom),
 	}
 }
 
 fn paste(app: &mut App) {
+	let is_snapping_on = app.multicanvas.is_snapping_on;
+	let clipboard_data = read_clipboard(app);
 	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
-		match app.clipboard.read() {
+		match clipboard_data {
 			Some(ClipboardData::Custom) => {
-				if let Some(ClipboardContents::Subcanvas(images, strokes)) = app.clipboard_contents.as_ref() {
-					let semidimensions = Vex([app.renderer.config.width as f32 / 2., app.renderer.config.height as f32 / 2.].map(Px)).s(app.scale).z(canvas.view.zoom);
-					let cursor_virtual_position = (app.cursor_physical_position.s(app.scale).z(canvas.view.zoom) - semidimensions).rotate(-canvas.view.tilt);
-
+				if let Some(ClipboardContents::Subcanvas(images, strokes, textures)) = app.clipboard_contents.as_ref() {
 					canvas.select_all(false);
 
-					let offset = cursor_virtual_position + canvas.view.position;
+					let offset = canvas.view.canvas_position(app.cursor_physical_position, [app.renderer.config.width, app.renderer.config.height], app.scale);
+
+					// Textures still held by the canvas, as when pasting into the canvas they were copied from, are reused rather than uploaded again.
+					let held_indices = textures
+						.iter()
+						.map(|clipboard_texture| canvas.textures.iter().position(|texture| texture.as_ref().is_some_and(|texture| texture.texture.global_id() == clipboard_texture.source)))
+						.collect();
+					let texture_indices = place_textures(textures, held_indices, |clipboard_texture| canvas.push_texture(&app.renderer, clipboard_texture.dimensions, clipboard_texture.data.clone()));
 
 					if !images.is_empty() {
 						canvas.perform_operation(Operation::CommitImages {
@@ -390,6 +1095,7 @@ fn paste(app: &mut App) {
 								.map(|image| {
 									Image {
 										position: image.position + offset,
+										texture_index: texture_indices[image.texture_index],
 										is_selected: true,
 										..image.clone()
 									}
@@ -421,15 +1127,28 @@ fn paste(app: &mut App) {
 				let Ok(height) = NonZero::try_from(dimensions[1]) else { break 'empty };
 				let dimensions = [width, height];
 				let texture_index = canvas.push_texture(&app.renderer, dimensions, data);
+				let dimensions = Vex(dimensions.map(|x| Vx(x.get() as f32)));
+				// Images are positioned by their centers, so it is their top-left corners that are snapped.
+				let position = if app.config.snap_pasted_images && is_snapping_on {
+					(canvas.view.position - dimensions / 2.).map(|x| Vx(x.0.round())) + dimensions / 2.
+				} else {
+					canvas.view.position
+				};
 
 				canvas.perform_operation(Operation::CommitImages {
 					images: vec![Image {
 						texture_index,
-						dimensions: Vex(dimensions.map(|x| Vx(x.get() as f32))),
-						position: canvas.view.position,
+						dimensions,
+						position,
 						orientation: canvas.view.tilt,
 						dilation: 1.,
 						is_selected: false,
+						is_visible: true,
+						note: None,
+						border_width: app.config.default_image_border_width,
+						border_color: app.config.default_image_border_color,
+						is_pixelated: app.config.pixelated_images,
+						world_aabb_cache: Default::default(),
 					}
 					.into()],
 				});
@@ -439,27 +1158,469 @@ fn paste(app: &mut App) {
 	}
 }
 
-fn select_all(app: &mut App) {
-	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+fn save_stamp(app: &mut App) {
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	if canvas.strokes().iter().any(|stroke| stroke.is_selected) || canvas.images().iter().any(|image| image.is_selected) {
+		app.multicanvas.text_capture = Some(TextCapture {
+			prompt: "Stamp name",
+			text: String::new(),
+			on_commit: save_selection_as_stamp,
+		});
+	}
+}
+
+// Saves the selection, centered on its centroid, as a canvas in the stamp library, replacing any stamp of the same name.
+fn save_selection_as_stamp(app: &mut App, name: String) {
+	let name = name.trim();
+	if name.is_empty() || name.contains(['/', '\\', ':', '*', '?', '"', '<', '>', '|']) {
+		app.multicanvas.show_banner("Stamp names can't be empty or contain any of / \\ : * ? \" < > |".to_owned());
+		return;
+	}
+	let Some(stamp_directory_path) = stamp_directory_path() else {
+		app.multicanvas.show_banner("The stamp library couldn't be opened.".to_owned());
+		return;
+	};
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	let Some(stamp) = canvas_from_selection(canvas, app) else { return };
+
+	match save_canvas_to_file(&stamp, &app.renderer, &stamp_directory_path.join(format!("{name}.inksy"))) {
+		Ok(()) => app.multicanvas.show_banner(format!("Saved stamp \"{name}\".")),
+		Err(error) => app.multicanvas.show_banner(error.to_string()),
+	}
+}
+
+// Picks a stamp from the library and places its objects, selected, centered on the cursor.
+fn place_stamp(app: &mut App) {
+	if app.multicanvas.current_canvas().is_none() {
+		return;
+	}
+	let Some(stamp_directory_path) = stamp_directory_path() else {
+		app.multicanvas.show_banner("The stamp library couldn't be opened.".to_owned());
+		return;
+	};
+	let Some(file_path) = rfd::FileDialog::new().set_directory(stamp_directory_path).add_filter("Inksy", &["inksy"]).pick_file() else {
+		return;
+	};
+	let stamp = match load_canvas_from_file(&mut app.renderer, file_path) {
+		Ok(stamp) => stamp,
+		Err(error) => {
+			app.multicanvas.show_banner(error.to_string());
+			return;
+		},
+	};
+
+	let Some(canvas) = app.multicanvas.current_canvas_mut() else { return };
+	let offset = canvas.view.canvas_position(app.cursor_physical_position, [app.renderer.config.width, app.renderer.config.height], app.scale);
+	stamp_canvas(canvas, stamp, offset);
+}
+
+// Moves the objects of a stamp onto a canvas, selected, with the stamp's origin at an offset.
+fn stamp_canvas(canvas: &mut Canvas, stamp: Canvas, offset: Vex<2, Vx>) {
+	// The stamp's textures are moved onto the canvas as they are, so only the indices that refer to them change.
+	let Canvas { images, strokes, textures, .. } = stamp;
+	let texture_indices = textures
+		.into_iter()
+		.map(|texture| {
+			canvas.textures.push(texture);
+			canvas.textures.len() - 1
+		})
+		.collect::<Vec<_>>();
+
+	canvas.select_all(false);
+	if !images.is_empty() {
+		canvas.perform_operation(Operation::CommitImages {
+			images: images
+				.iter()
+				.map(|image| {
+					Image {
+						texture_index: texture_indices[image.texture_index],
+						position: image.position + offset,
+						is_selected: true,
+						..Image::clone(image)
+					}
+					.into()
+				})
+				.collect(),
+		});
+	}
+	if !strokes.is_empty() {
+		canvas.perform_operation(Operation::CommitStrokes {
+			strokes: strokes
+				.iter()
+				.map(|stroke| {
+					Stroke {
+						position: stroke.position + offset,
+						is_selected: true,
+						..Stroke::clone(stroke)
+					}
+					.into()
+				})
+				.collect(),
+		});
+	}
+}
+
+fn select_all(multicanvas: &mut Multicanvas) {
+	if let Some(canvas) = multicanvas.current_canvas_mut() {
 		canvas.select_all(true);
 	}
 }
 
-fn select_none(app: &mut App) {
-	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+fn select_none(multicanvas: &mut Multicanvas) {
+	if let Some(canvas) = multicanvas.current_canvas_mut() {
 		canvas.select_all(false);
 	}
 }
 
-fn recolor_selection(app: &mut App) {
+fn cycle_selection(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		canvas.cycle_selection();
+	}
+}
+
+fn recolor_selection(multicanvas: &mut Multicanvas) {
+	let undo_coalescing = multicanvas.undo_coalescing;
+	if let Some(canvas) = multicanvas.current_canvas_mut() {
+		let selected_indices = canvas.strokes().iter().enumerate().filter_map(|(index, stroke)| if stroke.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
+
+		if !selected_indices.is_empty() {
+			canvas.perform_coalescing_operation(
+				Operation::RecolorStrokes {
+					indices: selected_indices,
+					new_color: canvas.stroke_color.to_srgba8(),
+				},
+				undo_coalescing,
+			);
+		}
+	}
+}
+
+// Copies the style of the topmost selected stroke.
+fn copy_stroke_style(app: &mut App) {
+	let Some(canvas) = app.multicanvas.current_canvas() else { return };
+	match canvas.strokes().iter().rev().find(|stroke| stroke.is_selected) {
+		Some(stroke) => app.copied_stroke_style = Some(stroke.style()),
+		None => app.multicanvas.show_banner("No stroke is selected to copy the style of.".to_owned()),
+	}
+}
+
+fn paste_stroke_style(app: &mut App) {
+	let Some(new_style) = app.copied_stroke_style else { return };
 	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
 		let selected_indices = canvas.strokes().iter().enumerate().filter_map(|(index, stroke)| if stroke.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
 
 		if !selected_indices.is_empty() {
-			canvas.perform_operation(Operation::RecolorStrokes {
+			canvas.perform_coalescing_operation(Operation::RestyleStrokes { indices: selected_indices, new_style }, app.config.undo_coalescing);
+		}
+	}
+}
+
+// Frames the selected images with the current pen, or unframes them if they are already framed so.
+fn frame_selection(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		let selected_indices = canvas.images().iter().enumerate().filter_map(|(index, image)| if image.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
+
+		if !selected_indices.is_empty() {
+			let border_color = canvas.stroke_color.to_srgba8();
+			let is_framed = selected_indices.iter().all(|index| {
+				let image = &canvas.images()[*index];
+				image.border_width == canvas.stroke_radius && image.border_color.0 == border_color.0
+			});
+
+			canvas.perform_operation(Operation::FrameImages {
+				indices: selected_indices,
+				new_border: if is_framed { (Vx(0.), border_color) } else { (canvas.stroke_radius, border_color) },
+			});
+		}
+	}
+}
+
+// Turns off filtering for the selected images, or turns it back on if all of them are already unfiltered.
+fn pixelate_selection(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		let selected_indices = canvas.images().iter().enumerate().filter_map(|(index, image)| if image.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
+
+		if !selected_indices.is_empty() {
+			let is_pixelated = selected_indices.iter().all(|index| canvas.images()[*index].is_pixelated);
+
+			canvas.perform_operation(Operation::PixelateImages {
 				indices: selected_indices,
-				new_color: canvas.stroke_color.to_srgb().to_srgb8().opaque(),
+				is_pixelated: !is_pixelated,
+			});
+		}
+	}
+}
+
+fn hide_selection(multicanvas: &mut Multicanvas) {
+	if let Some(canvas) = multicanvas.current_canvas_mut() {
+		let selected_image_indices = canvas.images().iter().enumerate().filter_map(|(index, image)| if image.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
+
+		let selected_stroke_indices = canvas.strokes().iter().enumerate().filter_map(|(index, stroke)| if stroke.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
+
+		if !selected_image_indices.is_empty() || !selected_stroke_indices.is_empty() {
+			canvas.perform_operation(Operation::ShowObjects {
+				image_indices: selected_image_indices,
+				stroke_indices: selected_stroke_indices,
+				is_visible: false,
+			});
+		}
+	}
+}
+
+fn show_hidden_objects(multicanvas: &mut Multicanvas) {
+	if let Some(canvas) = multicanvas.current_canvas_mut() {
+		let hidden_image_indices = canvas.images().iter().enumerate().filter_map(|(index, image)| if image.is_visible { None } else { Some(index) }).collect::<Vec<_>>();
+
+		let hidden_stroke_indices = canvas.strokes().iter().enumerate().filter_map(|(index, stroke)| if stroke.is_visible { None } else { Some(index) }).collect::<Vec<_>>();
+
+		if !hidden_image_indices.is_empty() || !hidden_stroke_indices.is_empty() {
+			canvas.perform_operation(Operation::ShowObjects {
+				image_indices: hidden_image_indices,
+				stroke_indices: hidden_stroke_indices,
+				is_visible: true,
+			});
+		}
+	}
+}
+
+// How inked a pixel must be to be traced, from 0 to 1.
+const TRACE_THRESHOLD: f32 = 0.5;
+
+// Traces the dark parts of the selected images into strokes of the current pen, which are drawn over the images and selected in their place.
+fn trace_selection(app: &mut App) {
+	let is_smooth = app.multicanvas.is_stroke_smoothing_on;
+	let Some(canvas) = app.multicanvas.current_canvas_mut() else { return };
+
+	let mut strokes = Vec::new();
+	for image in canvas.images().iter().filter(|image| image.is_selected) {
+		let Some(Some(texture)) = canvas.textures.get(image.texture_index) else { continue };
+		let Some(rgba) = app.renderer.read_texture(texture) else { continue };
+		let [width, height] = [texture.extent.width as usize, texture.extent.height as usize];
+
+		for outline in trace_outlines(width, height, &rgba, TRACE_THRESHOLD) {
+			// Map each position in the texture onto the image as it is placed on the canvas.
+			let points = outline
+				.into_iter()
+				.map(|[x, y]| Point {
+					position: Vex([image.dimensions[0] * (x / width as f32 - 0.5), image.dimensions[1] * (y / height as f32 - 0.5)]).rotate(image.orientation) * image.dilation,
+					pressure: 1.,
+					timestamp: 0,
+				})
+				.collect();
+
+			let mut stroke = Stroke {
+				is_selected: true,
+				..Stroke::new(canvas.stroke_color.to_srgba8(), canvas.stroke_radius, points, is_smooth, image.position, 0., 1.)
+			};
+			stroke.recenter();
+			strokes.push(stroke.into());
+		}
+	}
+
+	if !strokes.is_empty() {
+		canvas.select_all(false);
+		canvas.perform_operation(Operation::CommitStrokes { strokes });
+	}
+}
+
+fn annotate_selection(app: &mut App) {
+	if let Some(canvas) = app.multicanvas.current_canvas() {
+		let selected_notes = canvas
+			.images()
+			.iter()
+			.filter(|image| image.is_selected)
+			.map(|image| &image.note)
+			.chain(canvas.strokes().iter().filter(|stroke| stroke.is_selected).map(|stroke| &stroke.note))
+			.collect::<Vec<_>>();
+
+		if !selected_notes.is_empty() {
+			app.multicanvas.text_capture = Some(TextCapture {
+				prompt: "Note",
+				text: selected_notes.into_iter().find_map(Clone::clone).unwrap_or_default(),
+				on_commit: set_selection_note,
 			});
 		}
 	}
 }
+
+fn set_selection_note(app: &mut App, note: String) {
+	if let Some(canvas) = app.multicanvas.current_canvas_mut() {
+		let selected_image_indices = canvas.images().iter().enumerate().filter_map(|(index, image)| if image.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
+
+		let selected_stroke_indices = canvas.strokes().iter().enumerate().filter_map(|(index, stroke)| if stroke.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
+
+		if !selected_image_indices.is_empty() || !selected_stroke_indices.is_empty() {
+			canvas.perform_operation(Operation::AnnotateObjects {
+				image_indices: selected_image_indices,
+				stroke_indices: selected_stroke_indices,
+				new_note: (!note.is_empty()).then_some(note),
+			});
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		canvas::View,
+		config::Config,
+		utility::{Scale, Zoom},
+	};
+
+	const COLORS: [Srgba8; 2] = [Srgba8([255, 0, 0, 255]), Srgba8([0, 0, 255, 255])];
+
+	// A single tab holding a stroke of each color, with nothing selected.
+	fn multicanvas() -> Multicanvas {
+		let config = Config::default();
+		let mut multicanvas = Multicanvas::new(&config);
+		let mut canvas = Canvas::new(&config);
+		let points = vec![
+			Point { position: Vex::ZERO, pressure: 1., timestamp: 0 },
+			Point {
+				position: Vex([Vx(10.), Vx(0.)]),
+				pressure: 1.,
+				timestamp: 10,
+			},
+		];
+		canvas.perform_operation(Operation::CommitStrokes {
+			strokes: COLORS.into_iter().map(|color| Stroke::new(color, Vx(2.), points.clone(), false, Vex::ZERO, 0., 1.).into()).collect(),
+		});
+		canvas.select_all(false);
+		multicanvas.canvases = vec![canvas];
+		multicanvas.current_canvas_index = Some(0);
+		multicanvas
+	}
+
+	fn execute(multicanvas: &mut Multicanvas, commands: impl IntoIterator<Item = EditCommand>) {
+		commands.into_iter().for_each(|command| multicanvas.execute(command));
+	}
+
+	fn strokes(multicanvas: &Multicanvas) -> Vec<([u8; 4], bool, bool)> {
+		multicanvas.current_canvas().unwrap().strokes().iter().map(|stroke| (stroke.color.0, stroke.is_selected, stroke.is_visible)).collect()
+	}
+
+	#[test]
+	fn selections_are_recolored_and_undone() {
+		let mut multicanvas = multicanvas();
+		let green = Srgba8([0, 255, 0, 255]);
+		execute(&mut multicanvas, [EditCommand::SelectAll, EditCommand::SetColor(green), EditCommand::RecolorSelection]);
+		assert_eq!(strokes(&multicanvas), vec![(green.0, true, true); 2]);
+
+		execute(&mut multicanvas, [EditCommand::Undo]);
+		assert_eq!(strokes(&multicanvas), COLORS.map(|color| (color.0, true, true)));
+
+		execute(&mut multicanvas, [EditCommand::Redo, EditCommand::SelectNone]);
+		assert_eq!(strokes(&multicanvas), vec![(green.0, false, true); 2]);
+	}
+
+	#[test]
+	fn selections_are_hidden_shown_and_deleted() {
+		let mut multicanvas = multicanvas();
+		// Hidden objects can't stay selected.
+		execute(&mut multicanvas, [EditCommand::SelectAll, EditCommand::HideSelection]);
+		assert_eq!(strokes(&multicanvas), COLORS.map(|color| (color.0, false, false)));
+
+		execute(&mut multicanvas, [EditCommand::ShowHiddenObjects]);
+		assert_eq!(strokes(&multicanvas), COLORS.map(|color| (color.0, false, true)));
+
+		execute(&mut multicanvas, [EditCommand::SelectAll, EditCommand::DeleteSelection]);
+		assert!(strokes(&multicanvas).is_empty());
+
+		execute(&mut multicanvas, [EditCommand::Undo]);
+		assert_eq!(strokes(&multicanvas).len(), 2);
+	}
+
+	#[test]
+	fn only_base_tools_are_switched_to() {
+		let mut multicanvas = multicanvas();
+		execute(&mut multicanvas, [EditCommand::SetTool(ToolKind::Select), EditCommand::SetTool(ToolKind::Pan)]);
+		assert_eq!(multicanvas.current_tool_name(), ToolKind::Select.key());
+
+		execute(&mut multicanvas, [EditCommand::SetTool(ToolKind::Erase)]);
+		assert_eq!(multicanvas.current_tool_name(), ToolKind::Erase.key());
+	}
+
+	fn image(texture_index: usize) -> Image {
+		Image {
+			position: Vex::ZERO,
+			orientation: 0.,
+			dilation: 1.,
+			is_selected: true,
+			is_visible: true,
+			note: None,
+			border_width: Vx(0.),
+			border_color: Srgba8([0, 0, 0, 255]),
+			is_pixelated: false,
+			texture_index,
+			dimensions: Vex([Vx(1.); 2]),
+			world_aabb_cache: Default::default(),
+		}
+	}
+
+	// Copies images from one list of textures and pastes them into another, as the clipboard does across canvases, where textures are named by their contents.
+	fn copy_and_paste(source: &[Option<&'static str>], destination: &mut Vec<Option<&'static str>>, texture_indices: &[usize]) -> Vec<&'static str> {
+		let mut images = texture_indices.iter().copied().map(image).collect::<Vec<_>>();
+		let textures = gather_textures(&mut images, |image| image, |texture_index| source.get(texture_index).copied().flatten());
+		assert_eq!(textures.len(), textures.iter().collect::<std::collections::HashSet<_>>().len());
+
+		let held_indices = textures.iter().map(|texture| destination.iter().position(|held| *held == Some(*texture))).collect();
+		let pasted_indices = place_textures(&textures, held_indices, |texture| {
+			destination.push(Some(*texture));
+			destination.len() - 1
+		});
+		images.iter().map(|image| destination[pasted_indices[image.texture_index]].unwrap()).collect()
+	}
+
+	#[test]
+	fn pasted_images_keep_their_textures_across_canvases() {
+		let source = [Some("a"), None, Some("b"), Some("c")];
+
+		// Images whose textures are gone are dropped, and shared textures are uploaded once.
+		let mut destination = vec![Some("x"), None];
+		assert_eq!(copy_and_paste(&source, &mut destination, &[2, 0, 2, 1, 5, 3]), ["b", "a", "b", "c"]);
+		assert_eq!(destination, [Some("x"), None, Some("b"), Some("a"), Some("c")]);
+
+		// Textures that are still held aren't uploaded again.
+		let mut destination = source.to_vec();
+		assert_eq!(copy_and_paste(&source, &mut destination, &[3, 0]), ["c", "a"]);
+		assert_eq!(destination, source);
+	}
+
+	#[test]
+	fn only_images_whose_textures_are_copied_are_cut() {
+		// Cut deletes the images at the indices that survive copying, so those must be exactly the images whose textures could be read.
+		let source = [Some("a"), None, Some("b")];
+		let mut index_image_pairs = [0, 1, 2, 0, 7].into_iter().map(image).enumerate().collect::<Vec<_>>();
+		let textures = gather_textures(&mut index_image_pairs, |(_, image)| image, |texture_index| source.get(texture_index).copied().flatten());
+		assert_eq!(textures, ["a", "b"]);
+		assert_eq!(index_image_pairs.iter().map(|(index, image)| (*index, image.texture_index)).collect::<Vec<_>>(), [(0, 0), (2, 1), (3, 0)]);
+	}
+
+	#[test]
+	fn stamps_land_under_the_cursor_on_tilted_views() {
+		let config = Config::default();
+		let mut canvas = Canvas::new(&config);
+		canvas.view = View {
+			position: Vex([Vx(10.), Vx(-5.)]),
+			tilt: 0.6,
+			zoom: Zoom(1.5),
+		}
+		.into();
+		let points = vec![Point { position: Vex::ZERO, pressure: 1., timestamp: 0 }];
+		let strokes = [Vex::ZERO, Vex([Vx(3.), Vx(0.)])].map(|position| Stroke::new(COLORS[0], Vx(2.), points.clone(), false, position, 0., 1.).into());
+		let stamp = Canvas::from_file(None, Srgb8([255; 3]), Srgb8([0; 3]), Vx(2.), View::new(), None, Vec::new(), strokes.into(), Vec::new());
+
+		let (cursor, window, scale) = (Vex([Px(600.), Px(150.)]), [800, 600], Scale(2.));
+		let offset = canvas.view.canvas_position(cursor, window, scale);
+		stamp_canvas(&mut canvas, stamp, offset);
+
+		// The stamp's origin is drawn under the cursor, as the shaders place canvas points on the screen.
+		let semidimensions = Vex([window[0] as f32 / 2., window[1] as f32 / 2.].map(Px)).s(scale).z(canvas.view.zoom);
+		let origin = canvas.strokes()[0].position;
+		let drawn_origin = ((origin - canvas.view.position).rotate(-canvas.view.tilt) + semidimensions).z(canvas.view.zoom).s(scale);
+		assert!((drawn_origin - cursor).norm().0 < 1e-3);
+		assert!((canvas.strokes()[1].position - origin - Vex([Vx(3.), Vx(0.)])).norm().0 < 1e-3);
+		assert!(canvas.strokes().iter().all(|stroke| stroke.is_selected));
+	}
+}
@@ -5,7 +5,10 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::time::{Duration, Instant};
+use std::{
+	num::NonZeroU32,
+	time::{Duration, Instant},
+};
 
 use winit::{
 	dpi::{PhysicalPosition, PhysicalSize},
@@ -18,21 +21,38 @@ use crate::input::linux::*;
 #[cfg(target_os = "windows")]
 use crate::input::wintab::*;
 use crate::{
-	actions::default_keymap,
-	canvas::{Image, Multicanvas, Stroke},
+	actions::{default_keymap, presentation_keymap, request_exit},
+	canvas::{Canvas, Image, Multicanvas, Stroke, StrokeStyle, TextCapture},
 	clipboard::Clipboard,
-	config::Config,
+	config::{Config, WindowGeometry},
 	input::{
 		keymap::{execute_keymap, Keymap},
 		Button, InputMonitor, Key,
 	},
 	render::{Prerender, Renderer},
+	tools::WheelMode,
 	ui::Widget,
-	utility::{Lx, Px, Scale, Vex, Zero, Zoom},
+	utility::{Lx, Px, Scale, Vex, Vx, Zero, Zoom},
 	APP_NAME_CAPITALIZED,
 };
 pub enum ClipboardContents {
-	Subcanvas(Vec<Image>, Vec<Stroke>),
+	// The images index into the copied textures, rather than those of the canvas they were copied from.
+	Subcanvas(Vec<Image>, Vec<Stroke>, Vec<ClipboardTexture>),
+}
+
+// A copied texture, read back as straight RGBA so that it can be pasted into any canvas.
+// The texture it was read from is reused instead when pasting into a canvas that still holds it.
+pub struct ClipboardTexture {
+	pub source: wgpu::Id<wgpu::Texture>,
+	pub dimensions: [NonZeroU32; 2],
+	pub data: Vec<u8>,
+}
+
+// The canvas point under the centroid of a two-finger touch gesture, and the initial spread and zoom.
+pub struct TouchGestureOrigin {
+	pub position: Vex<2, Vx>,
+	pub spread: Px,
+	pub zoom: Zoom,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -44,21 +64,36 @@ pub enum PreFullscreenState {
 // Current state of our app.
 pub struct App<'window> {
 	pub config: Config,
-	pub clipboard: Clipboard,
+	// The system clipboard, if one is available; otherwise, copied objects are only kept in process.
+	pub clipboard: Option<Clipboard>,
 	pub pending_resize: Option<winit::dpi::PhysicalSize<u32>>,
 	pub should_redraw: bool,
 	pub renderer: Renderer<'window>,
+	// Where the cursor is, after the pen offset.
 	pub cursor_physical_position: Vex<2, Px>,
+	pub pen_offset: Vex<2, Lx>,
 	pub scale: Scale,
 	pub is_cursor_relevant: bool,
 	pub tablet_context: Option<TabletContext>,
 	pub pressure: Option<f64>,
+	// The pen buttons held as of the last tablet packet.
+	pub pen_buttons: u32,
+	// When the last tablet packet arrived, and whether the pen was touching the tablet then.
+	pub last_pen_packet: Option<(Instant, bool)>,
+	pub touches: Vec<(u64, Vex<2, Px>)>,
+	pub touch_gesture_origin: Option<TouchGestureOrigin>,
 	pub multicanvas: Multicanvas,
 	pub last_frame_instant: std::time::Instant,
 	pub input_monitor: InputMonitor,
 	pub keymap: Keymap,
+	// The keymap that is swapped in when entering or leaving presentation mode.
+	pub inactive_keymap: Keymap,
 	pub clipboard_contents: Option<ClipboardContents>,
+	// The style last copied from a stroke, which can be pasted onto others.
+	pub copied_stroke_style: Option<StrokeStyle>,
 	pub pre_fullscreen_state: Option<PreFullscreenState>,
+	// Whether the window should close, once any unsaved changes have been saved or discarded.
+	pub is_exiting: bool,
 	pub window: &'window winit::window::Window,
 }
 
@@ -66,7 +101,7 @@ impl<'window> App<'window> {
 	// Sets up the logger and renderer.
 	pub fn new(window: &'window winit::window::Window) -> Self {
 		let config = Config::load().unwrap_or_default();
-		let keymap = default_keymap();
+		let keymap = default_keymap(config.bare_undo_keys, config.color_picker_key);
 
 		// Attempt to establish a tablet context.
 		let tablet_context = TabletContext::new(window);
@@ -74,39 +109,39 @@ impl<'window> App<'window> {
 		// Set up the renderer.
 		let size = window.inner_size();
 		let scale_factor = window.scale_factor() as f32;
-		let renderer = Renderer::new(window, size.width, size.height, scale_factor);
+		let renderer = Renderer::new(window, size.width, size.height, scale_factor, config.linear_blending, config.round_caps, config.stroke_grain, config.font.as_deref(), config.font_size);
 
 		// Make the window visible and immediately clear color to prevent a flash.
-		let clear_color = config.default_canvas_color.opaque().to_lrgba().0.map(f64::from);
-		let output = renderer
-			.clear(wgpu::Color {
-				r: clear_color[0],
-				g: clear_color[1],
-				b: clear_color[2],
-				a: clear_color[3],
-			})
-			.unwrap();
+		let output = renderer.clear(renderer.background_color(config.default_canvas_color)).unwrap();
 		window.set_visible(true);
 		// FIXME: This sometimes flashes, and sometimes doesn't.
 		output.present();
 
 		// Return a new instance of the app state.
 		Self {
-			clipboard: Clipboard::new().unwrap(),
+			clipboard: Clipboard::new(),
 			pending_resize: None,
 			should_redraw: false,
 			renderer,
 			scale: Scale(scale_factor),
 			cursor_physical_position: Vex::ZERO,
+			pen_offset: config.pen_offset,
 			is_cursor_relevant: false,
 			tablet_context,
 			pressure: None,
-			multicanvas: Multicanvas::new(),
+			pen_buttons: 0,
+			last_pen_packet: None,
+			touches: Vec::new(),
+			touch_gesture_origin: None,
+			multicanvas: Multicanvas::new(&config),
 			last_frame_instant: Instant::now() - Duration::new(1, 0),
 			input_monitor: InputMonitor::new(),
 			keymap,
+			inactive_keymap: presentation_keymap(),
 			clipboard_contents: None,
+			copied_stroke_style: None,
 			pre_fullscreen_state: None,
+			is_exiting: false,
 			config,
 			window,
 		}
@@ -124,34 +159,81 @@ impl<'window> App<'window> {
 	// Handles a single event.
 	fn handle_event(&mut self, event: Event<()>, window_target: &EventLoopWindowTarget<()>) {
 		match event {
+			// Emitted when a timed wait has elapsed.
+			Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+				self.multicanvas.update_cursor_visibility(self.window);
+				self.multicanvas.commit_lifted_stroke(false);
+				self.should_redraw = true;
+				self.window.request_redraw();
+			},
 			// Emitted when the event loop resumes.
 			Event::NewEvents(_) => {},
 			// Check if a window event has occurred.
 			Event::WindowEvent { ref event, window_id } if window_id == self.window.id() => 'window_event: {
 				match event {
-					// If the titlebar close button is clicked  or the escape key is pressed, exit the loop.
-					WindowEvent::CloseRequested => window_target.exit(),
+					// If the titlebar close button is clicked, exit the loop, unless unsaved changes need to be dealt with first.
+					WindowEvent::CloseRequested => request_exit(self),
 					WindowEvent::KeyboardInput { event, .. } => {
 						self.input_monitor.process_key_event(event);
+						if event.state == ElementState::Pressed {
+							self.capture_text(event);
+							self.answer_confirmation(event);
+						}
 					},
 					WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+						// The pen's own press arrives alongside packets with pressure, so the tablet is polled first to tell it apart from the palm.
+						if *state == ElementState::Pressed {
+							self.poll_tablet();
+							if self.is_rejecting_palm(false) {
+								break 'window_event;
+							}
+						}
 						self.input_monitor.process_mouse_input(state);
 					},
 					WindowEvent::MouseWheel {
 						delta: MouseScrollDelta::LineDelta(lines, rows), ..
 					} => {
+						let should_zoom = self.input_monitor.active_keys.contains(Key::Control) != (self.config.wheel_mode == WheelMode::Zoom);
+						let semidimensions = Vex([self.renderer.config.width as f32 / 2., self.renderer.config.height as f32 / 2.].map(Px));
 						if let Some(canvas) = self.multicanvas.current_canvas_mut() {
-							if !self.input_monitor.active_keys.contains(Key::Control) {
+							if !should_zoom {
 								// Negative multiplier = reverse scrolling; positive multiplier = natural scrolling.
 								canvas.view.position = canvas.view.position + Vex([*lines, *rows].map(Lx)).z(canvas.view.zoom).rotate(canvas.view.tilt) * -32.;
 							} else {
+								// Zoom about the cursor, keeping the point beneath it in place.
+								let cursor_offset = (self.cursor_physical_position - semidimensions).s(self.scale);
+								let anchor = canvas.view.position + cursor_offset.z(canvas.view.zoom).rotate(canvas.view.tilt);
 								canvas.view.zoom = Zoom(canvas.view.zoom.0 * f32::powf(2., *rows / 32.));
+								canvas.view.position = anchor - cursor_offset.z(canvas.view.zoom).rotate(canvas.view.tilt);
 							}
 							self.should_redraw = true;
 						}
 					},
+					// Touches are tracked separately from the pen, which remains free to draw.
+					WindowEvent::Touch(Touch { phase, location, id, force, .. }) => {
+						let location = Vex([location.x as f32, location.y as f32].map(Px));
+						match phase {
+							TouchPhase::Started if self.is_rejecting_palm(true) => break 'window_event,
+							TouchPhase::Started => self.touches.push((*id, location)),
+							TouchPhase::Moved => {
+								if let Some((_, touch_location)) = self.touches.iter_mut().find(|(touch_id, _)| touch_id == id) {
+									*touch_location = location;
+								}
+							},
+							TouchPhase::Ended | TouchPhase::Cancelled => self.touches.retain(|(touch_id, _)| touch_id != id),
+						}
+						// Until a tablet packet arrives, the force of a touch stands in for pen pressure, as some pens and touchscreens report it, scaled to the range of tablet pressure readings.
+						if self.last_pen_packet.is_none() {
+							self.pressure = match phase {
+								TouchPhase::Started | TouchPhase::Moved => force.map(|force| force.normalized() * 32767.),
+								TouchPhase::Ended | TouchPhase::Cancelled => None,
+							};
+						}
+						self.process_touch_gesture();
+						self.should_redraw = true;
+					},
 					WindowEvent::CursorMoved { position, .. } => {
-						self.cursor_physical_position = Vex([position.x as _, position.y as _].map(Px));
+						self.cursor_physical_position = Vex([position.x as _, position.y as _].map(Px)) + self.pen_offset.s(self.scale);
 					},
 					WindowEvent::CursorEntered { .. } => {
 						self.is_cursor_relevant = true;
@@ -179,7 +261,8 @@ impl<'window> App<'window> {
 					// If a window redraw is requested, have the renderer update and render.
 					WindowEvent::RedrawRequested => {
 						self.update_renderer();
-						if self.should_redraw || (Instant::now() - self.last_frame_instant) >= Duration::new(1, 0) / 90 {
+						// Input that arrives faster than the frame rate limit is drawn in a deferred frame, so that the latest state is always shown.
+						let deferred_frame_instant = if self.should_redraw || (Instant::now() - self.last_frame_instant) >= self.config.frame_interval {
 							self.last_frame_instant = Instant::now();
 							match self.repaint() {
 								Ok(_) => {},
@@ -188,8 +271,15 @@ impl<'window> App<'window> {
 								Err(e) => eprintln!("{:?}", e),
 							}
 							self.should_redraw = false;
+							None
+						} else {
+							Some(self.last_frame_instant + self.config.frame_interval)
+						};
+						// Wake up again to draw a deferred frame, dismiss the banner, or hide an idle cursor, if needed.
+						match self.multicanvas.next_wakeup_instant().into_iter().chain(deferred_frame_instant).min() {
+							Some(instant) => window_target.set_control_flow(ControlFlow::WaitUntil(instant)),
+							None => window_target.set_control_flow(ControlFlow::Wait),
 						}
-						window_target.set_control_flow(ControlFlow::Wait);
 						break 'window_event;
 					},
 
@@ -200,6 +290,11 @@ impl<'window> App<'window> {
 				self.poll_tablet();
 				self.process_input();
 				self.window.request_redraw();
+
+				if self.is_exiting {
+					self.save_window_geometry();
+					window_target.exit();
+				}
 			},
 
 			// Ignore all other events.
@@ -221,19 +316,158 @@ impl<'window> App<'window> {
 
 		if let Some(buf) = self.tablet_context.as_mut().map(|c| c.get_packets(50)) {
 			if let Some(packet) = buf.last() {
+				self.last_pen_packet = Some((Instant::now(), packet.normal_pressure > 0));
 				self.pressure = Some(f64::from(packet.normal_pressure));
+				self.process_pen_buttons(packet.buttons);
 			}
 		}
 	}
 
+	// Whether input not made by the pen should be ignored, because the pen was near the tablet too recently.
+	// Touches are rejected even while the pen is touching the tablet, but clicks aren't, since the pen's own clicks arrive then.
+	fn is_rejecting_palm(&self, is_touch: bool) -> bool {
+		self.last_pen_packet.is_some_and(|(instant, is_pen_touching)| (is_touch || !is_pen_touching) && Instant::now() - instant < self.config.palm_rejection)
+	}
+
+	// Switches to the tools bound to the pen's barrel buttons while they are held, like a discovery shortcut.
+	fn process_pen_buttons(&mut self, buttons: u32) {
+		let different_buttons = buttons ^ self.pen_buttons;
+		self.pen_buttons = buttons;
+
+		// Presentations only allow panning with the cursor.
+		if self.multicanvas.presentation.is_some() {
+			return;
+		}
+
+		for (button, tool) in [(Packet::LOWER_BARREL_BUTTON, self.config.lower_pen_button), (Packet::UPPER_BARREL_BUTTON, self.config.upper_pen_button)] {
+			if let Some(tool) = tool.filter(|_| different_buttons & button != 0) {
+				self.multicanvas.mode_stack.switch_transient(tool.switch(buttons & button != 0));
+				self.should_redraw = true;
+			}
+		}
+	}
+
+	// Pans and zooms the current canvas so that the canvas point under the centroid of two touches follows them.
+	fn process_touch_gesture(&mut self) {
+		let (Some(canvas), [(_, a), (_, b)]) = (self.multicanvas.current_canvas_mut(), self.touches.as_slice()) else {
+			// Record the view in the view history once the gesture ends.
+			if self.touch_gesture_origin.take().is_some() {
+				self.multicanvas.current_canvas_mut().map(Canvas::commit_view);
+			}
+			return;
+		};
+
+		let semidimensions = Vex([self.renderer.config.width as f32 / 2., self.renderer.config.height as f32 / 2.].map(Px));
+		let centroid = (*a + *b) / 2.;
+		let spread = (*a - *b).norm();
+
+		match &self.touch_gesture_origin {
+			None => {
+				self.touch_gesture_origin = Some(TouchGestureOrigin {
+					position: canvas.view.position + (centroid - semidimensions).s(self.scale).z(canvas.view.zoom).rotate(canvas.view.tilt),
+					spread,
+					zoom: canvas.view.zoom,
+				});
+			},
+			Some(origin) => {
+				if origin.spread > Px(0.) {
+					canvas.view.zoom = Zoom(origin.zoom.0 * (spread / origin.spread));
+				}
+				canvas.view.position = origin.position - (centroid - semidimensions).s(self.scale).z(canvas.view.zoom).rotate(canvas.view.tilt);
+			},
+		}
+	}
+
+	fn capture_text(&mut self, event: &KeyEvent) {
+		use winit::keyboard::{Key as LogicalKey, NamedKey};
+		let Some(text_capture) = &mut self.multicanvas.text_capture else { return };
+		match &event.logical_key {
+			LogicalKey::Named(NamedKey::Enter) => {
+				if let Some(TextCapture { text, on_commit, .. }) = self.multicanvas.text_capture.take() {
+					on_commit(self, text);
+				}
+			},
+			LogicalKey::Named(NamedKey::Escape) => self.multicanvas.text_capture = None,
+			LogicalKey::Named(NamedKey::Backspace) => {
+				text_capture.text.pop();
+			},
+			_ => {
+				if let Some(text) = &event.text {
+					text_capture.text.extend(text.chars().filter(|c| !c.is_control()));
+				}
+			},
+		}
+	}
+
+	// Sets the pen offset so that the last tap lands on the target in the middle of the window.
+	fn calibrate_pen(&mut self) {
+		let center = Vex([self.renderer.config.width as f32 / 2., self.renderer.config.height as f32 / 2.].map(Px));
+		let reported_position = self.cursor_physical_position - self.pen_offset.s(self.scale);
+		self.pen_offset = (center - reported_position).s(self.scale);
+		self.cursor_physical_position = center;
+		self.multicanvas.is_calibrating_pen = false;
+		let Vex([Lx(x), Lx(y)]) = self.pen_offset;
+		self.multicanvas
+			.show_banner(format!("The pen is offset by ({x:.1}, {y:.1}) until Inksy is closed; add \"pen-offset {x:.1} {y:.1}\" to the config to keep it."));
+	}
+
+	fn answer_confirmation(&mut self, event: &KeyEvent) {
+		use winit::keyboard::{Key as LogicalKey, NamedKey};
+		let Some(confirmation) = &self.multicanvas.confirmation else { return };
+		let on_answer = match &event.logical_key {
+			LogicalKey::Character(character) if character.eq_ignore_ascii_case("s") => Some(confirmation.on_save),
+			LogicalKey::Character(character) if character.eq_ignore_ascii_case("d") => Some(confirmation.on_discard),
+			LogicalKey::Named(NamedKey::Escape) => None,
+			_ => return,
+		};
+		self.multicanvas.confirmation = None;
+		// The answering key mustn't also trigger a shortcut.
+		self.input_monitor.defresh();
+		if let Some(on_answer) = on_answer {
+			on_answer(self);
+		}
+	}
+
 	fn process_input(&mut self) {
-		if self.input_monitor.is_fresh {
+		// Any newly pressed key or button ends a replay, before the canvas can be edited.
+		if !(self.input_monitor.different_keys & self.input_monitor.active_keys).is_empty() || !(self.input_monitor.different_buttons & self.input_monitor.active_buttons).is_empty() {
+			if let Some(canvas) = self.multicanvas.current_canvas_mut() {
+				canvas.stop_replay();
+			}
+		}
+
+		// While calibrating the pen, a tap only measures the offset, and doesn't reach the canvas.
+		if self.multicanvas.is_calibrating_pen && (self.input_monitor.different_buttons & self.input_monitor.active_buttons).contains(Button::Left) {
+			self.calibrate_pen();
+			self.input_monitor.defresh();
+		}
+
+		// Shortcuts are suspended while text is being captured or a confirmation is awaited.
+		if self.input_monitor.is_fresh && self.multicanvas.text_capture.is_none() && self.multicanvas.confirmation.is_none() {
 			self.should_redraw = true;
+			// A stroke waiting out a lift is committed first, so that shortcuts act on it like any other stroke.
+			self.multicanvas.commit_lifted_stroke(true);
 			execute_keymap(self, self.input_monitor.active_keys, self.input_monitor.fresh_keys, self.input_monitor.different_keys);
 		}
 
 		self.multicanvas.update(self.window, &self.renderer, &self.input_monitor, self.is_cursor_relevant, self.pressure, self.cursor_physical_position, self.scale);
 
+		// Free the memory of textures that can no longer be shown, keeping those that could still be pasted without uploading them again.
+		if let Some(canvas) = self.multicanvas.current_canvas_mut() {
+			let clipboard_textures = match &self.clipboard_contents {
+				Some(ClipboardContents::Subcanvas(_, _, textures)) => textures.as_slice(),
+				None => &[],
+			};
+			let retained_texture_indices = canvas
+				.textures
+				.iter()
+				.enumerate()
+				.filter(|(_, texture)| texture.as_ref().is_some_and(|texture| clipboard_textures.iter().any(|clipboard_texture| clipboard_texture.source == texture.texture.global_id())))
+				.map(|(index, _)| index)
+				.collect::<Vec<_>>();
+			canvas.release_orphaned_textures(retained_texture_indices);
+		}
+
 		// TODO: Find a better way to handle this.
 		if let Some(canvas) = self.multicanvas.current_canvas_index.and_then(|x| self.multicanvas.canvases.get_mut(x)) {
 			if self.multicanvas.was_canvas_saved != canvas.is_saved() || canvas.file_path.read_if_dirty().is_some() {
@@ -246,6 +480,17 @@ impl<'window> App<'window> {
 		self.input_monitor.defresh();
 	}
 
+	// Draws a notice over the window right away, since it can't be redrawn until the blocking work that follows is done.
+	pub fn show_busy_indicator(&mut self, text: &'static str) {
+		self.multicanvas.busy_text = Some(text);
+		if let Err(error) = self.repaint() {
+			eprintln!("{:?}", error);
+		}
+		self.multicanvas.busy_text = None;
+		// The notice stays on screen until the next frame, which should follow the work.
+		self.should_redraw = true;
+	}
+
 	pub fn update_window_title(&mut self) {
 		let current_canvas = self.multicanvas.current_canvas_index.and_then(|x| self.multicanvas.canvases.get(x));
 		if let Some(canvas) = current_canvas {
@@ -267,10 +512,35 @@ impl<'window> App<'window> {
 		}
 	}
 
+	fn save_window_geometry(&self) {
+		// A maximized or fullscreen window doesn't know its normal geometry, so we keep the last one we saw.
+		let fallback_geometry = WindowGeometry::load().map_or_else(
+			|| (self.window.outer_position().unwrap_or_default(), self.window.inner_size()),
+			|window_geometry| (window_geometry.position.into(), window_geometry.size.into()),
+		);
+		let (position, size, is_maximized) = match self.pre_fullscreen_state {
+			Some(PreFullscreenState::Normal(position, size)) => (position, size, false),
+			Some(PreFullscreenState::Maximized) => (fallback_geometry.0, fallback_geometry.1, true),
+			None if self.window.is_maximized() || self.window.fullscreen().is_some() => (fallback_geometry.0, fallback_geometry.1, true),
+			None => (self.window.outer_position().unwrap_or_default(), self.window.inner_size(), false),
+		};
+		WindowGeometry {
+			position: [position.x, position.y],
+			size: [size.width, size.height],
+			is_maximized,
+		}
+		.save();
+	}
+
 	fn update_renderer(&mut self) {
 		// Apply a resize if necessary; resizes are time-intensive.
 		if let Some(size) = self.pending_resize.take() {
 			self.renderer.resize(size.width, size.height, self.scale.0);
 		}
+
+		// Advance any replay on the current canvas.
+		if let Some(canvas) = self.multicanvas.current_canvas_mut() {
+			canvas.advance_replay();
+		}
 	}
 }
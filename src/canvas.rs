@@ -5,21 +5,29 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::{num::NonZeroU32, path::PathBuf};
+use std::{
+	cell::Cell,
+	num::NonZeroU32,
+	path::PathBuf,
+	time::{Duration, Instant},
+};
 
 use crate::{
+	app::App,
 	config::Config,
-	input::{Button, InputMonitor, Key},
-	render::{stroke_renderer::SelectionTransformation, text_renderer::Align, texture::Texture, DrawCommand, Prerender, Renderer},
-	tools::{ColorSelectionPart, ModeStack, OrbitInitial, PanOrigin, ResizeDraft, RotateDraft, Tool, ZoomOrigin},
+	input::{keymap::BindingGroup, Button, InputMonitor, Key},
+	render::{stroke_renderer::SelectionTransformation, text_renderer::Align, texture::Texture, DrawCommand, Prerender, RenderStatistics, Renderer, DEFAULT_SELECTION_COLOR},
+	tools::{ColorPickerPlacement, ColorSelectionPart, ColorTarget, EraserMode, ModeStack, OrbitInitial, PanOrigin, ResizeDraft, RotateDraft, Tool, ToolKind, ZoomOrigin},
 	ui::Widget,
-	utility::{Hsv, Lx, Px, Scale, Srgb8, Srgba8, Tracked, Vex, Vx, Vx2, Zero, Zoom},
+	utility::{Hsva, Lx, Px, Scale, Srgb8, Srgba8, Tracked, Vex, Vx, Vx2, Zero, Zoom},
 };
 
 #[derive(Clone)]
 pub struct Point {
 	pub position: Vex<2, Vx>,
 	pub pressure: f32,
+	// Milliseconds since the stroke began.
+	pub timestamp: u32,
 }
 
 #[derive(Clone)]
@@ -31,10 +39,20 @@ pub struct Image {
 
 	// Modifiable data.
 	pub is_selected: bool,
+	// Whether the image is drawn and can be picked; hidden images stay in the document until shown again.
+	pub is_visible: bool,
+	pub note: Option<String>,
+	pub border_width: Vx,
+	pub border_color: Srgba8,
+	// Whether the image is sampled without filtering, so that pixel art stays crisp.
+	pub is_pixelated: bool,
 
 	// Stable data.
 	pub texture_index: usize,
 	pub dimensions: Vex<2, Vx>,
+
+	// Cached bounds.
+	pub world_aabb_cache: AabbCache,
 }
 
 #[derive(Clone)]
@@ -46,48 +64,230 @@ pub struct Stroke {
 
 	// Modifiable data.
 	pub is_selected: bool,
+	pub is_visible: bool,
 	pub color: Srgba8,
+	pub note: Option<String>,
 
 	// Geometry parameters.
 	pub stroke_radius: Vx,
 	pub points: Vec<Point>,
+	// Whether the geometry follows a spline through the points rather than the polyline between them.
+	pub is_smooth: bool,
 
 	// Cached geometry.
-	pub vertices: Vec<(Vex<2, Vx>, f32)>,
+	pub vertices: Vec<OutlineVertex>,
 	pub relative_indices: Vec<u32>,
+	pub world_aabb_cache: AabbCache,
+}
+
+// A vertex of the outline of a stroke, with its polarity across the stroke and how far past the end of the stroke it lies, both in radii.
+pub type OutlineVertex = (Vex<2, Vx>, [f32; 2]);
+
+// The caps at the ends of a stroke are each drawn as two triangles, after the rest of its triangles.
+const CAP_INDEX_COUNT: usize = 12;
+
+// The smallest and largest corners of an axis-aligned box on the canvas.
+pub type Aabb = (Vex<2, Vx>, Vex<2, Vx>);
+
+// The axis-aligned bounds of a set of points, if there are any.
+pub fn aabb_of(points: impl IntoIterator<Item = Vex<2, Vx>>) -> Option<Aabb> {
+	points.into_iter().fold(None, |bounds, point| match bounds {
+		None => Some((point, point)),
+		Some((min, max)) => Some((Vex([min[0].min(point[0]), min[1].min(point[1])]), Vex([max[0].max(point[0]), max[1].max(point[1])]))),
+	})
+}
+
+// The canvas bounds of an object, along with the placement they were computed for.
+// Since objects are moved in many places, the bounds are checked against the placement when read instead of being updated at each of them.
+#[derive(Clone, Default)]
+pub struct AabbCache(Cell<Option<([f32; 7], Aabb)>>);
+
+impl AabbCache {
+	fn get_or_compute(&self, placement: [f32; 7], compute: impl FnOnce() -> Aabb) -> Aabb {
+		match self.0.get() {
+			Some((cached_placement, aabb)) if cached_placement == placement => aabb,
+			_ => {
+				let aabb = compute();
+				self.0.set(Some((placement, aabb)));
+				aabb
+			},
+		}
+	}
+
+	// Discards the bounds, for when the shape of an object changes without it moving.
+	fn invalidate(&self) {
+		self.0.set(None);
+	}
 }
 
 impl Stroke {
-	pub fn new(color: Srgba8, stroke_radius: Vx, points: Vec<Point>, position: Vex<2, Vx>, orientation: f32, dilation: f32) -> Self {
-		let (vertices, relative_indices) = Self::compute_geometry(&points, stroke_radius);
+	pub fn new(color: Srgba8, stroke_radius: Vx, points: Vec<Point>, is_smooth: bool, position: Vex<2, Vx>, orientation: f32, dilation: f32) -> Self {
+		let (vertices, relative_indices) = Self::compute_geometry(&points, stroke_radius, is_smooth);
 
 		Self {
 			position,
 			orientation,
 			dilation,
 			is_selected: false,
+			is_visible: true,
 			color,
+			note: None,
 			stroke_radius,
 			points,
+			is_smooth,
 			vertices,
 			relative_indices,
+			world_aabb_cache: AabbCache::default(),
+		}
+	}
+
+	// Moves the local origin of the stroke to the centroid of its points without moving the stroke, so that it turns and scales about its middle.
+	pub fn recenter(&mut self) {
+		if self.points.is_empty() {
+			return;
+		}
+		let local_centroid = self.points.iter().fold(Vex::ZERO, |acc, point| acc + point.position) / self.points.len() as f32;
+		for point in self.points.iter_mut() {
+			point.position = point.position - local_centroid;
+		}
+		// The geometry doesn't depend on where the origin is, so it is shifted rather than recomputed.
+		for (vertex, _) in self.vertices.iter_mut() {
+			*vertex = *vertex - local_centroid;
+		}
+		self.position = self.position + local_centroid.rotate(self.orientation) * self.dilation;
+		self.world_aabb_cache.invalidate();
+	}
+
+	// The canvas bounds of the stroke's geometry, including its width.
+	pub fn world_aabb(&self) -> Aabb {
+		let placement = [self.position[0].0, self.position[1].0, self.orientation, self.dilation, 0., 0., 0.];
+		self.world_aabb_cache.get_or_compute(placement, || {
+			aabb_of(self.vertices.iter().map(|(vertex, _)| self.position + vertex.rotate(self.orientation) * self.dilation)).unwrap_or((self.position, self.position))
+		})
+	}
+
+	// The indices of the triangles of the stroke without its caps, which only the stroke shader can round, for drawing it elsewhere.
+	pub fn body_indices(&self) -> &[u32] {
+		let cap_index_count = if self.points.len() > 1 { CAP_INDEX_COUNT } else { 0 };
+		&self.relative_indices[..self.relative_indices.len().saturating_sub(cap_index_count)]
+	}
+
+	// The canvas position and radius of each point.
+	fn transformed_points(&self) -> impl Iterator<Item = (Vex<2, Vx>, Vx)> + '_ {
+		self.points
+			.iter()
+			.map(|point| (self.position + point.position.rotate(self.orientation) * self.dilation, self.stroke_radius * point.pressure * self.dilation))
+	}
+
+	pub fn style(&self) -> StrokeStyle {
+		StrokeStyle {
+			color: self.color,
+			stroke_radius: self.stroke_radius,
+			is_smooth: self.is_smooth,
+		}
+	}
+
+	// Replaces the style of the stroke, returning the old style and whether its geometry had to be recomputed.
+	fn restyle(&mut self, style: StrokeStyle) -> (StrokeStyle, bool) {
+		let old_style = self.style();
+		self.color = style.color;
+		let is_reshaped = style.stroke_radius != self.stroke_radius || style.is_smooth != self.is_smooth;
+		if is_reshaped {
+			self.stroke_radius = style.stroke_radius;
+			self.is_smooth = style.is_smooth;
+			(self.vertices, self.relative_indices) = Self::compute_geometry(&self.points, self.stroke_radius, self.is_smooth);
+			self.world_aabb_cache.invalidate();
+		}
+		(old_style, is_reshaped)
+	}
+
+	// Stretches the stroke along the canvas axes about a center, which is baked into its points.
+	fn stretch_about(&mut self, center: Vex<2, Vx>, stretch: Vex<2, f32>) {
+		self.position = center + stretched(self.position - center, stretch);
+		for point in self.points.iter_mut() {
+			point.position = stretched(point.position.rotate(self.orientation) * self.dilation, stretch).rotate(-self.orientation) / self.dilation;
+		}
+		(self.vertices, self.relative_indices) = Self::compute_geometry(&self.points, self.stroke_radius, self.is_smooth);
+		self.world_aabb_cache.invalidate();
+	}
+
+	// Whether the stroke's outline comes within a radius of a point.
+	pub fn is_near(&self, point: Vex<2, Vx>, radius: Vx) -> bool {
+		let (min, max) = self.world_aabb();
+		if (0..2).any(|i| point[i] < min[i] - radius || point[i] > max[i] + radius) {
+			return false;
+		}
+
+		let points = self.transformed_points().collect::<Vec<_>>();
+		match points.as_slice() {
+			[] => false,
+			[(position, stroke_radius)] => (point - *position).norm() <= radius + *stroke_radius,
+			_ => points.windows(2).any(|segment| {
+				let [(a, a_radius), (b, b_radius)] = [segment[0], segment[1]];
+				let ab = b - a;
+				let t = if ab.norm().0 > 0. { ((point - a).dot(ab) / ab.dot(ab)).clamp(0., 1.) } else { 0. };
+				(point - (a + ab * t)).norm() <= radius + a_radius.max(b_radius)
+			}),
+		}
+	}
+
+	// Splits the stroke into the runs of points that lie outside every circle, or returns `None` if no point does.
+	fn split_outside(&self, centers: &[Vex<2, Vx>], radius: Vx) -> Option<Vec<Stroke>> {
+		let is_outside = self
+			.transformed_points()
+			.map(|(position, point_radius)| centers.iter().all(|center| (*center - position).norm() > radius + point_radius))
+			.collect::<Vec<_>>();
+		if is_outside.iter().all(|x| *x) {
+			return None;
+		}
+
+		// Pieces keep the selection and note of the stroke they were split from, but are centered on their own points.
+		let piece = |points| {
+			let mut piece = Stroke {
+				is_selected: self.is_selected,
+				note: self.note.clone(),
+				..Stroke::new(self.color, self.stroke_radius, points, self.is_smooth, self.position, self.orientation, self.dilation)
+			};
+			piece.recenter();
+			piece
+		};
+		let mut pieces = Vec::new();
+		let mut run = Vec::new();
+		for (point, is_outside) in self.points.iter().zip(is_outside) {
+			if is_outside {
+				run.push(point.clone());
+			} else if !run.is_empty() {
+				pieces.push(piece(std::mem::take(&mut run)));
+			}
 		}
+		if !run.is_empty() {
+			pieces.push(piece(run));
+		}
+		Some(pieces)
 	}
 
-	fn compute_geometry(points: &[Point], stroke_radius: Vx) -> (Vec<(Vex<2, Vx>, f32)>, Vec<u32>) {
+	fn compute_geometry(points: &[Point], stroke_radius: Vx, is_smooth: bool) -> (Vec<OutlineVertex>, Vec<u32>) {
+		let smoothed_points;
+		let points = if is_smooth {
+			smoothed_points = smooth_points(points);
+			smoothed_points.as_slice()
+		} else {
+			points
+		};
+
 		if let [point] = points {
 			let heptagonal_vertices = {
 				use std::f32::consts::PI;
 				let i = Vex([point.pressure * stroke_radius, Vx(0.)]);
 				vec![
-					(Vex::ZERO, 0.),
-					(i, 1.),
-					(i.rotate(2. * PI * 1. / 7.), 1.),
-					(i.rotate(2. * PI * 2. / 7.), 1.),
-					(i.rotate(2. * PI * 3. / 7.), 1.),
-					(i.rotate(2. * PI * 4. / 7.), 1.),
-					(i.rotate(2. * PI * 5. / 7.), 1.),
-					(i.rotate(2. * PI * 6. / 7.), 1.),
+					(Vex::ZERO, [0., 0.]),
+					(i, [1., 0.]),
+					(i.rotate(2. * PI * 1. / 7.), [1., 0.]),
+					(i.rotate(2. * PI * 2. / 7.), [1., 0.]),
+					(i.rotate(2. * PI * 3. / 7.), [1., 0.]),
+					(i.rotate(2. * PI * 4. / 7.), [1., 0.]),
+					(i.rotate(2. * PI * 5. / 7.), [1., 0.]),
+					(i.rotate(2. * PI * 6. / 7.), [1., 0.]),
 				]
 			};
 
@@ -100,36 +300,22 @@ impl Stroke {
 
 			// We compute the four bounding vertices of each line segment.
 			for [a, b] in points.array_windows::<2>() {
-				// We compute a unit normal.
-				let perpendicular = {
-					let forward = b.position - a.position;
-					Vex([forward[1], -forward[0]]).normalized() * stroke_radius
-				};
-
-				let current_index = u32::try_from(vertices.len()).unwrap();
-				vertices.extend([
-					(a.position + perpendicular * a.pressure, 1.),
-					(a.position - perpendicular * a.pressure, -1.),
-					(b.position + perpendicular * b.pressure, 1.),
-					(b.position - perpendicular * b.pressure, -1.),
-				]);
-				indices.extend([0, 2, 3, 0, 3, 1].map(|n| current_index + n));
+				let (segment_vertices, segment_indices) = segment_geometry(a, b, stroke_radius, u32::try_from(vertices.len()).unwrap());
+				vertices.extend(segment_vertices);
+				indices.extend(segment_indices);
 			}
 
+			// We fill the outer corner of each join.
 			for (i, [a, b, c]) in points.array_windows::<3>().enumerate() {
-				let p = b.position - a.position;
-				let q = c.position - b.position;
-				let i = u32::try_from(i).unwrap();
-				let cross_product = p.cross(q);
-
-				if cross_product > Vx2(0.) {
-					/* Clockwise */
-					#[allow(clippy::identity_op)]
-					indices.extend([2, 4 + 0, 4 + 1].map(|n| n + i * 4));
-				} else if cross_product < Vx2(0.) {
-					/* Counterclockwise */
-					#[allow(clippy::identity_op)]
-					indices.extend([3, 4 + 1, 4 + 0].map(|n| n + i * 4));
+				indices.extend(join_indices([a, b, c], u32::try_from(i * 4).unwrap()).into_iter().flatten());
+			}
+
+			// We cap both ends of the stroke.
+			if let ([a, b, ..], [.., c, d]) = (points, points) {
+				for [inner, end] in [[b, a], [c, d]] {
+					let (cap_vertices, cap_indices) = cap_geometry(inner, end, stroke_radius, u32::try_from(vertices.len()).unwrap());
+					vertices.extend(cap_vertices);
+					indices.extend(cap_indices);
 				}
 			}
 
@@ -138,6 +324,127 @@ impl Stroke {
 	}
 }
 
+impl Image {
+	// The canvas bounds of the image, including its border.
+	pub fn world_aabb(&self) -> Aabb {
+		let placement = [self.position[0].0, self.position[1].0, self.orientation, self.dilation, self.dimensions[0].0, self.dimensions[1].0, self.border_width.0];
+		self.world_aabb_cache.get_or_compute(placement, || {
+			let semidimensions = self.dimensions * 0.5 + Vex([self.border_width; 2]);
+			let corners = [[-1., -1.], [1., -1.], [1., 1.], [-1., 1.]].map(|[x, y]| self.position + Vex([semidimensions[0] * x, semidimensions[1] * y]).rotate(self.orientation) * self.dilation);
+			aabb_of(corners).unwrap_or((self.position, self.position))
+		})
+	}
+
+	// Stretches the image about a center; since images can't be sheared, the stretch is only exact for images at right angles to the canvas axes.
+	fn stretch_about(&mut self, center: Vex<2, Vx>, stretch: Vex<2, f32>) {
+		self.position = center + stretched(self.position - center, stretch);
+		let [x_axis, y_axis] = [Vex([1., 0.]), Vex([0., 1.])].map(|axis: Vex<2, f32>| axis.rotate(self.orientation));
+		let [x_stretch, y_stretch] = [x_axis, y_axis].map(|axis| (axis[0] * stretch[0]).hypot(axis[1] * stretch[1]));
+		self.dimensions = Vex([self.dimensions[0] * x_stretch, self.dimensions[1] * y_stretch]);
+	}
+}
+
+// The position and dimensions of an image, as they were before it was stretched.
+type ImagePlacement = (Vex<2, Vx>, Vex<2, Vx>);
+
+// The position and points of a stroke, as they were before it was stretched.
+type StrokePlacement = (Vex<2, Vx>, Vec<Point>);
+
+// Scales each component of a vector separately.
+fn stretched(vector: Vex<2, Vx>, stretch: Vex<2, f32>) -> Vex<2, Vx> {
+	Vex([vector[0] * stretch[0], vector[1] * stretch[1]])
+}
+
+// The appearance of a stroke, apart from its shape, which can be copied from one stroke onto others.
+#[derive(Clone, Copy)]
+pub struct StrokeStyle {
+	pub color: Srgba8,
+	pub stroke_radius: Vx,
+	pub is_smooth: bool,
+}
+
+// Fits a Catmull-Rom spline through the points, returning them with extra points interpolated along each segment in proportion to its length.
+// Tangents are weighted by the lengths of the neighboring segments, so that unevenly spaced points don't make the curve overshoot.
+fn smooth_points(points: &[Point]) -> Vec<Point> {
+	let Some(last) = points.last() else { return Vec::new() };
+	let mut smoothed_points = Vec::with_capacity(points.len());
+	for (i, [b, c]) in points.array_windows::<2>().enumerate() {
+		let a = if i > 0 { &points[i - 1] } else { b };
+		let d = points.get(i + 2).unwrap_or(c);
+		let [ab, bc, cd] = [(b.position - a.position).norm(), (c.position - b.position).norm(), (d.position - c.position).norm()];
+		let b_tangent = if ab + bc > Vx(0.) { (c.position - a.position) * (bc / (ab + bc)) } else { Vex::ZERO };
+		let c_tangent = if bc + cd > Vx(0.) { (d.position - b.position) * (bc / (bc + cd)) } else { Vex::ZERO };
+		let step_count = ((bc / SMOOTHING_STEP_LENGTH).ceil() as usize).clamp(1, SMOOTHING_MAX_STEP_COUNT);
+		for step in 0..step_count {
+			let t = step as f32 / step_count as f32;
+			// We evaluate the cubic Hermite basis functions.
+			let (t2, t3) = (t * t, t * t * t);
+			let position = b.position * (2. * t3 - 3. * t2 + 1.) + b_tangent * (t3 - 2. * t2 + t) + c.position * (3. * t2 - 2. * t3) + c_tangent * (t3 - t2);
+			smoothed_points.push(Point {
+				position,
+				pressure: b.pressure + (c.pressure - b.pressure) * t,
+				timestamp: b.timestamp + (c.timestamp.saturating_sub(b.timestamp) as f32 * t) as u32,
+			});
+		}
+	}
+	smoothed_points.push(last.clone());
+	smoothed_points
+}
+
+// The four bounding vertices of the segment from a to b, and its two triangles, whose indices start at base.
+fn segment_geometry(a: &Point, b: &Point, stroke_radius: Vx, base: u32) -> ([OutlineVertex; 4], [u32; 6]) {
+	// We compute a unit normal.
+	let perpendicular = {
+		let forward = b.position - a.position;
+		Vex([forward[1], -forward[0]]).normalized() * stroke_radius
+	};
+
+	(
+		[
+			(a.position + perpendicular * a.pressure, [1., 0.]),
+			(a.position - perpendicular * a.pressure, [-1., 0.]),
+			(b.position + perpendicular * b.pressure, [1., 0.]),
+			(b.position - perpendicular * b.pressure, [-1., 0.]),
+		],
+		[0, 2, 3, 0, 3, 1].map(|n| base + n),
+	)
+}
+
+// The four bounding vertices of the cap past the end of the segment from inner to end, which is a square as long as the end's radius, and its two triangles.
+fn cap_geometry(inner: &Point, end: &Point, stroke_radius: Vx, base: u32) -> ([OutlineVertex; 4], [u32; 6]) {
+	let forward = (end.position - inner.position).normalized() * stroke_radius * end.pressure;
+	let perpendicular = Vex([forward[1], -forward[0]]);
+
+	(
+		[
+			(end.position + perpendicular, [1., 0.]),
+			(end.position - perpendicular, [-1., 0.]),
+			(end.position + forward + perpendicular, [1., 1.]),
+			(end.position + forward - perpendicular, [-1., 1.]),
+		],
+		[0, 2, 3, 0, 3, 1].map(|n| base + n),
+	)
+}
+
+// The triangle filling the outer corner between the segments from a to b and from b to c, where the vertices of the former start at base.
+fn join_indices([a, b, c]: [&Point; 3], base: u32) -> Option<[u32; 3]> {
+	let p = b.position - a.position;
+	let q = c.position - b.position;
+	let cross_product = p.cross(q);
+
+	if cross_product > Vx2(0.) {
+		/* Clockwise */
+		#[allow(clippy::identity_op)]
+		Some([2, 4 + 0, 4 + 1].map(|n| n + base))
+	} else if cross_product < Vx2(0.) {
+		/* Counterclockwise */
+		#[allow(clippy::identity_op)]
+		Some([3, 4 + 1, 4 + 0].map(|n| n + base))
+	} else {
+		None
+	}
+}
+
 #[derive(Clone)]
 pub struct IncompleteStroke {
 	pub position: Vex<2, Vx>,
@@ -145,55 +452,233 @@ pub struct IncompleteStroke {
 	pub radius: Vx,
 	pub points: Vec<Point>,
 	pub max_pressure: f32,
+	// Divides the minimum distance between consecutive points.
+	pub point_density: f32,
+	// Whether tablet pressure is replaced by full pressure, giving the stroke a uniform width.
+	pub is_pressure_constant: bool,
+	// Whether the finished stroke is smoothed; the stroke in progress is always drawn as a polyline.
+	pub is_smooth: bool,
+	// The last pressure given by the low-pass filter over pressure readings.
+	filtered_pressure: Option<f32>,
+	pub start: Instant,
+	// The last position and time at which pressure was simulated, along with the simulated pressure.
+	last_simulated_sample: Option<(Vex<2, Vx>, Instant, f32)>,
+	// An extrapolated point, only ever shown in the preview.
+	pub predicted_point: Option<Point>,
+	last_sample_position: Option<Vex<2, Vx>>,
+	// The ruler that points are snapped to, relative to the position of the stroke.
+	ruler: Option<Ruler>,
+	// When the pen was lifted, if the stroke is waiting to see whether it will be continued.
+	pub lifted_at: Option<Instant>,
+	// The geometry of the points so far, which only ever grows, so that the renderer can upload just what is new.
+	pub vertices: Vec<OutlineVertex>,
+	pub relative_indices: Vec<u32>,
 }
 
 impl IncompleteStroke {
-	pub fn new(position: Vex<2, Vx>, canvas: &Canvas) -> Self {
+	// A screen-width stroke's radius is interpreted in logical pixels at the current zoom.
+	pub fn new(position: Vex<2, Vx>, canvas: &Canvas, is_screen_width: bool, point_density: f32, is_pressure_constant: bool, is_smooth: bool, is_snapping_on: bool) -> Self {
 		Self {
 			position,
-			color: canvas.stroke_color.to_srgb().to_srgb8().opaque(),
-			radius: canvas.stroke_radius,
+			color: canvas.stroke_color.to_srgba8(),
+			radius: if is_screen_width { Lx(canvas.stroke_radius.0).z(canvas.view.zoom) } else { canvas.stroke_radius },
 			points: Vec::new(),
 			max_pressure: 0.,
+			point_density,
+			is_pressure_constant,
+			is_smooth,
+			filtered_pressure: None,
+			start: Instant::now(),
+			last_simulated_sample: None,
+			predicted_point: None,
+			last_sample_position: None,
+			ruler: canvas.ruler.filter(|_| is_snapping_on).map(|ruler| ruler.translated(-position)),
+			lifted_at: None,
+			vertices: Vec::new(),
+			relative_indices: Vec::new(),
 		}
 	}
 
 	pub fn add_point(&mut self, position: Vex<2, Vx>, pressure: f32) {
+		let position = self.ruler.map_or(position, |ruler| ruler.project(position));
+		let pressure = if self.is_pressure_constant { 1. } else { pressure };
 		let threshold = if self.points.len() < 2 {
 			(self.max_pressure.max(pressure) * self.radius).max(Vx(1.))
 		} else {
 			self.max_pressure.max(pressure) * self.radius.min(Vx(1.))
-		};
+		} / self.point_density;
 		if self.points.last().map_or(true, |point| (position - point.position).norm() > threshold) {
-			self.points.push(Point { position, pressure });
+			let timestamp = (Instant::now() - self.start).as_millis().try_into().unwrap_or(u32::MAX);
+			self.points.push(Point { position, pressure, timestamp });
 			self.max_pressure = pressure;
+			self.extend_geometry();
 		} else {
 			self.max_pressure = self.max_pressure.max(pressure);
 		}
 	}
 
-	pub fn finalize(mut self) -> Stroke {
-		let local_centroid = if !self.points.is_empty() {
-			let local_centroid = self.points.iter().fold(Vex::ZERO, |acc, point| acc + point.position) / self.points.len() as f32;
-			for point in self.points.iter_mut() {
-				point.position = point.position - local_centroid;
-			}
-			local_centroid
-		} else {
-			Vex::ZERO
+	// Constrains a position to the line from the start of the stroke at the nearest multiple of 45 degrees on screen, for drawing straight lines.
+	pub fn straighten(&self, position: Vex<2, Vx>, tilt: f32) -> Vex<2, Vx> {
+		let Some(start) = self.points.first().map(|point| point.position) else { return position };
+		let vector = (position - start).rotate(-tilt);
+		let step = std::f32::consts::FRAC_PI_4;
+		let angle = (vector.angle() / step).round() * step;
+		let length = vector[0] * angle.cos() + vector[1] * angle.sin();
+		start + Vex([length * angle.cos(), length * angle.sin()]).rotate(tilt)
+	}
+
+	// Blends a pressure reading into an exponential moving average, keeping the given fraction of the previous average, so that noise doesn't make the width wobble.
+	pub fn filter_pressure(&mut self, pressure: f32, smoothing: f32) -> f32 {
+		let pressure = self.filtered_pressure.map_or(pressure, |filtered_pressure| filtered_pressure + (pressure - filtered_pressure) * (1. - smoothing));
+		self.filtered_pressure = Some(pressure);
+		pressure
+	}
+
+	// Derives a pressure from the speed of the pointer, so that faster movements give thinner lines.
+	pub fn simulate_pressure(&mut self, position: Vex<2, Vx>) -> f32 {
+		let now = Instant::now();
+		let pressure = match self.last_simulated_sample {
+			Some((last_position, last_instant, last_pressure)) => {
+				let elapsed = (now - last_instant).as_secs_f32();
+				if elapsed <= 0. {
+					return last_pressure;
+				}
+				// Measure speed in stroke radii per second, so that it is independent of zoom.
+				let speed = ((position - last_position).norm() / self.radius) / elapsed;
+				let target_pressure = (1. / (1. + speed / SIMULATED_PRESSURE_SPEED)).max(SIMULATED_PRESSURE_MINIMUM);
+				last_pressure + (target_pressure - last_pressure) * SIMULATED_PRESSURE_SMOOTHING
+			},
+			None => 1.,
 		};
+		self.last_simulated_sample = Some((position, now, pressure));
+		pressure
+	}
+
+	// Extrapolates the next sample from the velocity between the last two samples.
+	pub fn predict(&mut self, position: Vex<2, Vx>, pressure: f32) {
+		let position = self.ruler.map_or(position, |ruler| ruler.project(position));
+		let pressure = if self.is_pressure_constant { 1. } else { pressure };
+		self.predicted_point = self.last_sample_position.map(|last_sample_position| Point {
+			position: position + (position - last_sample_position),
+			pressure,
+			timestamp: self.points.last().map_or(0, |point| point.timestamp),
+		});
+		self.last_sample_position = Some(position);
+	}
 
+	// Marks the pen as lifted; a prediction across the gap would be meaningless, so it is dropped.
+	pub fn lift(&mut self) {
+		self.lifted_at = Some(Instant::now());
+		self.predicted_point = None;
+		self.last_sample_position = None;
+	}
+
+	pub fn finalize(mut self) -> Stroke {
 		if let [point] = self.points.as_mut_slice() {
 			point.pressure = self.max_pressure;
 		}
 
-		Stroke::new(self.color, self.radius, self.points, self.position + local_centroid, 0., 1.)
+		let mut stroke = Stroke::new(self.color, self.radius, self.points, self.is_smooth, self.position, 0., 1.);
+		stroke.recenter();
+		stroke
+	}
+
+	// Appends the segment ending at the newest point, along with the join before it, to the geometry of the stroke.
+	// Since a lone point isn't shown until the stroke is finalized, this matches the geometry of the finished stroke up to the order of its triangles, unless it is smoothed.
+	fn extend_geometry(&mut self) {
+		let segment_count = self.points.len().saturating_sub(1);
+		if let [.., a, b] = self.points.as_slice() {
+			let (vertices, indices) = segment_geometry(a, b, self.radius, u32::try_from((segment_count - 1) * 4).unwrap());
+			self.vertices.extend(vertices);
+			self.relative_indices.extend(indices);
+		}
+		if let [.., a, b, c] = self.points.as_slice() {
+			self.relative_indices.extend(join_indices([a, b, c], u32::try_from((segment_count - 2) * 4).unwrap()).into_iter().flatten());
+		}
+	}
+
+	// The geometry that the predicted point and the caps add to the stroke, indexed after the stroke's own vertices.
+	// Caps are redrawn every frame, since the end of the stroke keeps moving.
+	pub fn predicted_geometry(&self) -> (Vec<OutlineVertex>, Vec<u32>) {
+		let mut vertices = Vec::new();
+		let mut indices = Vec::new();
+		let segment_count = self.points.len().saturating_sub(1);
+		if let (Some(predicted_point), [.., a, b]) = (&self.predicted_point, self.points.as_slice()) {
+			let (segment_vertices, segment_indices) = segment_geometry(b, predicted_point, self.radius, u32::try_from(segment_count * 4).unwrap());
+			vertices.extend(segment_vertices);
+			indices.extend(segment_indices);
+			indices.extend(join_indices([a, b, predicted_point], u32::try_from((segment_count - 1) * 4).unwrap()).into_iter().flatten());
+		}
+
+		if let ([first, second, ..], [.., second_last, last]) = (self.points.as_slice(), self.points.as_slice()) {
+			let [second_last, last] = self.predicted_point.as_ref().map_or([second_last, last], |predicted_point| [last, predicted_point]);
+			for [inner, end] in [[second, first], [second_last, last]] {
+				let (cap_vertices, cap_indices) = cap_geometry(inner, end, self.radius, u32::try_from(segment_count * 4 + vertices.len()).unwrap());
+				vertices.extend(cap_vertices);
+				indices.extend(cap_indices);
+			}
+		}
+
+		(vertices, indices)
+	}
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SymmetryKind {
+	Vertical,
+	Horizontal,
+	Radial { segment_count: usize },
+}
+
+// Strokes drawn under symmetry are repeated about a center in canvas coordinates.
+#[derive(Clone, Copy)]
+pub struct Symmetry {
+	pub kind: SymmetryKind,
+	pub center: Vex<2, Vx>,
+}
+
+impl Symmetry {
+	// The unit directions, in canvas coordinates, of the axes (or rays, if radial) to draw as guides.
+	fn guide_directions(&self) -> Vec<Vex<2, f32>> {
+		match self.kind {
+			SymmetryKind::Vertical => vec![Vex([0., 1.]), Vex([0., -1.])],
+			SymmetryKind::Horizontal => vec![Vex([1., 0.]), Vex([-1., 0.])],
+			SymmetryKind::Radial { segment_count } => (0..segment_count).map(|k| Vex([0., -1.]).rotate(2. * std::f32::consts::PI * k as f32 / segment_count as f32)).collect(),
+		}
+	}
+
+	fn transform(&self, position: Vex<2, Vx>, copy_index: usize) -> Vex<2, Vx> {
+		let Vex([x, y]) = position;
+		let Vex([cx, cy]) = self.center;
+		match self.kind {
+			SymmetryKind::Vertical => Vex([cx + cx - x, y]),
+			SymmetryKind::Horizontal => Vex([x, cy + cy - y]),
+			SymmetryKind::Radial { segment_count } => position.rotate_about(self.center, 2. * std::f32::consts::PI * (copy_index + 1) as f32 / segment_count as f32),
+		}
 	}
 
-	pub fn preview(&self) -> Stroke {
-		let points = if self.points.len() != 1 { self.points.clone() } else { Vec::new() };
+	// Creates the copies of a newly finalized stroke, excluding the stroke itself.
+	pub fn copies(&self, stroke: &Stroke) -> Vec<Stroke> {
+		let copy_count = match self.kind {
+			SymmetryKind::Vertical | SymmetryKind::Horizontal => 1,
+			SymmetryKind::Radial { segment_count } => segment_count.saturating_sub(1),
+		};
 
-		Stroke::new(self.color, self.radius, points, self.position, 0., 1.)
+		(0..copy_count)
+			.map(|copy_index| {
+				let position = self.transform(stroke.position, copy_index);
+				let points = stroke
+					.points
+					.iter()
+					.map(|point| Point {
+						position: self.transform(stroke.position + point.position, copy_index) - position,
+						pressure: point.pressure,
+						timestamp: point.timestamp,
+					})
+					.collect();
+				Stroke::new(stroke.color, stroke.stroke_radius, points, stroke.is_smooth, position, 0., 1.)
+			})
+			.collect()
 	}
 }
 
@@ -208,6 +693,28 @@ enum Retraction {
 		index_color_pairs: Vec<(usize, Srgba8)>,
 		new_color: Srgba8,
 	},
+	RestyleStrokes {
+		index_style_pairs: Vec<(usize, StrokeStyle)>,
+		new_style: StrokeStyle,
+	},
+	FrameImages {
+		index_border_pairs: Vec<(usize, (Vx, Srgba8))>,
+		new_border: (Vx, Srgba8),
+	},
+	PixelateImages {
+		index_pixelation_pairs: Vec<(usize, bool)>,
+		is_pixelated: bool,
+	},
+	AnnotateObjects {
+		index_note_image_pairs: Vec<(usize, Option<String>)>,
+		index_note_stroke_pairs: Vec<(usize, Option<String>)>,
+		new_note: Option<String>,
+	},
+	ShowObjects {
+		index_visibility_image_pairs: Vec<(usize, bool)>,
+		index_visibility_stroke_pairs: Vec<(usize, bool)>,
+		is_visible: bool,
+	},
 	TranslateObjects {
 		image_indices: Vec<usize>,
 		stroke_indices: Vec<usize>,
@@ -225,18 +732,95 @@ enum Retraction {
 		center: Vex<2, Vx>,
 		dilation: f32,
 	},
+	ResizeObjectsNonUniform {
+		index_placement_image_pairs: Vec<(usize, ImagePlacement)>,
+		index_placement_stroke_pairs: Vec<(usize, StrokePlacement)>,
+		center: Vex<2, Vx>,
+		scale: Vex<2, f32>,
+	},
+	SetBackground {
+		old_color: Srgb8,
+		new_color: Srgb8,
+	},
+	SplitStrokes {
+		antitone_index_stroke_pairs: Vec<(usize, Stroke)>,
+		piece_count: usize,
+	},
 }
 
 pub enum Operation {
-	CommitStrokes { strokes: Vec<Tracked<Stroke>> },
-	CommitImages { images: Vec<Tracked<Image>> },
-	DeleteObjects { monotone_image_indices: Vec<usize>, monotone_stroke_indices: Vec<usize> },
-	RecolorStrokes { indices: Vec<usize>, new_color: Srgba8 },
-	TranslateObjects { image_indices: Vec<usize>, stroke_indices: Vec<usize>, vector: Vex<2, Vx> },
-	RotateObjects { image_indices: Vec<usize>, stroke_indices: Vec<usize>, center: Vex<2, Vx>, angle: f32 },
-	ResizeObjects { image_indices: Vec<usize>, stroke_indices: Vec<usize>, center: Vex<2, Vx>, dilation: f32 },
+	CommitStrokes {
+		strokes: Vec<Tracked<Stroke>>,
+	},
+	CommitImages {
+		images: Vec<Tracked<Image>>,
+	},
+	DeleteObjects {
+		monotone_image_indices: Vec<usize>,
+		monotone_stroke_indices: Vec<usize>,
+	},
+	RecolorStrokes {
+		indices: Vec<usize>,
+		new_color: Srgba8,
+	},
+	RestyleStrokes {
+		indices: Vec<usize>,
+		new_style: StrokeStyle,
+	},
+	FrameImages {
+		indices: Vec<usize>,
+		new_border: (Vx, Srgba8),
+	},
+	PixelateImages {
+		indices: Vec<usize>,
+		is_pixelated: bool,
+	},
+	AnnotateObjects {
+		image_indices: Vec<usize>,
+		stroke_indices: Vec<usize>,
+		new_note: Option<String>,
+	},
+	// Hides or shows objects without removing them; hidden objects are also deselected.
+	ShowObjects {
+		image_indices: Vec<usize>,
+		stroke_indices: Vec<usize>,
+		is_visible: bool,
+	},
+	TranslateObjects {
+		image_indices: Vec<usize>,
+		stroke_indices: Vec<usize>,
+		vector: Vex<2, Vx>,
+	},
+	RotateObjects {
+		image_indices: Vec<usize>,
+		stroke_indices: Vec<usize>,
+		center: Vex<2, Vx>,
+		angle: f32,
+	},
+	ResizeObjects {
+		image_indices: Vec<usize>,
+		stroke_indices: Vec<usize>,
+		center: Vex<2, Vx>,
+		dilation: f32,
+	},
+	// Stretches objects along the canvas axes, changing the dimensions of images and the points of strokes.
+	ResizeObjectsNonUniform {
+		image_indices: Vec<usize>,
+		stroke_indices: Vec<usize>,
+		center: Vex<2, Vx>,
+		scale: Vex<2, f32>,
+	},
+	SetBackground {
+		new_color: Srgb8,
+	},
+	// Replaces strokes with pieces of them, which are appended.
+	SplitStrokes {
+		monotone_stroke_indices: Vec<usize>,
+		pieces: Vec<Tracked<Stroke>>,
+	},
 }
 
+#[derive(Clone, Copy)]
 pub struct View {
 	pub position: Vex<2, Vx>,
 	pub tilt: f32,
@@ -244,11 +828,131 @@ pub struct View {
 }
 
 impl View {
-	fn new() -> Self {
+	pub fn new() -> Self {
 		Self { position: Vex::ZERO, tilt: 0., zoom: Zoom(1.) }
 	}
+
+	// The canvas point under a physical position in a window of the given physical size, undoing the viewport transform of the shaders.
+	pub fn canvas_position(&self, physical_position: Vex<2, Px>, [width, height]: [u32; 2], scale: Scale) -> Vex<2, Vx> {
+		let semidimensions = Vex([width as f32 / 2., height as f32 / 2.].map(Px)).s(scale).z(self.zoom);
+		self.position + (physical_position.s(scale).z(self.zoom) - semidimensions).rotate(self.tilt)
+	}
+
+	// Whether the views are far enough apart to warrant separate entries in the view history.
+	fn is_distant_from(&self, other: &View) -> bool {
+		(self.position - other.position).norm().z(self.zoom) > VIEW_HISTORY_DISTANCE_THRESHOLD || (self.zoom.0 / other.zoom.0).ln().abs() > VIEW_HISTORY_ZOOM_THRESHOLD.ln() || (self.tilt - other.tilt).abs() > VIEW_HISTORY_TILT_THRESHOLD
+	}
+}
+
+// Animates the view's position towards a target, easing in and out.
+#[derive(Clone, Copy)]
+struct ViewAnimation {
+	origin: Vex<2, Vx>,
+	target: Vex<2, Vx>,
+	start: Instant,
+}
+
+// Keeps the view gliding after a pan is released, slowing it until it stops.
+#[derive(Clone, Copy)]
+struct PanInertia {
+	// In canvas units per second.
+	velocity: Vex<2, Vx>,
+	last_step: Instant,
+}
+
+// Pans the view towards the edge of the window while a drag is held near it.
+#[derive(Clone, Copy)]
+struct EdgeScroll {
+	// In canvas units per second.
+	velocity: Vex<2, Vx>,
+	last_step: Instant,
+}
+
+// A line of operations that could be redone until an edit was made after undoing, set aside so that it can be switched back to.
+struct RedoBranch {
+	// How many retractions deep the branch forks from the line it was set aside from.
+	depth: usize,
+	operations: Vec<Operation>,
+	// The branches forking from this one, which are only reachable once it is switched to.
+	branches: Vec<RedoBranch>,
+}
+
+impl RedoBranch {
+	// Counts this branch and every branch nested within it.
+	fn count(&self) -> usize {
+		1 + self.branches.iter().map(RedoBranch::count).sum::<usize>()
+	}
+
+	// The images that redoing the branch, or any branch nested within it, would bring back.
+	fn committed_images(&self) -> Vec<&Image> {
+		let images = self.operations.iter().flat_map(|operation| match operation {
+			Operation::CommitImages { images } => images.iter().map(AsRef::as_ref).collect(),
+			_ => Vec::new(),
+		});
+		images.chain(self.branches.iter().flat_map(RedoBranch::committed_images)).collect()
+	}
+}
+
+// Redraws a canvas's strokes from scratch, in the order and timing that they were drawn.
+struct Replay {
+	strokes: Vec<Tracked<Stroke>>,
+	start: Instant,
+	// Whether the last stroke on the canvas is only partially drawn.
+	is_last_stroke_partial: bool,
+}
+
+// Refers to a single object on a canvas by its index.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ObjectRef {
+	Image(usize),
+	Stroke(usize),
+}
+
+// Objects that can be found one at a time, like "find next".
+pub enum FindTarget {
+	Images,
+	StrokesOfColor(Srgba8),
 }
 
+// The kinds of export that a canvas remembers, so that they can be repeated without prompting.
+#[derive(Clone, Copy)]
+pub enum ExportKind {
+	Selection,
+	Pdf,
+	Replay,
+}
+
+// Simulated pressure constants.
+// The speed, in stroke radii per second, at which the simulated pressure is halved.
+const SIMULATED_PRESSURE_SPEED: f32 = 64.;
+const SIMULATED_PRESSURE_MINIMUM: f32 = 0.3;
+const SIMULATED_PRESSURE_SMOOTHING: f32 = 0.25;
+
+// Stroke smoothing constants.
+// The longest stretch of a smoothed segment drawn as a straight line.
+const SMOOTHING_STEP_LENGTH: Vx = Vx(2.);
+const SMOOTHING_MAX_STEP_COUNT: usize = 16;
+
+// View history constants.
+const VIEW_HISTORY_CAPACITY: usize = 64;
+const VIEW_HISTORY_DISTANCE_THRESHOLD: Lx = Lx(64.);
+const VIEW_HISTORY_ZOOM_THRESHOLD: f32 = 1.25;
+const VIEW_HISTORY_TILT_THRESHOLD: f32 = 0.1;
+// View animation constants.
+const VIEW_ANIMATION_DURATION: Duration = Duration::from_millis(250);
+const VIEW_ANIMATION_FRAME_INTERVAL: Duration = Duration::from_micros(11_111);
+// Pan inertia constants.
+// The span of time over which the velocity of a pan is averaged.
+const PAN_VELOCITY_WINDOW: Duration = Duration::from_millis(50);
+// A pan held still for this long before it is released doesn't glide.
+const PAN_INERTIA_RELEASE_DELAY: Duration = Duration::from_millis(60);
+// The time it takes for a glide to slow to about a third of its speed.
+const PAN_INERTIA_TIME_CONSTANT: Duration = Duration::from_millis(325);
+// Glides slower than this, on screen, come to a stop.
+const PAN_INERTIA_MIN_SPEED: Lx = Lx(20.);
+// Replay constants.
+const REPLAY_STROKE_GAP: u32 = 150;
+
 // TODO: Move this somewhere saner.
 // Color selector constants in logical pixels/points.
 const TRIGON_RADIUS: Lx = Lx(68.);
@@ -256,9 +960,173 @@ const HOLE_RADIUS: Lx = Lx(80.);
 const RING_WIDTH: Lx = Lx(28.);
 const OUTLINE_WIDTH: Lx = Lx(2.);
 const SATURATION_VALUE_WINDOW_DIAMETER: Lx = Lx(8.);
+const ALPHA_SLIDER_GAP: Lx = Lx(16.);
+const ALPHA_SLIDER_WIDTH: Lx = Lx(16.);
+const ALPHA_SLIDER_STEP_COUNT: usize = 32;
+const ALPHA_HANDLE_HEIGHT: Lx = Lx(4.);
+const COLOR_READOUT_GAP: Lx = Lx(12.);
+// Roughly three lines of readout text.
+const COLOR_READOUT_HEIGHT: Lx = Lx(50.);
+// Harmony swatches are stacked in a column to the left of the hue ring.
+const HARMONY_SWATCH_DIAMETER: Lx = Lx(20.);
+const HARMONY_SWATCH_GAP: Lx = Lx(12.);
+// The hue offsets, in turns, of the complement, analogous colors, and triad of a color.
+const HARMONY_HUE_OFFSETS: [f32; 5] = [1. / 2., -1. / 12., 1. / 12., -1. / 3., 1. / 3.];
+// The least distance between a color selector placed in a corner and the edges of the window.
+const COLOR_SELECTOR_CORNER_MARGIN: Lx = Lx(16.);
+// The swatch ring leaves a hole in its middle where releasing picks nothing.
+const SWATCH_RING_RADIUS: Lx = Lx(72.);
+const SWATCH_DIAMETER: Lx = Lx(36.);
+const SWATCH_HOLE_RADIUS: Lx = Lx(24.);
+// Status bar constants in logical pixels/points.
+const STATUS_BAR_HEIGHT: Lx = Lx(20.);
+const STATUS_BAR_PADDING: Lx = Lx(6.);
+// Scale bar constants in logical pixels/points.
+const SCALE_BAR_MAX_LENGTH: Lx = Lx(128.);
+const SCALE_BAR_WIDTH: Lx = Lx(2.);
+const SCALE_BAR_MARGIN: Lx = Lx(12.);
+// Selection constants in logical pixels/points.
+const PICK_RADIUS: Lx = Lx(6.);
+// Edge scroll constants in logical pixels/points.
+const EDGE_SCROLL_MARGIN: Lx = Lx(32.);
+// The speed, per second, of panning with the cursor at the very edge of the window.
+const EDGE_SCROLL_SPEED: Lx = Lx(768.);
+// Eraser constants in logical pixels/points.
+const ERASER_RADIUS: Lx = Lx(8.);
+// Software cursor constants in logical pixels/points.
+const CROSSHAIR_LENGTH: Lx = Lx(17.);
+const CROSSHAIR_WIDTH: Lx = Lx(1.);
+// The target tapped to calibrate the pen, in logical pixels/points.
+const CALIBRATION_TARGET_LENGTH: Lx = Lx(48.);
+// Brush outline constants in logical pixels/points.
+const BRUSH_OUTLINE_DOT_DIAMETER: Lx = Lx(2.);
+const BRUSH_OUTLINE_DOT_SPACING: Lx = Lx(5.);
+const MIN_BRUSH_OUTLINE_DOT_COUNT: usize = 8;
+// Symmetry guide constants in logical pixels/points.
+const SYMMETRY_GUIDE_DOT_DIAMETER: Lx = Lx(2.);
+const SYMMETRY_GUIDE_DOT_SPACING: Lx = Lx(8.);
+// Baseline constants in logical pixels/points.
+const BASELINE_DOT_DIAMETER: Lx = Lx(1.5);
+const BASELINE_DOT_SPACING: Lx = Lx(6.);
+// Baselines closer together than this on screen are too dense to be drawn.
+const MIN_BASELINE_SPACING: Lx = Lx(4.);
+// Page constants in logical pixels/points.
+const PAGE_BORDER_WIDTH: Lx = Lx(1.5);
+
+const STROKE_ORDER_ARROW_LENGTH: Lx = Lx(16.);
+const STROKE_ORDER_ARROW_WIDTH: Lx = Lx(2.);
+
+const RULER_LENGTH: Lx = Lx(384.);
+const RULER_WIDTH: Lx = Lx(2.);
+const RULER_HANDLE_DIAMETER: Lx = Lx(10.);
+
+const CHECKERBOARD_CELL_SIZE: Lx = Lx(8.);
+// Note constants in logical pixels/points.
+const NOTE_MARKER_DIAMETER: Lx = Lx(8.);
+const TEXT_CAPTURE_HEIGHT: Lx = Lx(24.);
+// Banner constants in logical pixels/points.
+const BANNER_HEIGHT: Lx = Lx(24.);
+const BANNER_DURATION: Duration = Duration::from_secs(4);
+// Shortcut overlay constants in physical pixels, to match the size of text.
+const SHORTCUT_LINE_HEIGHT: Px = Px(16.25);
+const SHORTCUT_KEYS_WIDTH: Px = Px(112.);
+const SHORTCUT_COLUMN_WIDTH: Px = Px(352.);
+const SHORTCUT_MARGIN: Px = Px(32.);
+// Presentation constants.
+const PRESENTATION_CURSOR_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub struct Banner {
+	pub text: String,
+	pub expiry: Instant,
+}
+
+// Modally captures a line of text, which is passed to `on_commit` when Enter is pressed.
+pub struct TextCapture {
+	pub prompt: &'static str,
+	pub text: String,
+	pub on_commit: fn(&mut App, String),
+}
+
+// Modally asks whether to save or discard unsaved changes, which is answered with S or D, or cancelled with Escape.
+pub struct Confirmation {
+	pub prompt: &'static str,
+	pub on_save: fn(&mut App),
+	pub on_discard: fn(&mut App),
+}
+
+// The editing state set aside while presenting, restored once the presentation ends.
+pub struct Presentation {
+	previous_base_mode: Tool,
+	previous_selections: Vec<(Vec<usize>, Vec<usize>)>,
+	last_cursor_position: Vex<2, Px>,
+	last_cursor_motion: Instant,
+}
+
+impl Presentation {
+	fn cursor_idle_instant(&self) -> Instant {
+		self.last_cursor_motion + PRESENTATION_CURSOR_TIMEOUT
+	}
+}
 
 pub struct Multicanvas {
 	pub is_debug_mode_on: bool,
+	// Whether debug mode also numbers strokes in the order they were drawn, and marks where and which way each one starts.
+	pub is_stroke_order_overlay_on: bool,
+	pub is_status_bar_on: bool,
+	pub is_scale_bar_on: bool,
+	// Whether a checkerboard is shown in place of the background color, as if it were transparent.
+	pub is_checkerboard_on: bool,
+	// Whether a dotted circle shows the size and position of the next stroke while drawing.
+	pub is_brush_outline_on: bool,
+	// Whether points and objects are snapped to rulers, baselines, and pixels, each where it is enabled.
+	pub is_snapping_on: bool,
+	// Whether horizontal baselines are drawn across the canvas for writing along, and pull committed strokes toward them.
+	pub are_baselines_on: bool,
+	pub baseline_spacing: Vx,
+	pub baseline_snap: f32,
+	// The index of the color scheme last switched to, if any.
+	pub color_scheme_index: Option<usize>,
+	pub selection_color: Srgb8,
+	// The colors offered by the swatch ring.
+	pub palette: Vec<Srgba8>,
+	pub are_color_harmonies_on: bool,
+	pub is_software_cursor_on: bool,
+	pub is_input_prediction_on: bool,
+	pub is_screen_width_pen_on: bool,
+	pub is_constant_pressure_pen_on: bool,
+	pub is_stroke_smoothing_on: bool,
+	pub pressure_smoothing: f32,
+	// The raw pressures that are stretched to span from no pressure to full pressure.
+	pub pressure_range: [f32; 2],
+	pub is_pressure_simulation_on: bool,
+	pub point_density: f32,
+	// How long the pen may be lifted before the current stroke is committed.
+	pub stroke_lift_timeout: Duration,
+	pub should_commit_stroke_on_tool_switch: bool,
+	// How soon after an adjustment another like it is merged into the same undo step.
+	pub undo_coalescing: Duration,
+	pub eraser_mode: EraserMode,
+	pub tilt_snap_angle: f32,
+	pub is_edge_scroll_on: bool,
+	edge_scroll: Option<EdgeScroll>,
+	pub is_pan_inertia_on: bool,
+	pub should_auto_save_on_close: bool,
+	pub object_limit: usize,
+	// How many redo branches each canvas keeps, or zero to discard what could be redone on any edit.
+	pub redo_branch_limit: usize,
+	ruler_drag: Option<RulerDrag>,
+	pub should_presentation_hide_cursor: bool,
+	is_cursor_relevant: bool,
+	pub text_capture: Option<TextCapture>,
+	pub confirmation: Option<Confirmation>,
+	// Whether the next tap is taken as aiming at a target in the middle of the window, to measure the pen offset.
+	pub is_calibrating_pen: bool,
+	pub banner: Option<Banner>,
+	// A notice drawn over the dimmed window while blocking work, like saving, is underway.
+	pub busy_text: Option<&'static str>,
+	// The bindings listed by the shortcut overlay, while it is open.
+	pub shortcut_overlay: Option<Vec<BindingGroup>>,
+	pub presentation: Option<Presentation>,
 	pub canvases: Vec<Canvas>,
 	// Should only be `None` iff `canvases` is empty.
 	pub current_canvas_index: Option<usize>,
@@ -267,14 +1135,62 @@ pub struct Multicanvas {
 }
 
 impl Multicanvas {
-	pub fn new() -> Self {
-		Self {
+	pub fn new(config: &Config) -> Self {
+		let mut multicanvas = Self {
 			is_debug_mode_on: false,
-			canvases: Vec::new(),
-			current_canvas_index: None,
+			is_stroke_order_overlay_on: false,
+			is_status_bar_on: true,
+			is_scale_bar_on: false,
+			is_checkerboard_on: config.checkerboard,
+			is_brush_outline_on: config.brush_outline,
+			is_snapping_on: config.snapping,
+			are_baselines_on: config.baselines,
+			baseline_spacing: config.baseline_spacing,
+			baseline_snap: config.baseline_snap,
+			color_scheme_index: None,
+			selection_color: DEFAULT_SELECTION_COLOR,
+			palette: config.palette.clone(),
+			are_color_harmonies_on: config.color_harmonies,
+			is_software_cursor_on: config.software_cursor,
+			is_input_prediction_on: config.input_prediction,
+			is_screen_width_pen_on: config.screen_width_pen,
+			is_constant_pressure_pen_on: config.constant_pressure_pen,
+			is_stroke_smoothing_on: config.smooth_strokes,
+			pressure_smoothing: config.pressure_smoothing,
+			pressure_range: config.pressure_range,
+			is_pressure_simulation_on: config.pressure_simulation,
+			point_density: config.point_density,
+			stroke_lift_timeout: config.stroke_lift_timeout,
+			should_commit_stroke_on_tool_switch: config.commit_stroke_on_tool_switch,
+			undo_coalescing: config.undo_coalescing,
+			eraser_mode: config.eraser_mode,
+			tilt_snap_angle: config.tilt_snap_angle,
+			is_edge_scroll_on: config.edge_scroll,
+			edge_scroll: None,
+			is_pan_inertia_on: config.pan_inertia,
+			should_auto_save_on_close: config.auto_save_on_close,
+			object_limit: config.object_limit,
+			redo_branch_limit: config.redo_branches,
+			ruler_drag: None,
+			should_presentation_hide_cursor: config.presentation_hides_cursor,
+			is_cursor_relevant: false,
+			text_capture: None,
+			confirmation: None,
+			is_calibrating_pen: false,
+			banner: None,
+			busy_text: None,
+			shortcut_overlay: None,
+			presentation: None,
+			canvases: if config.open_canvas_on_launch { vec![Canvas::new(config)] } else { Vec::new() },
+			current_canvas_index: config.open_canvas_on_launch.then_some(0),
 			was_canvas_saved: false,
 			mode_stack: ModeStack::new(Tool::Draw { current_stroke: None }),
+		};
+		// Unknown tool names, and tools that are only ever held, leave the draw tool in use.
+		if !multicanvas.set_tool_by_name(&config.default_tool) {
+			log::warn!("The default tool \"{}\" can't be switched to, so the draw tool is used.", config.default_tool);
 		}
+		multicanvas
 	}
 
 	pub fn current_canvas(&self) -> Option<&Canvas> {
@@ -284,12 +1200,149 @@ impl Multicanvas {
 	pub fn current_canvas_mut(&mut self) -> Option<&mut Canvas> {
 		self.current_canvas_index.and_then(|x| self.canvases.get_mut(x))
 	}
+
+	// Switches the base tool by its key, e.g. "draw", returning whether the key names a base tool; every tool switch goes through here.
+	// The keys of transient tools, like "pan", are reported by current_tool_name while they are held, but can't be switched to.
+	pub fn set_tool_by_name(&mut self, name: &str) -> bool {
+		let Some((kind, switch)) = ToolKind::from_key(name).and_then(|kind| Some((kind, ModeStack::switch_for_kind(kind)?))) else {
+			return false;
+		};
+		// Switching away from the pen drops the stroke being drawn, unless it is committed first.
+		if self.should_commit_stroke_on_tool_switch && self.mode_stack.base_mode.kind() != kind {
+			self.commit_current_stroke();
+		}
+		switch(&mut self.mode_stack);
+		true
+	}
+
+	// The key of the tool in use, which may be transient.
+	pub fn current_tool_name(&self) -> &'static str {
+		self.mode_stack.get().kind().key()
+	}
+
+	pub fn show_banner(&mut self, text: String) {
+		self.banner = Some(Banner { text, expiry: Instant::now() + BANNER_DURATION });
+	}
+
+	// Hides the UI and selections, and restricts the tool to panning.
+	pub fn start_presentation(&mut self, cursor_physical_position: Vex<2, Px>) {
+		if self.presentation.is_some() {
+			return;
+		}
+
+		self.mode_stack.discard_draft();
+		self.mode_stack.invalidate_base_transformation_draft();
+		self.text_capture = None;
+		self.confirmation = None;
+
+		let previous_base_mode = std::mem::replace(&mut self.mode_stack.base_mode, Tool::Pan { origin: None });
+		let previous_selections = self
+			.canvases
+			.iter_mut()
+			.map(|canvas| {
+				let image_indices = canvas.images.iter().enumerate().filter_map(|(index, image)| if image.is_selected { Some(index) } else { None }).collect();
+				let stroke_indices = canvas.strokes.iter().enumerate().filter_map(|(index, stroke)| if stroke.is_selected { Some(index) } else { None }).collect();
+				canvas.select_all(false);
+				(image_indices, stroke_indices)
+			})
+			.collect();
+
+		self.presentation = Some(Presentation {
+			previous_base_mode,
+			previous_selections,
+			last_cursor_position: cursor_physical_position,
+			last_cursor_motion: Instant::now(),
+		});
+	}
+
+	// Restores the tool and selections from before the presentation.
+	pub fn stop_presentation(&mut self) {
+		let Some(presentation) = self.presentation.take() else { return };
+
+		self.mode_stack.discard_draft();
+		self.mode_stack.base_mode = presentation.previous_base_mode;
+
+		for (canvas, (image_indices, stroke_indices)) in self.canvases.iter_mut().zip(presentation.previous_selections) {
+			for index in image_indices {
+				if let Some(image) = canvas.images.get_mut(index) {
+					image.is_selected = true;
+				}
+			}
+
+			for index in stroke_indices {
+				if let Some(stroke) = canvas.strokes.get_mut(index) {
+					stroke.is_selected = true;
+				}
+			}
+		}
+	}
+
+	// Commits the current stroke if its pen was lifted for longer than the lift timeout, or at all if forced.
+	pub fn commit_lifted_stroke(&mut self, is_forced: bool) {
+		let Some(canvas) = self.current_canvas_index.and_then(|x| self.canvases.get_mut(x)) else { return };
+		let Tool::Draw { current_stroke } = &mut self.mode_stack.base_mode else { return };
+		if current_stroke.as_ref().and_then(|stroke| stroke.lifted_at).is_some_and(|lifted_at| is_forced || Instant::now() - lifted_at >= self.stroke_lift_timeout) {
+			commit_stroke(canvas, current_stroke.take().unwrap(), (self.are_baselines_on && self.is_snapping_on).then_some((self.baseline_spacing, self.baseline_snap)));
+		}
+	}
+
+	// Commits the current stroke, even while the pen is still down.
+	fn commit_current_stroke(&mut self) {
+		let Some(canvas) = self.current_canvas_index.and_then(|x| self.canvases.get_mut(x)) else { return };
+		let Tool::Draw { current_stroke } = &mut self.mode_stack.base_mode else { return };
+		if let Some(stroke) = current_stroke.take() {
+			commit_stroke(canvas, stroke, (self.are_baselines_on && self.is_snapping_on).then_some((self.baseline_spacing, self.baseline_snap)));
+		}
+	}
+
+	pub fn update_cursor_visibility(&self, window: &winit::window::Window) {
+		if self.is_cursor_relevant && self.current_canvas_index.is_some() {
+			// Hide the system cursor for tools that have a software cursor, and when idle during a presentation.
+			let is_cursor_hidden = match &self.presentation {
+				Some(presentation) => self.should_presentation_hide_cursor && Instant::now() >= presentation.cursor_idle_instant(),
+				None => self.is_software_cursor_on && matches!(self.mode_stack.get(), Tool::Draw { .. } | Tool::Select { .. }),
+			};
+			window.set_cursor_visible(!is_cursor_hidden);
+		}
+	}
+
+	// The next instant at which the window should be redrawn without any input.
+	pub fn next_wakeup_instant(&self) -> Option<Instant> {
+		let banner_expiry = self.banner.as_ref().map(|banner| banner.expiry);
+		let cursor_idle_instant = self
+			.presentation
+			.as_ref()
+			.filter(|_| self.should_presentation_hide_cursor)
+			.map(Presentation::cursor_idle_instant)
+			.filter(|instant| *instant > Instant::now());
+		let animation_frame_instant = (self.current_canvas().is_some_and(Canvas::is_animating) || self.edge_scroll.is_some()).then(|| Instant::now() + VIEW_ANIMATION_FRAME_INTERVAL);
+		let stroke_lift_expiry = self.mode_stack.current_stroke().and_then(|stroke| stroke.lifted_at).map(|lifted_at| lifted_at + self.stroke_lift_timeout);
+		banner_expiry.into_iter().chain(cursor_idle_instant).chain(animation_frame_instant).chain(stroke_lift_expiry).min()
+	}
 }
 
 impl Widget for Multicanvas {
 	fn update(&mut self, window: &winit::window::Window, renderer: &Renderer, input_monitor: &InputMonitor, is_cursor_relevant: bool, pressure: Option<f64>, cursor_physical_position: Vex<2, Px>, scale: Scale) {
 		use Button::*;
 		use Key::*;
+		self.is_cursor_relevant = is_cursor_relevant;
+		if let Some(presentation) = self.presentation.as_mut().filter(|presentation| presentation.last_cursor_position != cursor_physical_position) {
+			presentation.last_cursor_position = cursor_physical_position;
+			presentation.last_cursor_motion = Instant::now();
+		}
+		self.update_cursor_visibility(window);
+		let object_limit = self.object_limit;
+		if let Some(canvas) = self.current_canvas_mut() {
+			// Very large canvases slow down rendering, so going over the object limit is logged once and warned about until objects are removed.
+			let is_over_object_limit = object_limit > 0 && canvas.object_count() > object_limit;
+			if is_over_object_limit && !canvas.is_over_object_limit {
+				log::warn!("The canvas has {} objects, over the limit of {object_limit}.", canvas.object_count());
+			}
+			canvas.is_over_object_limit = is_over_object_limit;
+		}
+		for canvas in self.canvases.iter_mut() {
+			canvas.trim_redo_branches(self.redo_branch_limit);
+		}
 		if let Some(canvas) = self.current_canvas_index.and_then(|x| self.canvases.get_mut(x)) {
 			let semidimensions = Vex([renderer.config.width as f32 / 2., renderer.config.height as f32 / 2.].map(Px)).s(scale).z(canvas.view.zoom);
 			let cursor_virtual_position = (cursor_physical_position.s(scale).z(canvas.view.zoom) - semidimensions).rotate(canvas.view.tilt);
@@ -301,21 +1354,43 @@ impl Widget for Multicanvas {
 					}
 					if input_monitor.active_buttons.contains(Left) {
 						if input_monitor.different_buttons.contains(Left) && current_stroke.is_none() {
-							*current_stroke = Some(IncompleteStroke::new(cursor_virtual_position, canvas));
+							*current_stroke = Some(IncompleteStroke::new(
+								cursor_virtual_position,
+								canvas,
+								self.is_screen_width_pen_on,
+								self.point_density,
+								self.is_constant_pressure_pen_on,
+								self.is_stroke_smoothing_on,
+								self.is_snapping_on,
+							));
 						}
 
 						if let Some(current_stroke) = current_stroke {
+							// The pen touching down again within the lift timeout continues the stroke.
+							current_stroke.lifted_at = None;
 							let offset = canvas.view.position + cursor_virtual_position - current_stroke.position;
-							current_stroke.add_point(
-								offset,
-								pressure.map_or(1., |pressure| {
-									let x = (pressure / 32767.) as f32;
+							// Holding Shift keeps the stroke on a straight line from where it started, until it is released.
+							let offset = if input_monitor.active_keys.contains(Shift) { current_stroke.straighten(offset, canvas.view.tilt) } else { offset };
+							let pressure = match pressure {
+								Some(pressure) => {
+									let [min, max] = self.pressure_range;
+									let x = (((pressure / 32767.) as f32 - min) / (max - min)).clamp(0., 1.);
 									x * (17. + x * -18. + x * x * 7.) / 6.
-								}),
-							)
+								},
+								None if self.is_pressure_simulation_on => current_stroke.simulate_pressure(offset),
+								None => 1.,
+							};
+							let pressure = current_stroke.filter_pressure(pressure, self.pressure_smoothing);
+							current_stroke.add_point(offset, pressure);
+							if self.is_input_prediction_on {
+								current_stroke.predict(offset, pressure);
+							}
 						}
-					} else if let Some(stroke) = current_stroke.take() {
-						canvas.perform_operation(Operation::CommitStrokes { strokes: vec![stroke.finalize().into()] });
+					} else if let Some(stroke) = current_stroke.as_mut().filter(|stroke| stroke.lifted_at.is_none() && !self.stroke_lift_timeout.is_zero()) {
+						// Brief lifts of the pen shouldn't split the stroke, so it is only committed once the lift timeout passes.
+						stroke.lift();
+					} else if current_stroke.as_ref().is_some_and(|stroke| stroke.lifted_at.map_or(true, |lifted_at| Instant::now() - lifted_at >= self.stroke_lift_timeout)) {
+						commit_stroke(canvas, current_stroke.take().unwrap(), (self.are_baselines_on && self.is_snapping_on).then_some((self.baseline_spacing, self.baseline_snap)));
 					}
 				},
 				Tool::Select { origin } => {
@@ -329,35 +1404,99 @@ impl Widget for Multicanvas {
 							*origin = Some(offset);
 						}
 					} else if let Some(origin) = origin.take() {
-						let offset = cursor_virtual_position.rotate(-canvas.view.tilt);
-						let origin = (origin - canvas.view.position).rotate(-canvas.view.tilt);
-						let min = Vex([offset[0].min(origin[0]), offset[1].min(origin[1])]);
-						let max = Vex([offset[0].max(origin[0]), offset[1].max(origin[1])]);
-						canvas.select(min, max, canvas.view.tilt, canvas.view.position, input_monitor.active_keys.contains(Shift));
+						// A click without a drag selects the topmost object beneath the cursor.
+						if (offset - origin).norm().z(canvas.view.zoom) <= PICK_RADIUS {
+							let picked_object = canvas.pick(offset, PICK_RADIUS.z(canvas.view.zoom));
+							let should_aggregate = input_monitor.active_keys.contains(Shift);
+							if !should_aggregate {
+								canvas.select_all(false);
+							}
+							match picked_object {
+								Some(ObjectRef::Image(index)) => canvas.images[index].is_selected ^= true,
+								Some(ObjectRef::Stroke(index)) => canvas.strokes[index].is_selected ^= true,
+								None => {},
+							}
+						} else {
+							let offset = cursor_virtual_position.rotate(-canvas.view.tilt);
+							let origin = (origin - canvas.view.position).rotate(-canvas.view.tilt);
+							let min = Vex([offset[0].min(origin[0]), offset[1].min(origin[1])]);
+							let max = Vex([offset[0].max(origin[0]), offset[1].max(origin[1])]);
+							canvas.select(min, max, canvas.view.tilt, canvas.view.position, input_monitor.active_keys.contains(Shift));
+						}
 					}
 				},
-				Tool::Pan { origin } => {
+				Tool::Erase { path } => {
+					let position = cursor_virtual_position + canvas.view.position;
+					let radius = ERASER_RADIUS.z(canvas.view.zoom);
+					if is_cursor_relevant {
+						window.set_cursor_icon(winit::window::CursorIcon::Crosshair);
+					}
+
+					if input_monitor.active_buttons.contains(Left) {
+						let path = path.get_or_insert_with(Vec::new);
+						// Space the circles of the path half a radius apart, filling in fast movements so that the path has no gaps.
+						match path.last().copied() {
+							Some(last_position) if (position - last_position).norm() < radius / 2. => {},
+							Some(last_position) => {
+								let step_count = (2. * (position - last_position).norm() / radius).ceil() as usize;
+								path.extend((1..=step_count).map(|step| last_position + (position - last_position) * (step as f32 / step_count as f32)));
+							},
+							None => path.push(position),
+						}
+					} else if let Some(path) = path.take() {
+						let mode = if input_monitor.active_keys.contains(Shift) {
+							EraserMode::Color
+						} else if input_monitor.active_keys.contains(Control) {
+							EraserMode::Segment
+						} else {
+							self.eraser_mode
+						};
+						canvas.erase(&path, radius, mode);
+					}
+				},
+				Tool::Pan { origin } => {
 					if input_monitor.active_buttons.contains(Left) {
 						if is_cursor_relevant {
 							window.set_cursor_icon(winit::window::CursorIcon::Grabbing);
 						}
 						if origin.is_none() {
+							canvas.pan_inertia = None;
 							*origin = Some(PanOrigin {
 								cursor: cursor_virtual_position,
 								position: canvas.view.position,
+								velocity: Vex::ZERO,
+								last_sample: (Instant::now(), canvas.view.position),
 							});
 						}
 					} else {
 						if is_cursor_relevant {
 							window.set_cursor_icon(winit::window::CursorIcon::Grab);
 						}
-						if origin.is_some() {
-							*origin = None;
+						if let Some(origin) = origin.take() {
+							let is_moving = origin.last_sample.0.elapsed() < PAN_INERTIA_RELEASE_DELAY && origin.velocity.norm().z(canvas.view.zoom) >= PAN_INERTIA_MIN_SPEED;
+							if self.is_pan_inertia_on && is_moving {
+								canvas.pan_inertia = Some(PanInertia {
+									velocity: origin.velocity,
+									last_step: Instant::now(),
+								});
+							} else {
+								canvas.commit_view();
+							}
 						}
 					}
 
 					if let Some(origin) = origin {
-						canvas.view.position = origin.position - (cursor_virtual_position - origin.cursor);
+						let position = origin.position - (cursor_virtual_position - origin.cursor);
+						// Samples are only taken when the view moves, so that the velocity reflects how fast it was moving when it last did.
+						let (last_instant, last_position) = origin.last_sample;
+						let now = Instant::now();
+						let elapsed = (now - last_instant).as_secs_f32();
+						if position != last_position && elapsed > 0. {
+							let weight = (elapsed / PAN_VELOCITY_WINDOW.as_secs_f32()).min(1.);
+							origin.velocity = origin.velocity * (1. - weight) + (position - last_position) * (weight / elapsed);
+							origin.last_sample = (now, position);
+						}
+						canvas.view.position = position;
 					}
 				},
 				Tool::Zoom { origin } => {
@@ -378,6 +1517,7 @@ impl Widget for Multicanvas {
 						}
 						if origin.is_some() {
 							*origin = None;
+							canvas.commit_view();
 						}
 					}
 
@@ -403,14 +1543,18 @@ impl Widget for Multicanvas {
 						if is_cursor_relevant {
 							window.set_cursor_icon(winit::window::CursorIcon::Grab);
 						}
-						*initial = None;
+						if initial.take().is_some() {
+							canvas.commit_view();
+						}
 					}
 
 					if let Some(OrbitInitial { tilt, cursor_angle }) = initial {
 						let semidimensions = Vex([renderer.config.width as f32 / 2., renderer.config.height as f32 / 2.].map(Px));
 						let vector = cursor_physical_position - semidimensions;
 						let angle = vector.angle();
-						canvas.view.tilt = *tilt - angle + *cursor_angle;
+						let tilt = *tilt - angle + *cursor_angle;
+						// Holding Control snaps the tilt to the nearest multiple of the snap angle.
+						canvas.view.tilt = if input_monitor.active_keys.contains(Control) { (tilt / self.tilt_snap_angle).round() * self.tilt_snap_angle } else { tilt };
 					}
 				},
 				Tool::Move { origin } => {
@@ -418,10 +1562,29 @@ impl Widget for Multicanvas {
 						window.set_cursor_icon(winit::window::CursorIcon::Move);
 					}
 
+					let offset = canvas.view.position + cursor_virtual_position;
 					if input_monitor.active_buttons.contains(Left) {
-						if input_monitor.different_buttons.contains(Left) && origin.is_none() {
-							*origin = Some(canvas.view.position + cursor_virtual_position);
+						if input_monitor.different_buttons.contains(Left) && origin.is_none() && self.ruler_drag.is_none() {
+							// Grabbing the ruler moves it, or one of its endpoints, instead of the selection.
+							match canvas.ruler.and_then(|ruler| Some((ruler, ruler.pick(offset, PICK_RADIUS.z(canvas.view.zoom))?))) {
+								Some((ruler, part)) => self.ruler_drag = Some(RulerDrag { part, origin: offset, initial_ruler: ruler }),
+								None => *origin = Some(offset),
+							}
 						}
+
+						if let (Some(ruler_drag), Some(ruler)) = (&self.ruler_drag, canvas.ruler.as_mut()) {
+							let vector = offset - ruler_drag.origin;
+							*ruler = match ruler_drag.part {
+								RulerPart::Endpoint(index) => {
+									let mut ruler = ruler_drag.initial_ruler;
+									ruler.endpoints[index] = ruler.endpoints[index] + vector;
+									ruler
+								},
+								RulerPart::Body => ruler_drag.initial_ruler.translated(vector),
+							};
+						}
+					} else if self.ruler_drag.take().is_some() {
+						// Letting go of the ruler leaves the selection as it is.
 					} else if let Some(origin) = origin.take() {
 						let selection_offset = canvas.view.position + cursor_virtual_position - origin;
 
@@ -430,11 +1593,14 @@ impl Widget for Multicanvas {
 						let selected_stroke_indices = canvas.strokes().iter().enumerate().filter_map(|(index, stroke)| stroke.is_selected.then_some(index)).collect::<Vec<_>>();
 
 						if !selected_image_indices.is_empty() || !selected_stroke_indices.is_empty() {
-							canvas.perform_operation(Operation::TranslateObjects {
-								image_indices: selected_image_indices,
-								stroke_indices: selected_stroke_indices,
-								vector: selection_offset,
-							});
+							canvas.perform_coalescing_operation(
+								Operation::TranslateObjects {
+									image_indices: selected_image_indices,
+									stroke_indices: selected_stroke_indices,
+									vector: selection_offset,
+								},
+								self.undo_coalescing,
+							);
 						}
 
 						canvas.selection_transformation = Default::default();
@@ -495,53 +1661,85 @@ impl Widget for Multicanvas {
 
 							let center = if count > 0 { sum / count as f32 } else { Vex::ZERO };
 
+							let initial_offset = canvas.view.position + cursor_virtual_position - center;
 							*origin = Some({
 								ResizeDraft {
 									center,
-									initial_distance: (canvas.view.position + cursor_virtual_position - center).norm(),
+									initial_distance: initial_offset.norm(),
+									initial_offset,
+									is_aspect_locked: false,
 								}
 							});
 						}
-					} else if let Some(ResizeDraft { center, initial_distance }) = origin.take() {
-						let selection_distance = (canvas.view.position + cursor_virtual_position - center).norm();
-						let dilation = selection_distance / initial_distance;
+						// Holding Shift keeps the aspect ratio of the selection.
+						if let Some(draft) = origin {
+							draft.is_aspect_locked = input_monitor.active_keys.contains(Shift);
+						}
+					} else if let Some(draft) = origin.take() {
+						let offset = canvas.view.position + cursor_virtual_position - draft.center;
 
 						let selected_image_indices = canvas.images().iter().enumerate().filter_map(|(index, image)| if image.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
 
 						let selected_stroke_indices = canvas.strokes().iter().enumerate().filter_map(|(index, stroke)| if stroke.is_selected { Some(index) } else { None }).collect::<Vec<_>>();
 
 						if !selected_image_indices.is_empty() || !selected_stroke_indices.is_empty() {
-							canvas.perform_operation(Operation::ResizeObjects {
-								image_indices: selected_image_indices,
-								stroke_indices: selected_stroke_indices,
-								center,
-								dilation,
+							canvas.perform_operation(if draft.is_aspect_locked {
+								Operation::ResizeObjects {
+									image_indices: selected_image_indices,
+									stroke_indices: selected_stroke_indices,
+									center: draft.center,
+									dilation: offset.norm() / draft.initial_distance,
+								}
+							} else {
+								Operation::ResizeObjectsNonUniform {
+									image_indices: selected_image_indices,
+									stroke_indices: selected_stroke_indices,
+									center: draft.center,
+									scale: draft.stretch(offset, PICK_RADIUS.z(canvas.view.zoom)),
+								}
 							});
 						}
 
 						canvas.selection_transformation = Default::default();
 					}
 				},
-				Tool::PickColor { cursor_physical_origin, part } => {
+				Tool::PickColor { cursor_physical_origin, part, target } => {
 					if is_cursor_relevant {
 						window.set_cursor_icon(winit::window::CursorIcon::Crosshair);
 					}
 
 					if input_monitor.active_buttons.contains(Left) {
+						// The background has no alpha, so only the stroke color has an alpha slider.
+						let is_alpha_editable = matches!(target, ColorTarget::Stroke);
+						let color = match target {
+							ColorTarget::Stroke => &mut canvas.stroke_color,
+							ColorTarget::Background { draft, .. } => draft,
+						};
 						let cursor = cursor_physical_position;
 						let vector = cursor - *cursor_physical_origin;
+						// The alpha slider spans the height of the hue ring, to its right.
+						let alpha_slider_left = (HOLE_RADIUS + RING_WIDTH + ALPHA_SLIDER_GAP).s(scale);
+						let alpha_slider_semilength = (HOLE_RADIUS + RING_WIDTH).s(scale);
 						if part.is_none() && input_monitor.different_buttons.contains(Left) {
 							let magnitude = vector.norm();
 							if magnitude >= HOLE_RADIUS.s(scale) && magnitude <= (HOLE_RADIUS + RING_WIDTH).s(scale) {
 								*part = Some(ColorSelectionPart::Hue);
 							} else if 2. * vector[1] < TRIGON_RADIUS.s(scale) && -(3.0f32.sqrt()) * vector[0] - vector[1] < TRIGON_RADIUS.s(scale) && (3.0f32.sqrt()) * vector[0] - vector[1] < TRIGON_RADIUS.s(scale) {
 								*part = Some(ColorSelectionPart::SaturationValue);
+							} else if is_alpha_editable && vector[0] >= alpha_slider_left && vector[0] <= alpha_slider_left + ALPHA_SLIDER_WIDTH.s(scale) && vector[1].abs() <= alpha_slider_semilength {
+								*part = Some(ColorSelectionPart::Alpha);
+							} else if let Some((hue_offset, _)) = harmony_swatch_offsets(scale)
+								.filter(|_| self.are_color_harmonies_on)
+								.find(|(_, offset)| (vector - *offset).norm() <= (HARMONY_SWATCH_DIAMETER / 2.).s(scale))
+							{
+								// Clicking a harmony adopts its hue, without dragging anything.
+								color[0] = (color[0] + hue_offset).rem_euclid(1.);
 							}
 						}
 
 						match part {
 							Some(ColorSelectionPart::Hue) => {
-								canvas.stroke_color[0] = vector.angle() / (2.0 * std::f32::consts::PI) + 0.5;
+								color[0] = vector.angle() / (2.0 * std::f32::consts::PI) + 0.5;
 							},
 							Some(ColorSelectionPart::SaturationValue) => {
 								let scaled_vector = vector / TRIGON_RADIUS.s(scale);
@@ -550,23 +1748,66 @@ impl Widget for Multicanvas {
 								let scaled_vector = scaled_vector + -other * (dot - dot.min(0.5));
 								let scaled_vector = Vex([scaled_vector[0].max(-(3.0f32.sqrt()) / 2.), scaled_vector[1].min(0.5)]);
 								let s = (1. - 2. * scaled_vector[1]) / (2. + 3.0f32.sqrt() * scaled_vector[0] - scaled_vector[1]);
-								canvas.stroke_color[1] = if s.is_nan() { 0. } else { s.clamp(0., 1.) };
-								canvas.stroke_color[2] = ((2. + 3.0f32.sqrt() * scaled_vector[0] - scaled_vector[1]) / 3.).clamp(0., 1.);
+								color[1] = if s.is_nan() { 0. } else { s.clamp(0., 1.) };
+								color[2] = ((2. + 3.0f32.sqrt() * scaled_vector[0] - scaled_vector[1]) / 3.).clamp(0., 1.);
+							},
+							Some(ColorSelectionPart::Alpha) => {
+								color[3] = ((alpha_slider_semilength - vector[1]) / (2. * alpha_slider_semilength)).clamp(0., 1.);
 							},
 							None => {},
 						}
+
+						// Preview the background draft on the canvas itself.
+						if let ColorTarget::Background { draft, .. } = target {
+							canvas.background_color = draft.to_srgb().to_srgb8();
+						}
 					} else {
 						*part = None;
 					}
 				},
+				Tool::PickSwatch { center, hovered_index } => {
+					if is_cursor_relevant {
+						window.set_cursor_icon(winit::window::CursorIcon::Default);
+					}
+
+					let vector = cursor_physical_position - *center;
+					*hovered_index = if vector.norm() >= SWATCH_HOLE_RADIUS.s(scale) { swatch_index_at(vector.angle(), self.palette.len()) } else { None };
+				},
+			}
+
+			// Dragging a selection near the edge of the window pans towards that edge, faster the closer the cursor is.
+			let is_dragging = input_monitor.active_buttons.contains(Left) && matches!(self.mode_stack.get(), Tool::Select { origin: Some(_) } | Tool::Move { origin: Some(_) });
+			let window_dimensions = Vex([renderer.config.width as f32, renderer.config.height as f32].map(Px));
+			let margin = EDGE_SCROLL_MARGIN.s(scale);
+			let edge_scroll_velocity = Vex([0, 1].map(|i| {
+				let towards_start = ((margin - cursor_physical_position[i]) / margin).clamp(0., 1.);
+				let towards_end = ((cursor_physical_position[i] - window_dimensions[i] + margin) / margin).clamp(0., 1.);
+				EDGE_SCROLL_SPEED * (towards_end - towards_start)
+			}))
+			.z(canvas.view.zoom)
+			.rotate(canvas.view.tilt);
+			if self.is_edge_scroll_on && is_dragging && edge_scroll_velocity != Vex::ZERO {
+				let last_step = self.edge_scroll.map_or_else(Instant::now, |edge_scroll| edge_scroll.last_step);
+				self.edge_scroll = Some(EdgeScroll { velocity: edge_scroll_velocity, last_step });
+			} else if self.edge_scroll.take().is_some() {
+				canvas.commit_view();
 			}
 		}
 	}
 
 	fn prepare<'a>(&'a mut self, renderer: &mut Renderer, scale: Scale, cursor_physical_position: Vex<2, Px>, prerender: &mut Prerender<'a>) {
+		// The canvas is borrowed for the rest of the frame, so the tool is named for the status bar first.
+		let tool_name = self.current_tool_name();
 		let mut current_canvas = self.current_canvas_index.and_then(|x| self.canvases.get_mut(x));
 
 		if let Some(canvas) = current_canvas.as_mut() {
+			canvas.advance_view_animation();
+			canvas.advance_pan_inertia();
+			if let Some(edge_scroll) = self.edge_scroll.as_mut() {
+				let now = Instant::now();
+				canvas.view.position = canvas.view.position + edge_scroll.velocity * (now - edge_scroll.last_step).as_secs_f32();
+				edge_scroll.last_step = now;
+			}
 			let semidimensions = Vex([renderer.config.width as f32 / 2., renderer.config.height as f32 / 2.].map(Px)).s(scale).z(canvas.view.zoom);
 			let cursor_virtual_position = (cursor_physical_position.s(scale).z(canvas.view.zoom) - semidimensions).rotate(canvas.view.tilt);
 
@@ -592,15 +1833,20 @@ impl Widget for Multicanvas {
 						..Default::default()
 					};
 				},
-				Tool::Resize {
-					origin: Some(ResizeDraft { center, initial_distance }),
-				} => {
-					let selection_distance = (canvas.view.position + cursor_virtual_position - center).norm();
-					let dilation = selection_distance / initial_distance;
-					*canvas.selection_transformation = SelectionTransformation {
-						center_of_transformation: *center,
-						dilation,
-						..Default::default()
+				Tool::Resize { origin: Some(draft) } => {
+					let offset = canvas.view.position + cursor_virtual_position - draft.center;
+					*canvas.selection_transformation = if draft.is_aspect_locked {
+						SelectionTransformation {
+							center_of_transformation: draft.center,
+							dilation: offset.norm() / draft.initial_distance,
+							..Default::default()
+						}
+					} else {
+						SelectionTransformation {
+							center_of_transformation: draft.center,
+							stretch: draft.stretch(offset, PICK_RADIUS.z(canvas.view.zoom)),
+							..Default::default()
+						}
 					};
 				},
 				_ => {},
@@ -642,22 +1888,49 @@ impl Widget for Multicanvas {
 						color: srgba8.0,
 						radius: hue_window_width / 2.,
 					});
+					// Show the tilt in whole degrees, within a single turn.
+					let tilt_degrees = (canvas.view.tilt.to_degrees().round() as i64).rem_euclid(360);
+					prerender.draw_commands.push(DrawCommand::Text {
+						text: format!("{tilt_degrees}°").into(),
+						align: Some(Align::Center),
+						position: center + Vex([Px(0.), hue_outline_width / 2. + COLOR_READOUT_GAP.s(scale)]),
+						anchors: [0.5, 0.],
+					});
+				},
+				Tool::Erase { path: Some(path) } => {
+					// Trace the path of the eraser until it is released.
+					let diameter = (ERASER_RADIUS * 2.).s(scale);
+					for position in path {
+						let position = ((*position - canvas.view.position).rotate(-canvas.view.tilt) + semidimensions).z(canvas.view.zoom).s(scale);
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: position.map(|x| x - diameter / 2.),
+							dimensions: Vex([diameter; 2]),
+							color: [0xff, 0xff, 0xff, 0x22],
+							radius: diameter / 2.,
+						});
+					}
 				},
-				Tool::PickColor { cursor_physical_origin: cursor_origin, .. } => {
+				Tool::PickColor {
+					cursor_physical_origin: cursor_origin, target, ..
+				} => {
+					let color = match target {
+						ColorTarget::Stroke => canvas.stroke_color,
+						ColorTarget::Background { draft, .. } => *draft,
+					};
 					prerender.draw_commands.push(DrawCommand::ColorSelector {
 						position: cursor_origin.map(|x| x - (HOLE_RADIUS + RING_WIDTH).s(scale)),
-						hsv: canvas.stroke_color.0,
+						hsv: [color[0], color[1], color[2]],
 						trigon_radius: TRIGON_RADIUS.s(scale),
 						hole_radius: HOLE_RADIUS.s(scale),
 						ring_width: RING_WIDTH.s(scale),
 					});
 
-					let srgba8 = canvas.stroke_color.to_srgb().to_srgb8().opaque();
+					let srgba8 = color.to_srgb().to_srgb8().opaque();
 
 					let ring_position = cursor_origin
 						+ Vex([
-							(HOLE_RADIUS + RING_WIDTH / 2.).s(scale) * -(canvas.stroke_color[0] * 2. * core::f32::consts::PI).cos(),
-							(HOLE_RADIUS + RING_WIDTH / 2.).s(scale) * -(canvas.stroke_color[0] * 2. * core::f32::consts::PI).sin(),
+							(HOLE_RADIUS + RING_WIDTH / 2.).s(scale) * -(color[0] * 2. * core::f32::consts::PI).cos(),
+							(HOLE_RADIUS + RING_WIDTH / 2.).s(scale) * -(color[0] * 2. * core::f32::consts::PI).sin(),
 						]);
 
 					let hue_outline_width = (RING_WIDTH + 4. * OUTLINE_WIDTH).s(scale);
@@ -682,11 +1955,7 @@ impl Widget for Multicanvas {
 						radius: hue_window_width / 2.,
 					});
 
-					let trigon_position = cursor_origin
-						+ Vex([
-							3.0f32.sqrt() * (canvas.stroke_color[2] - 0.5 * (canvas.stroke_color[1] * canvas.stroke_color[2] + 1.)),
-							0.5 * (1. - 3. * canvas.stroke_color[1] * canvas.stroke_color[2]),
-						]) * TRIGON_RADIUS.s(scale);
+					let trigon_position = cursor_origin + Vex([3.0f32.sqrt() * (color[2] - 0.5 * (color[1] * color[2] + 1.)), 0.5 * (1. - 3. * color[1] * color[2])]) * TRIGON_RADIUS.s(scale);
 
 					let sv_outline_width = (SATURATION_VALUE_WINDOW_DIAMETER + (4. * OUTLINE_WIDTH)).s(scale);
 					let sv_frame_width = (SATURATION_VALUE_WINDOW_DIAMETER + (2. * OUTLINE_WIDTH)).s(scale);
@@ -709,34 +1978,762 @@ impl Widget for Multicanvas {
 						color: srgba8.0,
 						radius: sv_window_width / 2.,
 					});
+
+					if self.are_color_harmonies_on {
+						let outline_diameter = (HARMONY_SWATCH_DIAMETER + 4. * OUTLINE_WIDTH).s(scale);
+						let frame_diameter = (HARMONY_SWATCH_DIAMETER + 2. * OUTLINE_WIDTH).s(scale);
+						let window_diameter = HARMONY_SWATCH_DIAMETER.s(scale);
+						for (hue_offset, offset) in harmony_swatch_offsets(scale) {
+							let mut harmony = color;
+							harmony[0] = (harmony[0] + hue_offset).rem_euclid(1.);
+							let harmony_center = *cursor_origin + offset;
+							for (diameter, color) in [(outline_diameter, [0xff; 4]), (frame_diameter, [0x00, 0x00, 0x00, 0xff]), (window_diameter, harmony.to_srgb().to_srgb8().opaque().0)] {
+								prerender.draw_commands.push(DrawCommand::Card {
+									position: harmony_center.map(|x| x - diameter / 2.),
+									dimensions: Vex([diameter; 2]),
+									color,
+									radius: diameter / 2.,
+								});
+							}
+						}
+					}
+
+					if matches!(target, ColorTarget::Stroke) {
+						// Draw the alpha slider as a stack of increasingly translucent steps, with the current alpha marked by a handle.
+						let alpha_slider_position = cursor_origin + Vex([(HOLE_RADIUS + RING_WIDTH + ALPHA_SLIDER_GAP).s(scale), -(HOLE_RADIUS + RING_WIDTH).s(scale)]);
+						let alpha_slider_dimensions = Vex([ALPHA_SLIDER_WIDTH.s(scale), (2. * (HOLE_RADIUS + RING_WIDTH)).s(scale)]);
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: alpha_slider_position.map(|x| x - (2. * OUTLINE_WIDTH).s(scale)),
+							dimensions: alpha_slider_dimensions.map(|x| x + (4. * OUTLINE_WIDTH).s(scale)),
+							color: [0xff; 4],
+							radius: Px(0.),
+						});
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: alpha_slider_position.map(|x| x - OUTLINE_WIDTH.s(scale)),
+							dimensions: alpha_slider_dimensions.map(|x| x + (2. * OUTLINE_WIDTH).s(scale)),
+							color: [0x00, 0x00, 0x00, 0xff],
+							radius: Px(0.),
+						});
+						let step_height = alpha_slider_dimensions[1] / ALPHA_SLIDER_STEP_COUNT as f32;
+						for step in 0..ALPHA_SLIDER_STEP_COUNT {
+							let [r, g, b, _] = srgba8.0;
+							let alpha = 1. - (step as f32 + 0.5) / ALPHA_SLIDER_STEP_COUNT as f32;
+							prerender.draw_commands.push(DrawCommand::Card {
+								position: alpha_slider_position + Vex([Px(0.), step_height * step as f32]),
+								dimensions: Vex([alpha_slider_dimensions[0], step_height]),
+								color: [r, g, b, (alpha * 255.) as u8],
+								radius: Px(0.),
+							});
+						}
+						let alpha_handle_position = alpha_slider_position + Vex([Px(0.), alpha_slider_dimensions[1] * (1. - color[3])]);
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: alpha_handle_position - Vex([(2. * OUTLINE_WIDTH).s(scale), (ALPHA_HANDLE_HEIGHT / 2. + OUTLINE_WIDTH).s(scale)]),
+							dimensions: Vex([alpha_slider_dimensions[0] + (4. * OUTLINE_WIDTH).s(scale), (ALPHA_HANDLE_HEIGHT + 2. * OUTLINE_WIDTH).s(scale)]),
+							color: [0x00, 0x00, 0x00, 0xff],
+							radius: Px(0.),
+						});
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: alpha_handle_position - Vex([OUTLINE_WIDTH.s(scale), (ALPHA_HANDLE_HEIGHT / 2.).s(scale)]),
+							dimensions: Vex([alpha_slider_dimensions[0] + (2. * OUTLINE_WIDTH).s(scale), ALPHA_HANDLE_HEIGHT.s(scale)]),
+							color: [0xff; 4],
+							radius: Px(0.),
+						});
+					}
+
+					// Show the numeric values of the color below the selector.
+					let [r, g, b, _] = srgba8.0;
+					let [h, s, v, a] = color.0.map(|x| x * 100.);
+					let hue_degrees = h * 3.6;
+					let alpha_readout = if matches!(target, ColorTarget::Stroke) { format!("\nA {a:.0}%") } else { String::new() };
+					prerender.draw_commands.push(DrawCommand::Text {
+						text: format!("R {r}  G {g}  B {b}\nH {hue_degrees:.0}°  S {s:.0}%  V {v:.0}%{alpha_readout}").into(),
+						align: Some(Align::Center),
+						position: cursor_origin + Vex([Px(0.), (HOLE_RADIUS + RING_WIDTH + COLOR_READOUT_GAP).s(scale)]),
+						anchors: [0.5, 0.],
+					});
+				},
+				Tool::PickSwatch { center, hovered_index } => {
+					// The current color sits in the hole, where releasing keeps it.
+					let current_diameter = (SWATCH_HOLE_RADIUS * 2. - 4. * OUTLINE_WIDTH).s(scale);
+					let current_frame_diameter = (SWATCH_HOLE_RADIUS * 2. - 2. * OUTLINE_WIDTH).s(scale);
+					prerender.draw_commands.push(DrawCommand::Card {
+						position: center.map(|x| x - current_frame_diameter / 2.),
+						dimensions: Vex([current_frame_diameter; 2]),
+						color: [0x00, 0x00, 0x00, 0xff],
+						radius: current_frame_diameter / 2.,
+					});
+					prerender.draw_commands.push(DrawCommand::Card {
+						position: center.map(|x| x - current_diameter / 2.),
+						dimensions: Vex([current_diameter; 2]),
+						color: canvas.stroke_color.to_srgba8().0,
+						radius: current_diameter / 2.,
+					});
+
+					// Large palettes widen the ring so that their swatches don't overlap.
+					let ring_radius = SWATCH_RING_RADIUS.max((SWATCH_DIAMETER + 4. * OUTLINE_WIDTH) * (self.palette.len() as f32 / std::f32::consts::TAU));
+					for (index, color) in self.palette.iter().enumerate() {
+						let angle = swatch_angle(index, self.palette.len());
+						let position = *center + Vex([angle.cos(), angle.sin()]) * ring_radius.s(scale);
+						let is_hovered = *hovered_index == Some(index);
+						// The hovered swatch is outlined in white, like the handles of the color selector.
+						let outline_diameter = (SWATCH_DIAMETER + 4. * OUTLINE_WIDTH).s(scale);
+						if is_hovered {
+							prerender.draw_commands.push(DrawCommand::Card {
+								position: position.map(|x| x - outline_diameter / 2.),
+								dimensions: Vex([outline_diameter; 2]),
+								color: [0xff; 4],
+								radius: outline_diameter / 2.,
+							});
+						}
+						for (diameter, color) in [((SWATCH_DIAMETER + 2. * OUTLINE_WIDTH).s(scale), [0x00, 0x00, 0x00, 0xff]), (SWATCH_DIAMETER.s(scale), color.0)] {
+							prerender.draw_commands.push(DrawCommand::Card {
+								position: position.map(|x| x - diameter / 2.),
+								dimensions: Vex([diameter; 2]),
+								color,
+								radius: diameter / 2.,
+							});
+						}
+					}
 				},
 				_ => {},
 			}
 
-			if self.is_debug_mode_on {
+			let is_ui_hidden = self.presentation.is_some();
+
+			// Draw dotted baselines across the window, which follow the tilt of the view.
+			if self.are_baselines_on && !is_ui_hidden && self.baseline_spacing.z(canvas.view.zoom) >= MIN_BASELINE_SPACING {
+				let semidiagonal = semidimensions.norm();
+				let direction = Vex([1., 0.]).rotate(-canvas.view.tilt);
+				let dot_diameter = BASELINE_DOT_DIAMETER.s(scale);
+				let dot_spacing = BASELINE_DOT_SPACING.s(scale);
+				// Each baseline is dotted from one side of the circle around the window to the other, through the point nearest the center.
+				let first_index = ((canvas.view.position[1] - semidiagonal) / self.baseline_spacing).ceil() as i64;
+				let last_index = ((canvas.view.position[1] + semidiagonal) / self.baseline_spacing).floor() as i64;
+				for index in first_index..=last_index {
+					let offset = Vex([-semidiagonal, self.baseline_spacing * index as f32 - canvas.view.position[1]]);
+					let start = (offset.rotate(-canvas.view.tilt) + semidimensions).z(canvas.view.zoom).s(scale);
+					for position in dotted_line(start, direction, (semidiagonal * 2.).z(canvas.view.zoom).s(scale).0, [renderer.config.width, renderer.config.height], dot_spacing) {
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: position.map(|x| x - dot_diameter / 2.),
+							dimensions: Vex([dot_diameter; 2]),
+							color: [0x80, 0xa0, 0xff, 0x55],
+							radius: dot_diameter / 2.,
+						});
+					}
+				}
+			}
+
+			// Draw faint dotted guides along the symmetry axes.
+			if let Some(symmetry) = canvas.symmetry.filter(|_| !is_ui_hidden) {
+				let center = ((symmetry.center - canvas.view.position).rotate(-canvas.view.tilt) + semidimensions).z(canvas.view.zoom).s(scale);
+				let dot_diameter = SYMMETRY_GUIDE_DOT_DIAMETER.s(scale);
+				let dot_spacing = SYMMETRY_GUIDE_DOT_SPACING.s(scale);
+				for direction in symmetry.guide_directions() {
+					for position in dotted_line(center, direction.rotate(-canvas.view.tilt), f32::INFINITY, [renderer.config.width, renderer.config.height], dot_spacing) {
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: position.map(|x| x - dot_diameter / 2.),
+							dimensions: Vex([dot_diameter; 2]),
+							color: [0xff, 0xff, 0xff, 0x44],
+							radius: dot_diameter / 2.,
+						});
+					}
+				}
+			}
+
+			// Outline the page as a line of square dots, which follows the tilt of the view.
+			if let Some(page) = canvas.page {
+				let corners =
+					[Vex::ZERO, Vex([page.dimensions[0], Vx(0.)]), page.dimensions, Vex([Vx(0.), page.dimensions[1]])].map(|corner| ((page.position + corner - canvas.view.position).rotate(-canvas.view.tilt) + semidimensions).z(canvas.view.zoom).s(scale));
+				let border_width = PAGE_BORDER_WIDTH.s(scale);
+				for (index, start) in corners.into_iter().enumerate() {
+					let delta = corners[(index + 1) % corners.len()] - start;
+					let length = delta.norm();
+					if length.0 <= 0. {
+						continue;
+					}
+					for position in dotted_line(start, delta.map(|x| x.0 / length.0), length.0, [renderer.config.width, renderer.config.height], border_width) {
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: position.map(|x| x - border_width / 2.),
+							dimensions: Vex([border_width; 2]),
+							color: [0xff, 0xff, 0xff, 0x66],
+							radius: Px(0.),
+						});
+					}
+				}
+			}
+
+			// Draw the ruler as a line of round dots between two round handles.
+			if let Some(ruler) = canvas.ruler.filter(|_| !is_ui_hidden) {
+				let [start, end] = ruler.endpoints.map(|endpoint| ((endpoint - canvas.view.position).rotate(-canvas.view.tilt) + semidimensions).z(canvas.view.zoom).s(scale));
+				let delta = end - start;
+				let length = delta.norm();
+				let ruler_width = RULER_WIDTH.s(scale);
+				if length.0 > 0. {
+					for position in dotted_line(start, delta.map(|x| x.0 / length.0), length.0, [renderer.config.width, renderer.config.height], ruler_width) {
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: position.map(|x| x - ruler_width / 2.),
+							dimensions: Vex([ruler_width; 2]),
+							color: [0xff, 0xc0, 0x40, 0xcc],
+							radius: ruler_width / 2.,
+						});
+					}
+				}
+				let handle_outline_diameter = (RULER_HANDLE_DIAMETER + 2. * OUTLINE_WIDTH).s(scale);
+				let handle_diameter = RULER_HANDLE_DIAMETER.s(scale);
+				for endpoint in [start, end] {
+					prerender.draw_commands.push(DrawCommand::Card {
+						position: endpoint.map(|x| x - handle_outline_diameter / 2.),
+						dimensions: Vex([handle_outline_diameter; 2]),
+						color: [0x00, 0x00, 0x00, 0xff],
+						radius: handle_outline_diameter / 2.,
+					});
+					prerender.draw_commands.push(DrawCommand::Card {
+						position: endpoint.map(|x| x - handle_diameter / 2.),
+						dimensions: Vex([handle_diameter; 2]),
+						color: [0xff, 0xc0, 0x40, 0xff],
+						radius: handle_diameter / 2.,
+					});
+				}
+			}
+
+			// Mark objects with notes, and show the notes of selected objects.
+			let images = canvas.images.iter().filter(|image| image.is_visible).map(|image| (image.position, image.is_selected, &image.note));
+			let strokes = canvas.strokes.iter().filter(|stroke| stroke.is_visible).map(|stroke| (stroke.position, stroke.is_selected, &stroke.note));
+			for (position, is_selected, note) in images.chain(strokes).filter(|_| !is_ui_hidden) {
+				let Some(note) = note else { continue };
+				let marker_position = ((position - canvas.view.position).rotate(-canvas.view.tilt) + semidimensions).z(canvas.view.zoom).s(scale);
+				let marker_outline_diameter = (NOTE_MARKER_DIAMETER + 2. * OUTLINE_WIDTH).s(scale);
+				let marker_diameter = NOTE_MARKER_DIAMETER.s(scale);
+				prerender.draw_commands.push(DrawCommand::Card {
+					position: marker_position.map(|x| x - marker_outline_diameter / 2.),
+					dimensions: Vex([marker_outline_diameter; 2]),
+					color: [0x00, 0x00, 0x00, 0xff],
+					radius: marker_outline_diameter / 2.,
+				});
+				prerender.draw_commands.push(DrawCommand::Card {
+					position: marker_position.map(|x| x - marker_diameter / 2.),
+					dimensions: Vex([marker_diameter; 2]),
+					color: [0xff, 0xc1, 0x07, 0xff],
+					radius: marker_diameter / 2.,
+				});
+				if is_selected {
+					prerender.draw_commands.push(DrawCommand::Text {
+						text: note.clone().into(),
+						align: Some(Align::Left),
+						position: marker_position + Vex([marker_outline_diameter, Px(0.)]),
+						anchors: [0., 0.5],
+					});
+				}
+			}
+
+			if self.is_debug_mode_on && !is_ui_hidden {
 				let [x, y] = canvas.view.position.0.map(|Vx(a)| a);
 				let zoom = canvas.view.zoom.0;
 				let tilt = canvas.view.tilt;
+				// The statistics are those of the previous frame, since this one hasn't been rendered yet.
+				let RenderStatistics {
+					canvas_buffer_size,
+					interface_buffer_size,
+					uploaded_stroke_count,
+					uploaded_vertex_count,
+					uploaded_index_count,
+					draw_call_count,
+				} = renderer.statistics;
+				let [canvas_buffer_kib, interface_buffer_kib] = [canvas_buffer_size, interface_buffer_size].map(|size| size / 1024);
 				prerender.draw_commands.push(DrawCommand::Text {
-					text: format!("position: ({x:.0}, {y:.0})\nzoom: {zoom:.2}\ntilt: {tilt:.2}").into(),
+					text: format!(
+						"position: ({x:.0}, {y:.0})\nzoom: {zoom:.2}\ntilt: {tilt:.2}\nbuffers: {canvas_buffer_kib} KiB canvas, {interface_buffer_kib} KiB interface\nuploaded: {uploaded_stroke_count} strokes, {uploaded_vertex_count} vertices, {uploaded_index_count} indices\ndraw calls: {draw_call_count}"
+					)
+					.into(),
 					align: Some(Align::Right),
 					position: Vex([Px(renderer.config.width as f32 - scale.0 * 4.), Px(scale.0 * 4.)]),
 					anchors: [1., 0.],
 				});
+
+				if self.is_stroke_order_overlay_on {
+					let window_dimensions = [renderer.config.width, renderer.config.height];
+					let arrow_width = STROKE_ORDER_ARROW_WIDTH.s(scale);
+					for (index, stroke) in canvas.strokes.iter().enumerate().filter(|(_, stroke)| stroke.is_visible) {
+						let mut points = stroke.points.iter().map(|point| {
+							((stroke.position + point.position.rotate(stroke.orientation) * stroke.dilation - canvas.view.position).rotate(-canvas.view.tilt) + semidimensions)
+								.z(canvas.view.zoom)
+								.s(scale)
+						});
+						let Some(start) = points.next() else { continue };
+						if !(0..2).all(|axis| start[axis].0 >= 0. && start[axis].0 <= window_dimensions[axis] as f32) {
+							continue;
+						}
+
+						// The arrow points from the first point towards the first point that is apart from it.
+						if let Some(direction) = points.map(|point| point - start).find(|delta| delta.norm().0 > 0.) {
+							let direction = direction.map(|x| x.0 / direction.norm().0);
+							for position in dotted_line(start, direction, STROKE_ORDER_ARROW_LENGTH.s(scale).0, window_dimensions, arrow_width) {
+								prerender.draw_commands.push(DrawCommand::Card {
+									position: position.map(|x| x - arrow_width / 2.),
+									dimensions: Vex([arrow_width; 2]),
+									color: [0xff, 0x40, 0x40, 0xff],
+									radius: Px(0.),
+								});
+							}
+						}
+						prerender.draw_commands.push(DrawCommand::Card {
+							position: start.map(|x| x - arrow_width),
+							dimensions: Vex([arrow_width * 2.; 2]),
+							color: [0xff, 0x40, 0x40, 0xff],
+							radius: arrow_width,
+						});
+						prerender.draw_commands.push(DrawCommand::Text {
+							text: index.to_string().into(),
+							align: Some(Align::Right),
+							position: start - Vex([arrow_width * 2.; 2]),
+							anchors: [1., 1.],
+						});
+					}
+				}
+			}
+
+			if self.is_status_bar_on && !is_ui_hidden {
+				let [x, y] = canvas.view.position.0.map(|Vx(a)| a);
+				let zoom_percentage = canvas.view.zoom.0 * 100.;
+				let selected_count = canvas.strokes.iter().filter(|stroke| stroke.is_selected).count() + canvas.images.iter().filter(|image| image.is_selected).count();
+				let stroke_count = canvas.strokes.len();
+				let image_count = canvas.images.len();
+				let hidden_count = canvas.strokes.iter().filter(|stroke| !stroke.is_visible).count() + canvas.images.iter().filter(|image| !image.is_visible).count();
+				let hidden_indicator = if hidden_count > 0 { format!(" ({hidden_count} hidden)") } else { String::new() };
+				let snapping_indicator = if self.is_snapping_on { "    snapping" } else { "" };
+				let redo_branch_indicator = match canvas.redo_branch_count() {
+					0 => String::new(),
+					count => format!("    {} redo branches", count + 1),
+				};
+				let window_width = Px(renderer.config.width as f32);
+				let window_height = Px(renderer.config.height as f32);
+				prerender.draw_commands.push(DrawCommand::Card {
+					position: Vex([Px(0.), window_height - STATUS_BAR_HEIGHT.s(scale)]),
+					dimensions: Vex([window_width, STATUS_BAR_HEIGHT.s(scale)]),
+					color: [0x00, 0x00, 0x00, 0x99],
+					radius: Px(0.),
+				});
+				prerender.draw_commands.push(DrawCommand::Text {
+					text: format!("{zoom_percentage:.0}%    ({x:.0}, {y:.0})    {tool_name}    {selected_count} selected    {stroke_count} strokes, {image_count} images{hidden_indicator}{snapping_indicator}{redo_branch_indicator}").into(),
+					align: Some(Align::Left),
+					position: Vex([STATUS_BAR_PADDING.s(scale), window_height - (STATUS_BAR_HEIGHT / 2.).s(scale)]),
+					anchors: [0., 0.5],
+				});
+			}
+
+			if canvas.is_over_object_limit && !is_ui_hidden {
+				let window_width = Px(renderer.config.width as f32);
+				let window_height = Px(renderer.config.height as f32);
+				let bottom = if self.is_status_bar_on { window_height - STATUS_BAR_HEIGHT.s(scale) } else { window_height };
+				prerender.draw_commands.push(DrawCommand::Card {
+					position: Vex([Px(0.), bottom - STATUS_BAR_HEIGHT.s(scale)]),
+					dimensions: Vex([window_width, STATUS_BAR_HEIGHT.s(scale)]),
+					color: [0x90, 0x60, 0x00, 0xcc],
+					radius: Px(0.),
+				});
+				prerender.draw_commands.push(DrawCommand::Text {
+					text: format!(
+						"This canvas has {} objects, over the limit of {}, and may be slow. Consider moving some to a new tab (Ctrl-Shift-N).",
+						canvas.object_count(),
+						self.object_limit
+					)
+					.into(),
+					align: Some(Align::Left),
+					position: Vex([STATUS_BAR_PADDING.s(scale), bottom - (STATUS_BAR_HEIGHT / 2.).s(scale)]),
+					anchors: [0., 0.5],
+				});
 			}
+
+			// Draw a bar in the bottom-right corner spanning a round number of canvas units.
+			if self.is_scale_bar_on && !is_ui_hidden {
+				let max_length = SCALE_BAR_MAX_LENGTH.z(canvas.view.zoom).0;
+				let magnitude = 10f32.powf(max_length.log10().floor());
+				let length = Vx([5., 2., 1.].into_iter().map(|x| x * magnitude).find(|&x| x <= max_length).unwrap_or(magnitude));
+				let window_width = Px(renderer.config.width as f32);
+				let window_height = Px(renderer.config.height as f32);
+				let bottom = if self.is_status_bar_on { window_height - STATUS_BAR_HEIGHT.s(scale) } else { window_height } - SCALE_BAR_MARGIN.s(scale);
+				let right = window_width - SCALE_BAR_MARGIN.s(scale);
+				let bar_length = length.z(canvas.view.zoom).s(scale);
+				let bar_width = SCALE_BAR_WIDTH.s(scale);
+				prerender.draw_commands.push(DrawCommand::Card {
+					position: Vex([right - bar_length, bottom - bar_width]),
+					dimensions: Vex([bar_length, bar_width]),
+					color: [0xff, 0xff, 0xff, 0xcc],
+					radius: Px(0.),
+				});
+				prerender.draw_commands.push(DrawCommand::Text {
+					text: format!("{:.*}", (-magnitude.log10().round()).max(0.) as usize, length.0).into(),
+					align: Some(Align::Center),
+					position: Vex([right - bar_length / 2., bottom - bar_width * 2.]),
+					anchors: [0.5, 1.],
+				});
+			}
+		}
+
+		// The brush outline is dotted, with each dot drawn light on dark, so that it is visible on any background without hiding what is beneath it.
+		if let Some(canvas) = current_canvas
+			.as_ref()
+			.filter(|_| self.is_brush_outline_on && self.is_cursor_relevant && self.presentation.is_none() && matches!(self.mode_stack.get(), Tool::Draw { .. }))
+		{
+			let brush_radius = if self.is_screen_width_pen_on { Lx(canvas.stroke_radius.0) } else { canvas.stroke_radius.z(canvas.view.zoom) };
+			let dot_count = ((brush_radius * 2. * std::f32::consts::PI / BRUSH_OUTLINE_DOT_SPACING) as usize).max(MIN_BRUSH_OUTLINE_DOT_COUNT);
+			for (diameter, color) in [((BRUSH_OUTLINE_DOT_DIAMETER + 2. * OUTLINE_WIDTH).s(scale), [0x00, 0x00, 0x00, 0x99]), (BRUSH_OUTLINE_DOT_DIAMETER.s(scale), [0xff, 0xff, 0xff, 0xcc])] {
+				for i in 0..dot_count {
+					let angle = i as f32 / dot_count as f32 * std::f32::consts::TAU;
+					let dot_center = cursor_physical_position + Vex([angle.cos(), angle.sin()]) * brush_radius.s(scale);
+					prerender.draw_commands.push(DrawCommand::Card {
+						position: dot_center.map(|x| x - diameter / 2.),
+						dimensions: Vex([diameter; 2]),
+						color,
+						radius: diameter / 2.,
+					});
+				}
+			}
+		}
+
+		if let Some(canvas) = current_canvas.as_ref().filter(|_| self.is_software_cursor_on && self.is_cursor_relevant && self.presentation.is_none()) {
+			match self.mode_stack.get() {
+				Tool::Draw { .. } => {
+					let srgba8 = canvas.stroke_color.to_srgba8();
+					let brush_radius = if self.is_screen_width_pen_on { Lx(canvas.stroke_radius.0) } else { canvas.stroke_radius.z(canvas.view.zoom) };
+					let brush_outline_diameter = (brush_radius * 2. + 2. * OUTLINE_WIDTH).s(scale);
+					let brush_diameter = (brush_radius * 2.).s(scale);
+					prerender.draw_commands.push(DrawCommand::Card {
+						position: cursor_physical_position.map(|x| x - brush_outline_diameter / 2.),
+						dimensions: Vex([brush_outline_diameter; 2]),
+						color: [0x00, 0x00, 0x00, 0x66],
+						radius: brush_outline_diameter / 2.,
+					});
+					prerender.draw_commands.push(DrawCommand::Card {
+						position: cursor_physical_position.map(|x| x - brush_diameter / 2.),
+						dimensions: Vex([brush_diameter; 2]),
+						color: [srgba8.0[0], srgba8.0[1], srgba8.0[2], 0x99],
+						radius: brush_diameter / 2.,
+					});
+				},
+				Tool::Select { .. } => draw_crosshair(&mut prerender.draw_commands, cursor_physical_position, CROSSHAIR_LENGTH, scale),
+				_ => {},
+			}
+		}
+
+		if self.is_calibrating_pen {
+			let center = Vex([renderer.config.width as f32 / 2., renderer.config.height as f32 / 2.].map(Px));
+			draw_crosshair(&mut prerender.draw_commands, center, CALIBRATION_TARGET_LENGTH, scale);
+		}
+
+		let modal_text = match (&self.text_capture, &self.confirmation) {
+			(Some(TextCapture { prompt, text, .. }), _) => Some(format!("{prompt}: {text}|")),
+			(None, Some(Confirmation { prompt, .. })) => Some(format!("{prompt} Save (S), discard (D), or cancel (Esc).")),
+			(None, None) if self.is_calibrating_pen => Some("Tap the center of the target with the pen, or cancel (Esc).".to_owned()),
+			(None, None) => None,
+		};
+		if let Some(modal_text) = modal_text {
+			let window_width = Px(renderer.config.width as f32);
+			let window_height = Px(renderer.config.height as f32);
+			let bottom = if self.is_status_bar_on && current_canvas.is_some() { window_height - STATUS_BAR_HEIGHT.s(scale) } else { window_height };
+			prerender.draw_commands.push(DrawCommand::Card {
+				position: Vex([Px(0.), bottom - TEXT_CAPTURE_HEIGHT.s(scale)]),
+				dimensions: Vex([window_width, TEXT_CAPTURE_HEIGHT.s(scale)]),
+				color: [0x22, 0x22, 0x22, 0xee],
+				radius: Px(0.),
+			});
+			prerender.draw_commands.push(DrawCommand::Text {
+				text: modal_text.into(),
+				align: Some(Align::Left),
+				position: Vex([STATUS_BAR_PADDING.s(scale), bottom - (TEXT_CAPTURE_HEIGHT / 2.).s(scale)]),
+				anchors: [0., 0.5],
+			});
+		}
+
+		if self.banner.as_ref().is_some_and(|banner| Instant::now() >= banner.expiry) {
+			self.banner = None;
 		}
 
+		if let Some(banner) = &self.banner {
+			let window_width = Px(renderer.config.width as f32);
+			prerender.draw_commands.push(DrawCommand::Card {
+				position: Vex([Px(0.), Px(0.)]),
+				dimensions: Vex([window_width, BANNER_HEIGHT.s(scale)]),
+				color: [0xb0, 0x20, 0x20, 0xdd],
+				radius: Px(0.),
+			});
+			prerender.draw_commands.push(DrawCommand::Text {
+				text: banner.text.as_str().into(),
+				align: Some(Align::Center),
+				position: Vex([window_width / 2., (BANNER_HEIGHT / 2.).s(scale)]),
+				anchors: [0.5, 0.5],
+			});
+		}
+
+		if let Some(groups) = &self.shortcut_overlay {
+			let window_dimensions = Vex([renderer.config.width as f32, renderer.config.height as f32].map(Px));
+			prerender.draw_commands.push(DrawCommand::Card {
+				position: Vex([Px(0.), Px(0.)]),
+				dimensions: window_dimensions,
+				color: [0x00, 0x00, 0x00, 0xcc],
+				radius: Px(0.),
+			});
+
+			// Categories are stacked into columns as tall as the window allows, each followed by a blank line.
+			let max_line_count = (((window_dimensions[1] - SHORTCUT_MARGIN * 2.) / SHORTCUT_LINE_HEIGHT).floor() as usize).max(1);
+			let mut columns = Vec::<(String, String, usize)>::new();
+			for group in groups {
+				let line_count = group.bindings.len() + 1;
+				match columns.last_mut() {
+					Some((_, _, column_line_count)) if *column_line_count + 1 + line_count <= max_line_count => *column_line_count += 1 + line_count,
+					_ => columns.push((String::new(), String::new(), line_count)),
+				}
+				let Some((keys, descriptions, _)) = columns.last_mut() else { continue };
+				if !keys.is_empty() {
+					keys.push_str("\n\n");
+					descriptions.push_str("\n\n");
+				}
+				keys.push_str(group.category);
+				for (shortcut, description) in &group.bindings {
+					keys.push('\n');
+					keys.push_str(shortcut);
+					descriptions.push('\n');
+					descriptions.push_str(description);
+				}
+			}
+
+			let line_count = columns.iter().map(|(_, _, line_count)| *line_count).max().unwrap_or(0);
+			let origin = (window_dimensions - Vex([SHORTCUT_COLUMN_WIDTH * columns.len() as f32, SHORTCUT_LINE_HEIGHT * line_count as f32])) / 2.;
+			for (i, (keys, descriptions, _)) in columns.into_iter().enumerate() {
+				let position = origin + Vex([SHORTCUT_COLUMN_WIDTH * i as f32, Px(0.)]);
+				prerender.draw_commands.push(DrawCommand::Text {
+					text: keys.into(),
+					align: Some(Align::Left),
+					position,
+					anchors: [0., 0.],
+				});
+				prerender.draw_commands.push(DrawCommand::Text {
+					text: descriptions.into(),
+					align: Some(Align::Left),
+					position: position + Vex([SHORTCUT_KEYS_WIDTH, Px(0.)]),
+					anchors: [0., 0.],
+				});
+			}
+		}
+
+		if let Some(busy_text) = self.busy_text {
+			let window_dimensions = Vex([renderer.config.width as f32, renderer.config.height as f32].map(Px));
+			prerender.draw_commands.push(DrawCommand::Card {
+				position: Vex([Px(0.), Px(0.)]),
+				dimensions: window_dimensions,
+				color: [0x00, 0x00, 0x00, 0x99],
+				radius: Px(0.),
+			});
+			prerender.draw_commands.push(DrawCommand::Text {
+				text: busy_text.into(),
+				align: Some(Align::Center),
+				position: window_dimensions / 2.,
+				anchors: [0.5, 0.5],
+			});
+		}
+
+		prerender.checkerboard_cell_size = (self.is_checkerboard_on && current_canvas.is_some()).then(|| CHECKERBOARD_CELL_SIZE.s(scale));
+		prerender.selection_color = self.selection_color;
 		prerender.canvas = current_canvas;
 		prerender.current_stroke = self.mode_stack.current_stroke();
 	}
 }
 
+// Places a ruler through the center of the screen, level with it.
+pub fn centered_ruler(view: &View) -> Ruler {
+	let semilength = Vex([RULER_LENGTH / 2., Lx(0.)]).z(view.zoom).rotate(view.tilt);
+	Ruler {
+		endpoints: [view.position - semilength, view.position + semilength],
+	}
+}
+
+// The angle of a swatch in a ring of them, starting from the top and going clockwise.
+fn swatch_angle(index: usize, count: usize) -> f32 {
+	-std::f32::consts::FRAC_PI_2 + index as f32 * std::f32::consts::TAU / count as f32
+}
+
+// The index of the swatch whose wedge of the ring contains an angle, if there are any swatches.
+fn swatch_index_at(angle: f32, count: usize) -> Option<usize> {
+	if count == 0 {
+		return None;
+	}
+	let index = ((angle + std::f32::consts::FRAC_PI_2) * count as f32 / std::f32::consts::TAU).round() as isize;
+	Some(index.rem_euclid(count as isize) as usize)
+}
+
+// Centers the color selector according to its placement, keeping it clear of the window's edges if it is placed in a corner.
+pub fn color_selector_center(placement: ColorPickerPlacement, cursor_center: Vex<2, Px>, window_dimensions: Vex<2, Px>, scale: Scale) -> Vex<2, Px> {
+	match placement {
+		ColorPickerPlacement::Cursor => cursor_center,
+		ColorPickerPlacement::Center => window_dimensions / 2.,
+		ColorPickerPlacement::Corner { is_right, is_bottom } => {
+			let x = if is_right {
+				window_dimensions[0] - (HOLE_RADIUS + RING_WIDTH + ALPHA_SLIDER_GAP + ALPHA_SLIDER_WIDTH + COLOR_SELECTOR_CORNER_MARGIN).s(scale)
+			} else {
+				(HOLE_RADIUS + RING_WIDTH + HARMONY_SWATCH_GAP + HARMONY_SWATCH_DIAMETER + COLOR_SELECTOR_CORNER_MARGIN).s(scale)
+			};
+			let y = if is_bottom {
+				window_dimensions[1] - (HOLE_RADIUS + RING_WIDTH + COLOR_READOUT_GAP + COLOR_READOUT_HEIGHT + COLOR_SELECTOR_CORNER_MARGIN).s(scale)
+			} else {
+				(HOLE_RADIUS + RING_WIDTH + COLOR_SELECTOR_CORNER_MARGIN).s(scale)
+			};
+			Vex([x, y])
+		},
+	}
+}
+
+// The hue offset of each harmony swatch, and the offset of its center from the center of the color selector.
+fn harmony_swatch_offsets(scale: Scale) -> impl Iterator<Item = (f32, Vex<2, Px>)> {
+	let x = -(HOLE_RADIUS + RING_WIDTH + HARMONY_SWATCH_GAP + HARMONY_SWATCH_DIAMETER / 2.).s(scale);
+	let spacing = (HARMONY_SWATCH_DIAMETER + HARMONY_SWATCH_GAP).s(scale);
+	let middle = (HARMONY_HUE_OFFSETS.len() - 1) as f32 / 2.;
+	HARMONY_HUE_OFFSETS.into_iter().enumerate().map(move |(index, hue_offset)| (hue_offset, Vex([x, spacing * (index as f32 - middle)])))
+}
+
+// Symmetric copies are committed together with the stroke, as a single undoable operation.
+// Given the spacing of baselines and a snap strength, the bottom of the stroke is first pulled toward the nearest baseline.
+fn commit_stroke(canvas: &mut Canvas, stroke: IncompleteStroke, baselines: Option<(Vx, f32)>) {
+	let mut stroke = stroke.finalize();
+	if let Some((spacing, snap)) = baselines.filter(|(_, snap)| *snap > 0.) {
+		let bottom = stroke.world_aabb().1[1];
+		stroke.position = stroke.position + Vex([Vx(0.), (spacing * (bottom / spacing).round() - bottom) * snap]);
+	}
+	let copies = canvas.symmetry.map_or(Vec::new(), |symmetry| symmetry.copies(&stroke));
+	canvas.perform_operation(Operation::CommitStrokes {
+		strokes: std::iter::once(stroke).chain(copies).map(Into::into).collect(),
+	});
+}
+
+// Finds what a replay of strokes shows some milliseconds after it starts: how many strokes are drawn in full, and the stroke being drawn, if it has any points yet.
+// Strokes are redrawn in the timing that they were drawn, with a short gap between them; once every stroke is drawn, the replay is over.
+pub fn replay_progress(strokes: &[Tracked<Stroke>], elapsed: u32) -> Option<(usize, Option<Stroke>)> {
+	let mut stroke_start = 0u32;
+	for (index, stroke) in strokes.iter().enumerate() {
+		// Hidden strokes take no time, and are counted as drawn without being shown.
+		if !stroke.is_visible {
+			continue;
+		}
+		let duration = stroke.points.last().map_or(0, |point| point.timestamp);
+		if elapsed < stroke_start.saturating_add(duration) {
+			let points = stroke.points.iter().filter(|point| stroke_start.saturating_add(point.timestamp) <= elapsed).cloned().collect::<Vec<_>>();
+			let partial_stroke = (!points.is_empty()).then(|| Stroke::new(stroke.color, stroke.stroke_radius, points, stroke.is_smooth, stroke.position, stroke.orientation, stroke.dilation));
+			return Some((index, partial_stroke));
+		}
+		stroke_start = stroke_start.saturating_add(duration).saturating_add(REPLAY_STROKE_GAP);
+		if elapsed < stroke_start {
+			return Some((index + 1, None));
+		}
+	}
+	None
+}
+
+// Draws a dark outline beneath a light crosshair so that it is visible on any background.
+fn draw_crosshair(draw_commands: &mut Vec<DrawCommand>, center: Vex<2, Px>, length: Lx, scale: Scale) {
+	let length = length.s(scale);
+	for (width, color) in [((CROSSHAIR_WIDTH + 2. * OUTLINE_WIDTH).s(scale), [0x00, 0x00, 0x00, 0xff]), (CROSSHAIR_WIDTH.s(scale), [0xff; 4])] {
+		let length = length + width - CROSSHAIR_WIDTH.s(scale);
+		draw_commands.push(DrawCommand::Card {
+			position: center - Vex([length, width]) / 2.,
+			dimensions: Vex([length, width]),
+			color,
+			radius: Px(0.),
+		});
+		draw_commands.push(DrawCommand::Card {
+			position: center - Vex([width, length]) / 2.,
+			dimensions: Vex([width, length]),
+			color,
+			radius: Px(0.),
+		});
+	}
+}
+
+// Places evenly spaced dots along a ray of the given length, skipping those outside the window.
+fn dotted_line(origin: Vex<2, Px>, direction: Vex<2, f32>, length: f32, window_dimensions: [u32; 2], dot_spacing: Px) -> impl Iterator<Item = Vex<2, Px>> {
+	let mut extent = (0f32, length);
+	for (axis, window_length) in window_dimensions.into_iter().enumerate() {
+		let (origin, direction) = (origin[axis].0, direction[axis]);
+		if direction.abs() > f32::EPSILON {
+			let (a, b) = ((0. - origin) / direction, (window_length as f32 - origin) / direction);
+			extent = (extent.0.max(a.min(b)), extent.1.min(a.max(b)));
+		} else if origin < 0. || origin > window_length as f32 {
+			extent = (0., -1.);
+		}
+	}
+	let dot_indices = if extent.0 <= extent.1 {
+		(extent.0 / dot_spacing.0).ceil() as usize..(extent.1 / dot_spacing.0).floor() as usize + 1
+	} else {
+		0..0
+	};
+	dot_indices.map(move |dot_index| origin + direction.map(|x| dot_spacing * (dot_index as f32 * x)))
+}
+
+// A finite region of the canvas to compose within, such as a sheet of paper.
+#[derive(Clone, Copy)]
+pub struct Page {
+	// The top-left corner of the page.
+	pub position: Vex<2, Vx>,
+	pub dimensions: Vex<2, Vx>,
+}
+
+// A straight guide that strokes are drawn along, like a physical ruler.
+#[derive(Clone, Copy)]
+pub struct Ruler {
+	pub endpoints: [Vex<2, Vx>; 2],
+}
+
+#[derive(Clone, Copy)]
+pub enum RulerPart {
+	Endpoint(usize),
+	Body,
+}
+
+impl Ruler {
+	// Projects a point onto the line through the endpoints.
+	pub fn project(&self, point: Vex<2, Vx>) -> Vex<2, Vx> {
+		let [a, b] = self.endpoints;
+		let ab = b - a;
+		if ab.norm().0 > 0. {
+			a + ab * ((point - a).dot(ab) / ab.dot(ab))
+		} else {
+			point
+		}
+	}
+
+	pub fn translated(self, vector: Vex<2, Vx>) -> Self {
+		Self {
+			endpoints: self.endpoints.map(|endpoint| endpoint + vector),
+		}
+	}
+
+	// Finds the part of the ruler within a radius of a point, preferring its endpoints.
+	pub fn pick(&self, point: Vex<2, Vx>, radius: Vx) -> Option<RulerPart> {
+		if let Some(index) = self.endpoints.iter().position(|endpoint| (point - *endpoint).norm() <= radius) {
+			return Some(RulerPart::Endpoint(index));
+		}
+		let [a, b] = self.endpoints;
+		let ab = b - a;
+		let t = if ab.norm().0 > 0. { ((point - a).dot(ab) / ab.dot(ab)).clamp(0., 1.) } else { 0. };
+		((point - (a + ab * t)).norm() <= radius).then_some(RulerPart::Body)
+	}
+}
+
+// A part of the ruler being dragged with the move tool, from where it was grabbed.
+struct RulerDrag {
+	part: RulerPart,
+	origin: Vex<2, Vx>,
+	initial_ruler: Ruler,
+}
+
 pub struct Canvas {
 	pub file_path: Tracked<Option<PathBuf>>,
 	pub background_color: Srgb8,
-	pub stroke_color: Hsv,
+	pub stroke_color: Hsva,
 	pub stroke_radius: Vx,
 	pub view: Tracked<View>,
+	// Views to navigate back and forward to, and the view as of the latest navigation or commit.
+	previous_views: Vec<View>,
+	next_views: Vec<View>,
+	committed_view: View,
 	pub images: Vec<Tracked<Image>>,
 	pub strokes: Vec<Tracked<Stroke>>,
 	// Tracks the smallest indices of an invalidated image/stroke.
@@ -744,9 +2741,30 @@ pub struct Canvas {
 	pub base_dirty_stroke_index: usize,
 	retractions: Vec<Retraction>,
 	operations: Vec<Operation>,
-	pub textures: Vec<Texture>,
+	// The redo branches forking from the current line of history, which are kept up to a limit.
+	redo_branches: Vec<RedoBranch>,
+	// Textures are released once no image refers to them, leaving an empty slot so that other indices stay valid.
+	pub textures: Vec<Option<Texture>>,
+	// Whether discarded history may have held the last references to some textures.
+	may_have_orphaned_textures: bool,
+	// When the last operation was performed, unless it has since been undone, so that adjustments in quick succession can be merged.
+	last_operation_instant: Option<Instant>,
 	pub retraction_count_at_save: Option<usize>,
 	pub selection_transformation: Tracked<SelectionTransformation>,
+	pub symmetry: Option<Symmetry>,
+	pub page: Option<Page>,
+	pub ruler: Option<Ruler>,
+	// The index, among matching objects, of the object last found.
+	find_index: Option<usize>,
+	// The objects of a selection being stepped through one at a time, and the index of the one currently selected.
+	selection_cycle: Option<(Vec<ObjectRef>, usize)>,
+	view_animation: Option<ViewAnimation>,
+	pan_inertia: Option<PanInertia>,
+	replay: Option<Replay>,
+	// The kind and destination of the last export, which quick export repeats.
+	pub last_export: Option<(ExportKind, PathBuf)>,
+	// Whether the canvas has more objects than the configured limit, as of the last update.
+	pub is_over_object_limit: bool,
 }
 
 impl Canvas {
@@ -754,38 +2772,70 @@ impl Canvas {
 		Self {
 			file_path: None.into(),
 			background_color: config.default_canvas_color,
-			stroke_color: config.default_stroke_color.to_hsv(),
+			stroke_color: config.default_stroke_color.to_hsva(1.),
 			stroke_radius: config.default_stroke_radius,
-			view: View::new().into(),
+			view: config.new_canvas_view.into(),
+			previous_views: Vec::new(),
+			next_views: Vec::new(),
+			committed_view: config.new_canvas_view,
 			images: Vec::new(),
 			strokes: Vec::new(),
 			base_dirty_image_index: 0,
 			base_dirty_stroke_index: 0,
 			retractions: Vec::new(),
 			operations: Vec::new(),
+			redo_branches: Vec::new(),
 			textures: Vec::new(),
+			may_have_orphaned_textures: false,
+			last_operation_instant: None,
 			retraction_count_at_save: None,
 			selection_transformation: Default::default(),
+			symmetry: None,
+			page: None,
+			ruler: None,
+			find_index: None,
+			selection_cycle: None,
+			view_animation: None,
+			pan_inertia: None,
+			replay: None,
+			last_export: None,
+			is_over_object_limit: false,
 		}
 	}
 
 	#[allow(clippy::too_many_arguments)]
-	pub fn from_file(file_path: PathBuf, background_color: Srgb8, stroke_color: Srgb8, stroke_radius: Vx, view: View, images: Vec<Tracked<Image>>, strokes: Vec<Tracked<Stroke>>, textures: Vec<Texture>) -> Self {
+	pub fn from_file(file_path: Option<PathBuf>, background_color: Srgb8, stroke_color: Srgb8, stroke_radius: Vx, view: View, page: Option<Page>, images: Vec<Tracked<Image>>, strokes: Vec<Tracked<Stroke>>, textures: Vec<Texture>) -> Self {
 		Self {
-			file_path: Some(file_path).into(),
+			file_path: file_path.into(),
 			background_color,
-			stroke_color: stroke_color.to_hsv(),
+			stroke_color: stroke_color.to_hsva(1.),
 			stroke_radius,
 			view: view.into(),
+			previous_views: Vec::new(),
+			next_views: Vec::new(),
+			committed_view: view,
 			images,
 			strokes,
 			base_dirty_image_index: 0,
 			base_dirty_stroke_index: 0,
 			retractions: Vec::new(),
 			operations: Vec::new(),
-			textures,
+			redo_branches: Vec::new(),
+			textures: textures.into_iter().map(Some).collect(),
+			may_have_orphaned_textures: false,
+			last_operation_instant: None,
 			retraction_count_at_save: Some(0),
 			selection_transformation: Default::default(),
+			symmetry: None,
+			page,
+			ruler: None,
+			find_index: None,
+			selection_cycle: None,
+			view_animation: None,
+			pan_inertia: None,
+			replay: None,
+			last_export: None,
+			is_over_object_limit: false,
 		}
 	}
 
@@ -795,6 +2845,182 @@ impl Canvas {
 		self.base_dirty_stroke_index = 0;
 	}
 
+	// Records the current view in the view history if it has moved far enough since the last commit.
+	pub fn commit_view(&mut self) {
+		if self.view.is_distant_from(&self.committed_view) {
+			if self.previous_views.len() == VIEW_HISTORY_CAPACITY {
+				self.previous_views.remove(0);
+			}
+			self.previous_views.push(self.committed_view);
+			self.next_views.clear();
+			self.committed_view = *self.view;
+		}
+	}
+
+	pub fn navigate_back(&mut self) {
+		self.pan_inertia = None;
+		self.commit_view();
+		if let Some(view) = self.previous_views.pop() {
+			self.next_views.push(*self.view);
+			*self.view = view;
+			self.committed_view = view;
+		}
+	}
+
+	pub fn navigate_forward(&mut self) {
+		self.pan_inertia = None;
+		self.commit_view();
+		if let Some(view) = self.next_views.pop() {
+			self.previous_views.push(*self.view);
+			*self.view = view;
+			self.committed_view = view;
+		}
+	}
+
+	// Centers the view on the next object matching the target, wrapping around after the last.
+	pub fn find_next(&mut self, target: FindTarget) {
+		let positions = match target {
+			FindTarget::Images => self.images.iter().filter(|image| image.is_visible).map(|image| image.position).collect::<Vec<_>>(),
+			FindTarget::StrokesOfColor(color) => self.strokes.iter().filter(|stroke| stroke.is_visible && stroke.color.0 == color.0).map(|stroke| stroke.position).collect(),
+		};
+		if positions.is_empty() {
+			return;
+		}
+
+		let index = self.find_index.map_or(0, |index| (index + 1) % positions.len());
+		self.find_index = Some(index);
+		self.pan_inertia = None;
+		self.commit_view();
+		self.view_animation = Some(ViewAnimation {
+			origin: self.view.position,
+			target: positions[index],
+			start: Instant::now(),
+		});
+	}
+
+	// Narrows the selection to one of its objects and centers the view on it; repeating this steps through the rest of the original selection.
+	pub fn cycle_selection(&mut self) {
+		let selected_objects = (self.images.iter().enumerate().filter(|(_, image)| image.is_selected).map(|(index, _)| ObjectRef::Image(index)))
+			.chain(self.strokes.iter().enumerate().filter(|(_, stroke)| stroke.is_selected).map(|(index, _)| ObjectRef::Stroke(index)))
+			.collect::<Vec<_>>();
+
+		// We only continue the cycle if its current object is still the only one selected, and none of its objects have since disappeared.
+		let (image_count, stroke_count) = (self.images.len(), self.strokes.len());
+		let cycle = match self.selection_cycle.take() {
+			Some((objects, index))
+				if selected_objects == [objects[index]]
+					&& objects.iter().all(|object| match *object {
+						ObjectRef::Image(index) => index < image_count,
+						ObjectRef::Stroke(index) => index < stroke_count,
+					}) =>
+			{
+				let index = (index + 1) % objects.len();
+				(objects, index)
+			},
+			_ if selected_objects.is_empty() => return,
+			_ => (selected_objects, 0),
+		};
+
+		self.select_all(false);
+		let target = match cycle.0[cycle.1] {
+			ObjectRef::Image(index) => {
+				self.images[index].is_selected = true;
+				self.images[index].position
+			},
+			ObjectRef::Stroke(index) => {
+				self.strokes[index].is_selected = true;
+				self.strokes[index].position
+			},
+		};
+		self.selection_cycle = Some(cycle);
+
+		self.pan_inertia = None;
+		self.commit_view();
+		self.view_animation = Some(ViewAnimation {
+			origin: self.view.position,
+			target,
+			start: Instant::now(),
+		});
+	}
+
+	// Steps the view animation, ending it once the target is reached.
+	pub fn advance_view_animation(&mut self) {
+		let Some(animation) = self.view_animation else { return };
+		let t = ((Instant::now() - animation.start).as_secs_f32() / VIEW_ANIMATION_DURATION.as_secs_f32()).min(1.);
+		let eased_t = t * t * (3. - 2. * t);
+		self.view.position = animation.origin + (animation.target - animation.origin) * eased_t;
+		if t >= 1. {
+			self.view_animation = None;
+			self.commit_view();
+		}
+	}
+
+	// Steps a glide after a pan, slowing it exponentially and ending it once it is too slow to notice.
+	pub fn advance_pan_inertia(&mut self) {
+		let Some(inertia) = self.pan_inertia.as_mut() else { return };
+		let now = Instant::now();
+		let elapsed = (now - inertia.last_step).as_secs_f32();
+		let decay = (-elapsed / PAN_INERTIA_TIME_CONSTANT.as_secs_f32()).exp();
+		// Move by the integral of the decaying velocity over the step, so that the glide covers the same distance at any frame rate.
+		self.view.position = self.view.position + inertia.velocity * (PAN_INERTIA_TIME_CONSTANT.as_secs_f32() * (1. - decay));
+		inertia.velocity = inertia.velocity * decay;
+		inertia.last_step = now;
+		if inertia.velocity.norm().z(self.view.zoom) < PAN_INERTIA_MIN_SPEED {
+			self.pan_inertia = None;
+			self.commit_view();
+		}
+	}
+
+	// Whether the canvas changes over time without any input.
+	pub fn is_animating(&self) -> bool {
+		self.view_animation.is_some() || self.pan_inertia.is_some() || self.replay.is_some()
+	}
+
+	pub fn start_replay(&mut self) {
+		if self.replay.is_some() || self.strokes.is_empty() {
+			return;
+		}
+
+		self.replay = Some(Replay {
+			strokes: std::mem::take(&mut self.strokes),
+			start: Instant::now(),
+			is_last_stroke_partial: false,
+		});
+		self.base_dirty_stroke_index = 0;
+	}
+
+	// Shows every stroke that has been drawn by now in the replay, along with the stroke being drawn.
+	pub fn advance_replay(&mut self) {
+		let Some(replay) = self.replay.as_mut() else { return };
+		let elapsed: u32 = (Instant::now() - replay.start).as_millis().try_into().unwrap_or(u32::MAX);
+
+		if replay.is_last_stroke_partial {
+			self.strokes.pop();
+			replay.is_last_stroke_partial = false;
+		}
+		self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(self.strokes.len());
+
+		let Some((complete_stroke_count, partial_stroke)) = replay_progress(&replay.strokes, elapsed) else {
+			self.stop_replay();
+			return;
+		};
+		for stroke in replay.strokes.iter().take(complete_stroke_count).skip(self.strokes.len()) {
+			self.strokes.push(Stroke::clone(stroke).into());
+		}
+		if let Some(stroke) = partial_stroke {
+			self.strokes.push(stroke.into());
+			replay.is_last_stroke_partial = true;
+		}
+	}
+
+	// Ends the replay, restoring every stroke.
+	pub fn stop_replay(&mut self) {
+		if let Some(replay) = self.replay.take() {
+			self.strokes = replay.strokes;
+			self.base_dirty_stroke_index = 0;
+		}
+	}
+
 	pub fn images(&self) -> &[Tracked<Image>] {
 		self.images.as_ref()
 	}
@@ -832,34 +3058,126 @@ impl Canvas {
 						self.base_dirty_image_index = self.base_dirty_image_index.min(*index);
 					}
 
-					let mut antitone_index_stroke_pairs = Vec::with_capacity(monotone_stroke_indices.len());
+					let mut antitone_index_stroke_pairs = Vec::with_capacity(monotone_stroke_indices.len());
+
+					for index in monotone_stroke_indices.iter().rev().copied() {
+						debug_assert!(index < self.strokes.len());
+						let stroke = self.strokes.remove(index);
+						antitone_index_stroke_pairs.push((index, stroke.take()));
+					}
+
+					if let Some(index) = monotone_stroke_indices.first() {
+						self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(*index);
+					}
+
+					Retraction::DeleteObjects {
+						antitone_index_image_pairs,
+						antitone_index_stroke_pairs,
+					}
+				},
+				RecolorStrokes { indices, new_color } => {
+					let mut index_color_pairs = Vec::with_capacity(indices.len());
+
+					for index in indices {
+						if let Some(stroke) = self.strokes.get_mut(index) {
+							index_color_pairs.push((index, stroke.color));
+							stroke.color = new_color;
+						}
+					}
+
+					Retraction::RecolorStrokes { index_color_pairs, new_color }
+				},
+				RestyleStrokes { indices, new_style } => {
+					let mut index_style_pairs = Vec::with_capacity(indices.len());
+
+					for index in indices {
+						if let Some(stroke) = self.strokes.get_mut(index) {
+							let (old_style, is_reshaped) = stroke.restyle(new_style);
+							index_style_pairs.push((index, old_style));
+							// Strokes with new geometry must be uploaded again.
+							if is_reshaped {
+								self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(index);
+							}
+						}
+					}
+
+					Retraction::RestyleStrokes { index_style_pairs, new_style }
+				},
+				FrameImages { indices, new_border } => {
+					let mut index_border_pairs = Vec::with_capacity(indices.len());
+
+					for index in indices {
+						if let Some(image) = self.images.get_mut(index) {
+							index_border_pairs.push((index, (image.border_width, image.border_color)));
+							(image.border_width, image.border_color) = new_border;
+						}
+					}
+
+					Retraction::FrameImages { index_border_pairs, new_border }
+				},
+				PixelateImages { indices, is_pixelated } => {
+					let mut index_pixelation_pairs = Vec::with_capacity(indices.len());
+
+					for index in indices {
+						if let Some(image) = self.images.get_mut(index) {
+							index_pixelation_pairs.push((index, image.is_pixelated));
+							image.is_pixelated = is_pixelated;
+						}
+					}
+
+					Retraction::PixelateImages { index_pixelation_pairs, is_pixelated }
+				},
+				AnnotateObjects { image_indices, stroke_indices, new_note } => {
+					let mut index_note_image_pairs = Vec::with_capacity(image_indices.len());
+
+					for index in image_indices {
+						if let Some(image) = self.images.get_mut(index) {
+							index_note_image_pairs.push((index, std::mem::replace(&mut image.note, new_note.clone())));
+						}
+					}
+
+					let mut index_note_stroke_pairs = Vec::with_capacity(stroke_indices.len());
 
-					for index in monotone_stroke_indices.iter().rev().copied() {
-						debug_assert!(index < self.strokes.len());
-						let stroke = self.strokes.remove(index);
-						antitone_index_stroke_pairs.push((index, stroke.take()));
+					for index in stroke_indices {
+						if let Some(stroke) = self.strokes.get_mut(index) {
+							index_note_stroke_pairs.push((index, std::mem::replace(&mut stroke.note, new_note.clone())));
+						}
 					}
 
-					if let Some(index) = monotone_stroke_indices.first() {
-						self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(*index);
+					Retraction::AnnotateObjects {
+						index_note_image_pairs,
+						index_note_stroke_pairs,
+						new_note,
 					}
+				},
+				ShowObjects { image_indices, stroke_indices, is_visible } => {
+					let mut index_visibility_image_pairs = Vec::with_capacity(image_indices.len());
 
-					Retraction::DeleteObjects {
-						antitone_index_image_pairs,
-						antitone_index_stroke_pairs,
+					for index in image_indices {
+						if let Some(image) = self.images.get_mut(index) {
+							index_visibility_image_pairs.push((index, image.is_visible));
+							image.is_visible = is_visible;
+							image.is_selected &= is_visible;
+						}
 					}
-				},
-				RecolorStrokes { indices, new_color } => {
-					let mut index_color_pairs = Vec::with_capacity(indices.len());
 
-					for index in indices {
+					let mut index_visibility_stroke_pairs = Vec::with_capacity(stroke_indices.len());
+
+					for index in stroke_indices {
 						if let Some(stroke) = self.strokes.get_mut(index) {
-							index_color_pairs.push((index, stroke.color));
-							stroke.color = new_color;
+							index_visibility_stroke_pairs.push((index, stroke.is_visible));
+							stroke.is_visible = is_visible;
+							stroke.is_selected &= is_visible;
+							// The geometry of strokes is packed together, so it is rebuilt from the first stroke to appear or disappear.
+							self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(index);
 						}
 					}
 
-					Retraction::RecolorStrokes { index_color_pairs, new_color }
+					Retraction::ShowObjects {
+						index_visibility_image_pairs,
+						index_visibility_stroke_pairs,
+						is_visible,
+					}
 				},
 				TranslateObjects { image_indices, stroke_indices, vector } => {
 					for index in image_indices.iter().copied() {
@@ -910,33 +3228,78 @@ impl Canvas {
 
 					Retraction::ResizeObjects { image_indices, stroke_indices, center, dilation }
 				},
+				ResizeObjectsNonUniform { image_indices, stroke_indices, center, scale } => {
+					let mut index_placement_image_pairs = Vec::with_capacity(image_indices.len());
+
+					for index in image_indices {
+						if let Some(image) = self.images.get_mut(index) {
+							index_placement_image_pairs.push((index, (image.position, image.dimensions)));
+							image.stretch_about(center, scale);
+						}
+					}
+
+					let mut index_placement_stroke_pairs = Vec::with_capacity(stroke_indices.len());
+
+					for index in stroke_indices {
+						if let Some(stroke) = self.strokes.get_mut(index) {
+							index_placement_stroke_pairs.push((index, (stroke.position, stroke.points.clone())));
+							stroke.stretch_about(center, scale);
+							// The stroke's geometry has changed, so it must be uploaded again.
+							self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(index);
+						}
+					}
+
+					Retraction::ResizeObjectsNonUniform {
+						index_placement_image_pairs,
+						index_placement_stroke_pairs,
+						center,
+						scale,
+					}
+				},
+				SetBackground { new_color } => {
+					let old_color = std::mem::replace(&mut self.background_color, new_color);
+
+					Retraction::SetBackground { old_color, new_color }
+				},
+				SplitStrokes { monotone_stroke_indices, mut pieces } => {
+					let mut antitone_index_stroke_pairs = Vec::with_capacity(monotone_stroke_indices.len());
+
+					for index in monotone_stroke_indices.iter().rev().copied() {
+						debug_assert!(index < self.strokes.len());
+						let stroke = self.strokes.remove(index);
+						antitone_index_stroke_pairs.push((index, stroke.take()));
+					}
+
+					if let Some(index) = monotone_stroke_indices.first() {
+						self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(*index);
+					}
+
+					let piece_count = pieces.len();
+					self.strokes.append(&mut pieces);
+
+					Retraction::SplitStrokes { antitone_index_stroke_pairs, piece_count }
+				},
 			});
 		}
 	}
 
 	pub fn undo(&mut self) {
+		self.last_operation_instant = None;
 		if let Some(operation) = self.retractions.pop() {
 			use Retraction::*;
 			self.operations.push(match operation {
 				CommitStrokes(length) => {
-					let mut strokes = Vec::with_capacity(length);
-
+					// Keep the strokes in order, so that redoing restores their order and selection exactly.
 					debug_assert!(length <= self.strokes.len());
-					for _ in 0..length {
-						strokes.push(self.strokes.pop().unwrap());
-					}
+					let strokes = self.strokes.split_off(self.strokes.len() - length);
 
 					self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(self.strokes.len());
 
 					Operation::CommitStrokes { strokes }
 				},
 				CommitImages(length) => {
-					let mut images = Vec::with_capacity(length);
-
 					debug_assert!(length <= self.images.len());
-					for _ in 0..length {
-						images.push(self.images.pop().unwrap());
-					}
+					let images = self.images.split_off(self.images.len() - length);
 
 					Operation::CommitImages { images }
 				},
@@ -983,6 +3346,102 @@ impl Canvas {
 
 					Operation::RecolorStrokes { indices, new_color }
 				},
+				RestyleStrokes { index_style_pairs, new_style } => {
+					let mut indices = Vec::with_capacity(index_style_pairs.len());
+
+					for (index, old_style) in index_style_pairs.into_iter() {
+						if let Some(stroke) = self.strokes.get_mut(index) {
+							if stroke.restyle(old_style).1 {
+								self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(index);
+							}
+						}
+
+						indices.push(index);
+					}
+
+					Operation::RestyleStrokes { indices, new_style }
+				},
+				FrameImages { index_border_pairs, new_border } => {
+					let mut indices = Vec::with_capacity(index_border_pairs.len());
+
+					for (index, old_border) in index_border_pairs.into_iter() {
+						if let Some(image) = self.images.get_mut(index) {
+							(image.border_width, image.border_color) = old_border;
+						}
+
+						indices.push(index);
+					}
+
+					Operation::FrameImages { indices, new_border }
+				},
+				PixelateImages { index_pixelation_pairs, is_pixelated } => {
+					let mut indices = Vec::with_capacity(index_pixelation_pairs.len());
+
+					for (index, was_pixelated) in index_pixelation_pairs.into_iter() {
+						if let Some(image) = self.images.get_mut(index) {
+							image.is_pixelated = was_pixelated;
+						}
+
+						indices.push(index);
+					}
+
+					Operation::PixelateImages { indices, is_pixelated }
+				},
+				AnnotateObjects {
+					index_note_image_pairs,
+					index_note_stroke_pairs,
+					new_note,
+				} => {
+					let mut image_indices = Vec::with_capacity(index_note_image_pairs.len());
+
+					for (index, old_note) in index_note_image_pairs.into_iter() {
+						if let Some(image) = self.images.get_mut(index) {
+							image.note = old_note;
+						}
+
+						image_indices.push(index);
+					}
+
+					let mut stroke_indices = Vec::with_capacity(index_note_stroke_pairs.len());
+
+					for (index, old_note) in index_note_stroke_pairs.into_iter() {
+						if let Some(stroke) = self.strokes.get_mut(index) {
+							stroke.note = old_note;
+						}
+
+						stroke_indices.push(index);
+					}
+
+					Operation::AnnotateObjects { image_indices, stroke_indices, new_note }
+				},
+				ShowObjects {
+					index_visibility_image_pairs,
+					index_visibility_stroke_pairs,
+					is_visible,
+				} => {
+					let mut image_indices = Vec::with_capacity(index_visibility_image_pairs.len());
+
+					for (index, was_visible) in index_visibility_image_pairs.into_iter() {
+						if let Some(image) = self.images.get_mut(index) {
+							image.is_visible = was_visible;
+						}
+
+						image_indices.push(index);
+					}
+
+					let mut stroke_indices = Vec::with_capacity(index_visibility_stroke_pairs.len());
+
+					for (index, was_visible) in index_visibility_stroke_pairs.into_iter() {
+						if let Some(stroke) = self.strokes.get_mut(index) {
+							stroke.is_visible = was_visible;
+							self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(index);
+						}
+
+						stroke_indices.push(index);
+					}
+
+					Operation::ShowObjects { image_indices, stroke_indices, is_visible }
+				},
 				TranslateObjects { image_indices, stroke_indices, vector } => {
 					for index in image_indices.iter().copied() {
 						if let Some(image) = self.images.get_mut(index) {
@@ -1032,6 +3491,60 @@ impl Canvas {
 
 					Operation::ResizeObjects { image_indices, stroke_indices, center, dilation }
 				},
+				ResizeObjectsNonUniform {
+					index_placement_image_pairs,
+					index_placement_stroke_pairs,
+					center,
+					scale,
+				} => {
+					let mut image_indices = Vec::with_capacity(index_placement_image_pairs.len());
+
+					for (index, (position, dimensions)) in index_placement_image_pairs.into_iter() {
+						if let Some(image) = self.images.get_mut(index) {
+							(image.position, image.dimensions) = (position, dimensions);
+						}
+
+						image_indices.push(index);
+					}
+
+					let mut stroke_indices = Vec::with_capacity(index_placement_stroke_pairs.len());
+
+					for (index, (position, points)) in index_placement_stroke_pairs.into_iter() {
+						if let Some(stroke) = self.strokes.get_mut(index) {
+							*stroke.as_mut() = Stroke {
+								is_selected: stroke.is_selected,
+								note: stroke.note.take(),
+								..Stroke::new(stroke.color, stroke.stroke_radius, points, stroke.is_smooth, position, stroke.orientation, stroke.dilation)
+							};
+							self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(index);
+						}
+
+						stroke_indices.push(index);
+					}
+
+					Operation::ResizeObjectsNonUniform { image_indices, stroke_indices, center, scale }
+				},
+				SetBackground { old_color, new_color } => {
+					self.background_color = old_color;
+
+					Operation::SetBackground { new_color }
+				},
+				SplitStrokes { antitone_index_stroke_pairs, piece_count } => {
+					debug_assert!(piece_count <= self.strokes.len());
+					let pieces = self.strokes.split_off(self.strokes.len() - piece_count);
+
+					let mut monotone_stroke_indices = Vec::with_capacity(antitone_index_stroke_pairs.len());
+
+					for (index, stroke) in antitone_index_stroke_pairs.into_iter().rev() {
+						debug_assert!(index <= self.strokes.len());
+						self.strokes.insert(index, stroke.into());
+						monotone_stroke_indices.push(index);
+					}
+
+					self.base_dirty_stroke_index = self.base_dirty_stroke_index.min(monotone_stroke_indices.first().copied().unwrap_or(self.strokes.len()));
+
+					Operation::SplitStrokes { monotone_stroke_indices, pieces }
+				},
 			});
 		}
 	}
@@ -1042,9 +3555,154 @@ impl Canvas {
 				self.retraction_count_at_save = None;
 			}
 		}
-		self.operations.clear();
+		// Operations that could only be redone are set aside as a branch, along with the branches forking from them, until they are trimmed.
+		if !self.operations.is_empty() {
+			let depth = self.retractions.len();
+			let branches = self.redo_branches.extract_if(|branch| branch.depth > depth).collect();
+			self.redo_branches.push(RedoBranch {
+				depth,
+				operations: std::mem::take(&mut self.operations),
+				branches,
+			});
+		}
 		self.operations.push(operation);
 		self.redo();
+		self.last_operation_instant = Some(Instant::now());
+	}
+
+	// Swaps what would be redone for another redo branch forking at this point in the history, returning how many branches fork here, if any.
+	pub fn switch_redo_branch(&mut self) -> Option<usize> {
+		let depth = self.retractions.len();
+		let index = self.redo_branches.iter().position(|branch| branch.depth == depth)?;
+		let branch = self.redo_branches.remove(index);
+		let branches = self.redo_branches.extract_if(|branch| branch.depth > depth).collect::<Vec<_>>();
+		let operations = std::mem::replace(&mut self.operations, branch.operations);
+		self.redo_branches.extend(branch.branches);
+		// The line being switched away from goes to the back, so that switching repeatedly cycles through every branch.
+		if !operations.is_empty() {
+			self.redo_branches.push(RedoBranch { depth, operations, branches });
+		}
+		self.last_operation_instant = None;
+		Some(self.redo_branch_count())
+	}
+
+	// Counts the redo branches forking at this point in the history, other than what would be redone.
+	pub fn redo_branch_count(&self) -> usize {
+		self.redo_branches.iter().filter(|branch| branch.depth == self.retractions.len()).count()
+	}
+
+	// Discards the oldest redo branches until there are no more than the limit, counting those nested within them.
+	pub fn trim_redo_branches(&mut self, limit: usize) {
+		while self.redo_branches.iter().map(RedoBranch::count).sum::<usize>() > limit {
+			let branch = self.redo_branches.remove(0);
+			// Images that could only have been brought back by redoing are gone for good, along with any references to their textures.
+			self.may_have_orphaned_textures |= !branch.committed_images().is_empty();
+		}
+	}
+
+	// Performs an operation, merging it into the last one if that was the same adjustment of the same objects within the window.
+	// Merged operations are undone as one, which keeps fine adjustments from flooding the history.
+	pub fn perform_coalescing_operation(&mut self, operation: Operation, window: Duration) {
+		let is_recent = self.last_operation_instant.is_some_and(|instant| instant.elapsed() < window);
+		// The saved state must stay reachable by undoing, so the retraction it ends at is never merged into.
+		let is_saved_state = self.retraction_count_at_save == Some(self.retractions.len());
+		self.perform_operation(operation);
+		if is_recent && !is_saved_state {
+			self.coalesce_last_retractions();
+		}
+	}
+
+	// Merges the last retraction into the one before it, if they adjust the same objects in the same way.
+	fn coalesce_last_retractions(&mut self) {
+		let Some(newer) = self.retractions.pop() else { return };
+		let Some(older) = self.retractions.last_mut() else {
+			self.retractions.push(newer);
+			return;
+		};
+
+		let is_merged = match (older, &newer) {
+			(
+				Retraction::RecolorStrokes { index_color_pairs, new_color },
+				Retraction::RecolorStrokes {
+					index_color_pairs: newer_pairs,
+					new_color: newer_color,
+				},
+			) if index_color_pairs.iter().map(|(index, _)| index).eq(newer_pairs.iter().map(|(index, _)| index)) => {
+				*new_color = *newer_color;
+				true
+			},
+			(
+				Retraction::RestyleStrokes { index_style_pairs, new_style },
+				Retraction::RestyleStrokes {
+					index_style_pairs: newer_pairs,
+					new_style: newer_style,
+				},
+			) if index_style_pairs.iter().map(|(index, _)| index).eq(newer_pairs.iter().map(|(index, _)| index)) => {
+				*new_style = *newer_style;
+				true
+			},
+			(
+				Retraction::TranslateObjects { image_indices, stroke_indices, vector },
+				Retraction::TranslateObjects {
+					image_indices: newer_image_indices,
+					stroke_indices: newer_stroke_indices,
+					vector: newer_vector,
+				},
+			) if image_indices == newer_image_indices && stroke_indices == newer_stroke_indices => {
+				*vector = *vector + *newer_vector;
+				true
+			},
+			(Retraction::SetBackground { new_color, .. }, Retraction::SetBackground { new_color: newer_color, .. }) => {
+				*new_color = *newer_color;
+				true
+			},
+			_ => false,
+		};
+
+		if !is_merged {
+			self.retractions.push(newer);
+		}
+	}
+
+	// Counts the references to each texture from images on the canvas and in its history, and releases those that have none.
+	// Textures referenced from elsewhere, such as the clipboard, are retained.
+	pub fn release_orphaned_textures(&mut self, retained_texture_indices: impl IntoIterator<Item = usize>) {
+		if !std::mem::take(&mut self.may_have_orphaned_textures) {
+			return;
+		}
+
+		let mut reference_counts = vec![0_usize; self.textures.len()];
+
+		let history_images = self.retractions.iter().flat_map(|retraction| match retraction {
+			Retraction::DeleteObjects { antitone_index_image_pairs, .. } => antitone_index_image_pairs.iter().map(|(_, image)| image).collect(),
+			_ => Vec::new(),
+		});
+		let pending_images = self.operations.iter().flat_map(|operation| match operation {
+			Operation::CommitImages { images } => images.iter().map(AsRef::as_ref).collect(),
+			_ => Vec::new(),
+		});
+		let branch_images = self.redo_branches.iter().flat_map(RedoBranch::committed_images);
+
+		for texture_index in self
+			.images
+			.iter()
+			.map(AsRef::as_ref)
+			.chain(history_images)
+			.chain(pending_images)
+			.chain(branch_images)
+			.map(|image| image.texture_index)
+			.chain(retained_texture_indices)
+		{
+			if let Some(reference_count) = reference_counts.get_mut(texture_index) {
+				*reference_count += 1;
+			}
+		}
+
+		for (texture, reference_count) in self.textures.iter_mut().zip(reference_counts) {
+			if reference_count == 0 {
+				*texture = None;
+			}
+		}
 	}
 
 	pub fn select(&mut self, min: Vex<2, Vx>, max: Vex<2, Vx>, tilt: f32, screen_center: Vex<2, Vx>, should_aggregate: bool) {
@@ -1055,7 +3713,7 @@ impl Canvas {
 		let beta = selection_corners[3] - selection_corners[0];
 		let alpha_hat = alpha.normalized();
 		let beta_hat = beta.normalized();
-		for image in self.images.iter_mut() {
+		for image in self.images.iter_mut().filter(|image| image.is_visible) {
 			let image_corners = [-image.dimensions, image.dimensions.flip::<1>(), image.dimensions, image.dimensions.flip::<0>()].map(|v| ((v * 0.5).rotate(image.orientation) * image.dilation) + image.position);
 			let image_semidimensions = image.dimensions * 0.5 * image.dilation;
 			let gamma_hat = (image_corners[1] - image_corners[0]).normalized();
@@ -1076,7 +3734,7 @@ impl Canvas {
 			}
 		}
 
-		'strokes: for stroke in self.strokes.iter_mut() {
+		'strokes: for stroke in self.strokes.iter_mut().filter(|stroke| stroke.is_visible) {
 			for point in stroke.points.iter() {
 				let point_position = (stroke.position + point.position.rotate(stroke.orientation) * stroke.dilation - screen_center).rotate(-tilt);
 				if point_position[0] >= min[0] && point_position[1] >= min[1] && point_position[0] <= max[0] && point_position[1] <= max[1] {
@@ -1090,26 +3748,417 @@ impl Canvas {
 		}
 	}
 
+	// Erases strokes along a path of circles.
+	pub fn erase(&mut self, path: &[Vex<2, Vx>], radius: Vx, mode: EraserMode) {
+		let is_touched = |stroke: &Stroke| stroke.is_visible && path.iter().any(|center| stroke.is_near(*center, radius));
+		match mode {
+			EraserMode::Object => {
+				let monotone_stroke_indices = self.strokes.iter().enumerate().filter_map(|(index, stroke)| is_touched(stroke).then_some(index)).collect::<Vec<_>>();
+				if !monotone_stroke_indices.is_empty() {
+					self.perform_operation(Operation::DeleteObjects {
+						monotone_image_indices: Vec::new(),
+						monotone_stroke_indices,
+					});
+				}
+			},
+			EraserMode::Color => {
+				let Some(color) = path.iter().find_map(|center| self.strokes.iter().rev().find(|stroke| is_touched(stroke) && stroke.is_near(*center, radius)).map(|stroke| stroke.color)) else {
+					return;
+				};
+				let monotone_stroke_indices = self.strokes.iter().enumerate().filter_map(|(index, stroke)| (stroke.color.0 == color.0 && is_touched(stroke)).then_some(index)).collect::<Vec<_>>();
+				self.perform_operation(Operation::DeleteObjects {
+					monotone_image_indices: Vec::new(),
+					monotone_stroke_indices,
+				});
+			},
+			EraserMode::Segment => {
+				let mut monotone_stroke_indices = Vec::new();
+				let mut pieces = Vec::new();
+				for (index, stroke) in self.strokes.iter().enumerate().filter(|(_, stroke)| stroke.is_visible) {
+					if let Some(stroke_pieces) = stroke.split_outside(path, radius) {
+						monotone_stroke_indices.push(index);
+						pieces.extend(stroke_pieces.into_iter().map(Into::into));
+					}
+				}
+				if !monotone_stroke_indices.is_empty() {
+					self.perform_operation(Operation::SplitStrokes { monotone_stroke_indices, pieces });
+				}
+			},
+		}
+	}
+
+	// Finds the topmost object within a radius of a point, with strokes drawn above images.
+	pub fn pick(&self, point: Vex<2, Vx>, radius: Vx) -> Option<ObjectRef> {
+		if let Some(index) = self.strokes.iter().rposition(|stroke| stroke.is_visible && stroke.is_near(point, radius)) {
+			return Some(ObjectRef::Stroke(index));
+		}
+
+		self.images
+			.iter()
+			.rposition(|image| {
+				if !image.is_visible {
+					return false;
+				}
+				let local_point = (point - image.position).rotate(-image.orientation);
+				let semidimensions = (image.dimensions * 0.5 + Vex([image.border_width; 2])) * image.dilation + Vex([radius; 2]);
+				local_point[0].abs() <= semidimensions[0] && local_point[1].abs() <= semidimensions[1]
+			})
+			.map(ObjectRef::Image)
+	}
+
 	pub fn set_retraction_count_at_save(&mut self) {
 		self.retraction_count_at_save = Some(self.retractions.len());
 	}
 
+	pub fn object_count(&self) -> usize {
+		self.images.len() + self.strokes.len()
+	}
+
 	pub fn is_saved(&self) -> bool {
 		self.retraction_count_at_save.map_or(false, |x| x == self.retractions.len())
 	}
 
+	// Whether closing the canvas would lose work, which a new canvas without any changes wouldn't.
+	pub fn has_unsaved_changes(&self) -> bool {
+		!self.is_saved() && (self.file_path.is_some() || !self.retractions.is_empty())
+	}
+
+	// Hidden objects can't be selected, so selecting everything leaves them out.
 	pub fn select_all(&mut self, is_selected: bool) {
 		for image in self.images.iter_mut() {
-			image.is_selected = is_selected;
+			image.is_selected = is_selected && image.is_visible;
 		}
 
 		for stroke in self.strokes.iter_mut() {
-			stroke.is_selected = is_selected;
+			stroke.is_selected = is_selected && stroke.is_visible;
 		}
 	}
 
 	pub fn push_texture(&mut self, renderer: &Renderer, dimensions: [NonZeroU32; 2], image: Vec<u8>) -> usize {
-		self.textures.push(renderer.create_texture(dimensions, image));
+		self.textures.push(Some(renderer.create_texture(dimensions, image)));
 		self.textures.len() - 1
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use enumset::EnumSet;
+
+	use super::*;
+	use crate::tools::TransientModeSwitch;
+
+	#[test]
+	fn every_tool_name_round_trips() {
+		let mut multicanvas = Multicanvas::new(&Config::default());
+		for kind in EnumSet::<ToolKind>::all() {
+			assert_eq!(ToolKind::from_key(kind.key()), Some(kind));
+
+			let previous_name = multicanvas.current_tool_name();
+			let is_base = multicanvas.set_tool_by_name(kind.key());
+			assert_eq!(is_base, ModeStack::switch_for_kind(kind).is_some());
+			// Transient tools can't be switched to, so the tool is left as it was.
+			assert_eq!(multicanvas.current_tool_name(), if is_base { kind.key() } else { previous_name });
+		}
+		assert!(!multicanvas.set_tool_by_name("lasso"));
+
+		// Held tools are still reported by name.
+		multicanvas.mode_stack.switch_transient(TransientModeSwitch::Pan { should_pan: true });
+		assert_eq!(multicanvas.current_tool_name(), "pan");
+	}
+
+	#[test]
+	fn the_default_tool_is_switched_to_by_name() {
+		for (default_tool, tool_name) in [("select", "select"), ("erase", "erase"), ("pan", "draw"), ("lasso", "draw")] {
+			let config = Config {
+				default_tool: default_tool.to_owned(),
+				..Config::default()
+			};
+			assert_eq!(Multicanvas::new(&config).current_tool_name(), tool_name);
+		}
+	}
+
+	const COALESCING_WINDOW: Duration = Duration::from_secs(60);
+
+	fn stroke(positions: &[[f32; 2]]) -> Stroke {
+		let points = positions
+			.iter()
+			.map(|&position| Point {
+				position: Vex(position.map(Vx)),
+				pressure: 1.,
+				timestamp: 0,
+			})
+			.collect();
+		Stroke::new(Srgba8([0, 0, 0, 255]), Vx(2.), points, false, Vex::ZERO, 0., 1.)
+	}
+
+	// A canvas holding two strokes, whose history begins with them being drawn.
+	fn canvas() -> Canvas {
+		let mut canvas = Canvas::new(&Config::default());
+		canvas.perform_operation(Operation::CommitStrokes {
+			strokes: vec![stroke(&[[0., 0.], [10., 0.]]).into(), stroke(&[[0., 10.], [10., 20.]]).into()],
+		});
+		canvas
+	}
+
+	fn recolor(canvas: &mut Canvas, indices: Vec<usize>, color: [u8; 4]) {
+		canvas.perform_coalescing_operation(Operation::RecolorStrokes { indices, new_color: Srgba8(color) }, COALESCING_WINDOW);
+	}
+
+	fn translate(canvas: &mut Canvas, stroke_indices: Vec<usize>, vector: [f32; 2]) {
+		canvas.perform_coalescing_operation(
+			Operation::TranslateObjects {
+				image_indices: Vec::new(),
+				stroke_indices,
+				vector: Vex(vector.map(Vx)),
+			},
+			COALESCING_WINDOW,
+		);
+	}
+
+	fn colors(canvas: &Canvas) -> Vec<[u8; 4]> {
+		canvas.strokes.iter().map(|stroke| stroke.color.0).collect()
+	}
+
+	fn positions(canvas: &Canvas) -> Vec<Vex<2, Vx>> {
+		canvas.strokes.iter().map(|stroke| stroke.position).collect()
+	}
+
+	#[test]
+	fn consecutive_adjustments_are_undone_together() {
+		let mut canvas = canvas();
+		recolor(&mut canvas, vec![0, 1], [255, 0, 0, 255]);
+		recolor(&mut canvas, vec![0, 1], [0, 255, 0, 255]);
+		assert_eq!(canvas.retractions.len(), 2);
+		canvas.undo();
+		assert_eq!(colors(&canvas), vec![[0, 0, 0, 255]; 2]);
+		canvas.redo();
+		assert_eq!(colors(&canvas), vec![[0, 255, 0, 255]; 2]);
+
+		translate(&mut canvas, vec![1], [1., 2.]);
+		translate(&mut canvas, vec![1], [3., 4.]);
+		assert_eq!(canvas.retractions.len(), 3);
+		assert_eq!(positions(&canvas), vec![Vex::ZERO, Vex([Vx(4.), Vx(6.)])]);
+		canvas.undo();
+		assert_eq!(positions(&canvas), vec![Vex::ZERO; 2]);
+	}
+
+	#[test]
+	fn adjustments_are_not_merged_across_other_operations() {
+		let mut canvas = canvas();
+		recolor(&mut canvas, vec![0], [255, 0, 0, 255]);
+		translate(&mut canvas, vec![0], [1., 1.]);
+		recolor(&mut canvas, vec![0], [0, 255, 0, 255]);
+		assert_eq!(canvas.retractions.len(), 4);
+		canvas.undo();
+		assert_eq!(colors(&canvas)[0], [255, 0, 0, 255]);
+	}
+
+	#[test]
+	fn adjustments_of_other_selections_are_not_merged() {
+		let mut canvas = canvas();
+		recolor(&mut canvas, vec![0], [255, 0, 0, 255]);
+		recolor(&mut canvas, vec![1], [255, 0, 0, 255]);
+		translate(&mut canvas, vec![0], [1., 1.]);
+		translate(&mut canvas, vec![0, 1], [1., 1.]);
+		assert_eq!(canvas.retractions.len(), 5);
+		canvas.undo();
+		canvas.undo();
+		assert_eq!(positions(&canvas), vec![Vex::ZERO; 2]);
+		canvas.undo();
+		assert_eq!(colors(&canvas), vec![[255, 0, 0, 255], [0, 0, 0, 255]]);
+	}
+
+	fn assert_near(a: Vex<2, Vx>, b: Vex<2, Vx>) {
+		assert!((a - b).norm().0 < 1e-3, "{a:?} is not near {b:?}");
+	}
+
+	// The canvas positions of a stroke's points.
+	fn world_points(stroke: &Stroke) -> Vec<Vex<2, Vx>> {
+		stroke.points.iter().map(|point| stroke.position + point.position.rotate(stroke.orientation) * stroke.dilation).collect()
+	}
+
+	#[test]
+	fn recentered_strokes_stay_in_place() {
+		let mut stroke = stroke(&[[2., 4.], [12., 4.], [12., 10.]]);
+		stroke.position = Vex([Vx(5.), Vx(-3.)]);
+		stroke.orientation = 0.7;
+		stroke.dilation = 1.5;
+		let old_points = world_points(&stroke);
+		let old_aabb = stroke.world_aabb();
+
+		stroke.recenter();
+		for (old, new) in old_points.iter().zip(world_points(&stroke)) {
+			assert_near(*old, new);
+		}
+		let centroid = old_points.iter().fold(Vex::ZERO, |acc, &point| acc + point) / old_points.len() as f32;
+		assert_near(stroke.position, centroid);
+		assert_near(stroke.world_aabb().0, old_aabb.0);
+		assert_near(stroke.world_aabb().1, old_aabb.1);
+	}
+
+	// Checks the cached bounds of every object against bounds computed afresh.
+	fn assert_bounds_are_current(canvas: &Canvas) {
+		for image in canvas.images.iter() {
+			let fresh = Image {
+				world_aabb_cache: AabbCache::default(),
+				..(**image).clone()
+			};
+			assert_eq!(image.world_aabb(), fresh.world_aabb());
+		}
+		for stroke in canvas.strokes.iter() {
+			let fresh = Stroke {
+				world_aabb_cache: AabbCache::default(),
+				..(**stroke).clone()
+			};
+			assert_eq!(stroke.world_aabb(), fresh.world_aabb());
+		}
+	}
+
+	#[test]
+	fn cached_bounds_follow_transformations() {
+		let mut canvas = canvas();
+		canvas.perform_operation(Operation::CommitImages {
+			images: vec![Image {
+				position: Vex([Vx(20.), Vx(5.)]),
+				orientation: 0.3,
+				dilation: 1.,
+				is_selected: false,
+				is_visible: true,
+				note: None,
+				border_width: Vx(1.),
+				border_color: Srgba8([0, 0, 0, 255]),
+				is_pixelated: false,
+				texture_index: 0,
+				dimensions: Vex([Vx(8.), Vx(4.)]),
+				world_aabb_cache: AabbCache::default(),
+			}
+			.into()],
+		});
+		let center = Vex([Vx(3.), Vx(-2.)]);
+		let operations = [
+			Operation::TranslateObjects {
+				image_indices: vec![0],
+				stroke_indices: vec![0, 1],
+				vector: Vex([Vx(4.), Vx(-1.)]),
+			},
+			Operation::RotateObjects {
+				image_indices: vec![0],
+				stroke_indices: vec![0, 1],
+				center,
+				angle: 0.9,
+			},
+			Operation::ResizeObjects {
+				image_indices: vec![0],
+				stroke_indices: vec![0, 1],
+				center,
+				dilation: 1.7,
+			},
+			Operation::ResizeObjectsNonUniform {
+				image_indices: vec![0],
+				stroke_indices: vec![0, 1],
+				center,
+				scale: Vex([2., 0.5]),
+			},
+		];
+
+		let count = operations.len();
+		for operation in operations {
+			assert_bounds_are_current(&canvas);
+			canvas.perform_operation(operation);
+			assert_bounds_are_current(&canvas);
+		}
+		for _ in 0..count {
+			canvas.undo();
+			assert_bounds_are_current(&canvas);
+		}
+		for _ in 0..count {
+			canvas.redo();
+			assert_bounds_are_current(&canvas);
+		}
+	}
+
+	fn selection(canvas: &Canvas) -> Vec<bool> {
+		canvas.strokes.iter().map(|stroke| stroke.is_selected).collect()
+	}
+
+	#[test]
+	fn undo_and_redo_keep_order_and_selection() {
+		let mut canvas = Canvas::new(&Config::default());
+		let strokes = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]].map(|color| Stroke {
+			color: Srgba8(color),
+			is_selected: color[1] == 255,
+			..stroke(&[[0., 0.], [10., 0.]])
+		});
+		canvas.perform_operation(Operation::CommitStrokes {
+			strokes: strokes.into_iter().map(Into::into).collect(),
+		});
+		let (old_colors, old_selection) = (colors(&canvas), selection(&canvas));
+
+		canvas.undo();
+		assert!(canvas.strokes.is_empty());
+		canvas.redo();
+		assert_eq!((colors(&canvas), selection(&canvas)), (old_colors.clone(), old_selection.clone()));
+
+		canvas.perform_operation(Operation::DeleteObjects {
+			monotone_image_indices: Vec::new(),
+			monotone_stroke_indices: vec![0, 1],
+		});
+		canvas.undo();
+		assert_eq!((colors(&canvas), selection(&canvas)), (old_colors, old_selection));
+	}
+
+	#[test]
+	fn split_pieces_keep_selection_and_note() {
+		let stroke = Stroke {
+			is_selected: true,
+			note: Some("a note".to_owned()),
+			..stroke(&[[0., 0.], [10., 0.], [20., 0.], [30., 0.], [40., 0.]])
+		};
+		let pieces = stroke.split_outside(&[Vex([Vx(20.), Vx(0.)])], Vx(1.)).unwrap();
+		assert_eq!(pieces.len(), 2);
+		for piece in pieces {
+			assert!(piece.is_selected);
+			assert_eq!(piece.note.as_deref(), Some("a note"));
+		}
+	}
+
+	// The triangles of some geometry, in a fixed order.
+	fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+		let mut triangles = indices.chunks_exact(3).map(|triangle| [triangle[0], triangle[1], triangle[2]]).collect::<Vec<_>>();
+		triangles.sort_unstable();
+		triangles
+	}
+
+	#[test]
+	fn incremental_geometry_matches_a_full_rebuild() {
+		let mut incomplete = IncompleteStroke::new(Vex::ZERO, &Canvas::new(&Config::default()), false, 1., false, false, false);
+		for (i, (position, pressure)) in [([0., 0.], 1.), ([10., 0.], 0.8), ([20., 5.], 0.6), ([25., 15.], 0.9), ([15., 20.], 0.5), ([15., 30.], 1.)].into_iter().enumerate() {
+			incomplete.add_point(Vex(position.map(Vx)), pressure);
+			assert_eq!(incomplete.points.len(), i + 1);
+
+			for predicted_point in [
+				None,
+				Some(Point {
+					position: Vex([Vx(40.), Vx(40.)]),
+					pressure: 0.7,
+					timestamp: 0,
+				}),
+			] {
+				incomplete.predicted_point = predicted_point;
+				let (predicted_vertices, predicted_indices) = incomplete.predicted_geometry();
+				// A lone point isn't shown until the stroke is finalized, even with a prediction.
+				if incomplete.points.len() < 2 {
+					assert!(incomplete.vertices.is_empty() && predicted_vertices.is_empty() && predicted_indices.is_empty());
+					continue;
+				}
+
+				let points = incomplete.points.iter().chain(&incomplete.predicted_point).cloned().collect::<Vec<_>>();
+				let vertices = incomplete.vertices.iter().chain(&predicted_vertices).copied().collect::<Vec<_>>();
+				let indices = incomplete.relative_indices.iter().chain(&predicted_indices).copied().collect::<Vec<_>>();
+				let (full_vertices, full_indices) = Stroke::compute_geometry(&points, incomplete.radius, false);
+				assert_eq!(vertices, full_vertices);
+				assert_eq!(sorted_triangles(&indices), sorted_triangles(&full_indices));
+			}
+		}
+	}
+}
@@ -0,0 +1,26 @@
+// Copyright (C) 2023 Aaron Yeoh Cruz <zeyonaut@gmail.com>
+// SPDX-License-Identifier: MPL-2.0
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+#[cfg(not(target_os = "windows"))]
+mod arboard;
+#[cfg(target_os = "windows")]
+mod windows;
+
+#[cfg(not(target_os = "windows"))]
+pub use self::arboard::Clipboard;
+#[cfg(target_os = "windows")]
+pub use self::windows::Clipboard;
+
+const CLIPBOARD_FORMAT_NAME: &str = crate::APP_NAME_LOWERCASE;
+
+pub enum ClipboardData {
+	// The objects themselves are kept in process; the clipboard only records that they were copied.
+	Custom,
+	Image { dimensions: [u32; 2], data: Vec<u8> },
+	// Text for other applications, like markup; it is never read back as objects.
+	Text(String),
+}
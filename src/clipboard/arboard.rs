@@ -0,0 +1,55 @@
+// Copyright (C) 2023 Aaron Yeoh Cruz <zeyonaut@gmail.com>
+// SPDX-License-Identifier: MPL-2.0
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::cell::RefCell;
+
+use super::{ClipboardData, CLIPBOARD_FORMAT_NAME};
+
+// Not every platform supports custom clipboard formats, so copied objects are marked with a text placeholder instead.
+pub struct Clipboard {
+	clipboard: RefCell<arboard::Clipboard>,
+}
+
+impl Clipboard {
+	pub fn new() -> Option<Self> {
+		let clipboard = arboard::Clipboard::new().ok()?;
+		Some(Self { clipboard: RefCell::new(clipboard) })
+	}
+
+	pub fn write(&self, content: ClipboardData) -> Option<()> {
+		let mut clipboard = self.clipboard.borrow_mut();
+		match content {
+			ClipboardData::Custom => clipboard.set_text(placeholder()).ok()?,
+			ClipboardData::Image { dimensions: [width, height], data } => clipboard
+				.set_image(arboard::ImageData {
+					width: width as usize,
+					height: height as usize,
+					bytes: data.into(),
+				})
+				.ok()?,
+			ClipboardData::Text(text) => clipboard.set_text(text).ok()?,
+		}
+		Some(())
+	}
+
+	pub fn read(&self) -> Option<ClipboardData> {
+		let mut clipboard = self.clipboard.borrow_mut();
+		if clipboard.get_text().is_ok_and(|text| text == placeholder()) {
+			return Some(ClipboardData::Custom);
+		} else if let Ok(image) = clipboard.get_image() {
+			return Some(ClipboardData::Image {
+				dimensions: [image.width as u32, image.height as u32],
+				data: image.bytes.into_owned(),
+			});
+		}
+		None
+	}
+}
+
+fn placeholder() -> String {
+	format!("<{CLIPBOARD_FORMAT_NAME} objects>")
+}
@@ -5,7 +5,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::{io::Cursor, num::NonZeroU32};
+use std::num::NonZeroU32;
 
 use clipboard_win::{
 	formats::{Bitmap, CF_BITMAP},
@@ -13,18 +13,14 @@ use clipboard_win::{
 };
 use embedded_graphics::pixelcolor::RgbColor;
 
+use super::{ClipboardData, CLIPBOARD_FORMAT_NAME};
+use crate::file::decode_texture;
+
 pub struct Clipboard {
 	custom_format: NonZeroU32,
 	png_format: NonZeroU32,
 }
 
-const CLIPBOARD_FORMAT_NAME: &str = crate::APP_NAME_LOWERCASE;
-
-pub enum ClipboardData {
-	Custom,
-	Image { dimensions: [u32; 2], data: Vec<u8> },
-}
-
 impl Clipboard {
 	pub fn new() -> Option<Self> {
 		let custom_format = clipboard_win::register_format(CLIPBOARD_FORMAT_NAME)?;
@@ -40,6 +36,7 @@ impl Clipboard {
 				clipboard_win::raw::close().ok()?;
 			},
 			ClipboardData::Image { .. } => {},
+			ClipboardData::Text(text) => clipboard_win::set_clipboard_string(&text).ok()?,
 		}
 		Some(())
 	}
@@ -53,14 +50,13 @@ impl Clipboard {
 			clipboard_win::raw::get_vec(self.png_format.into(), &mut data).ok()?;
 			clipboard_win::raw::close().ok()?;
 
-			let png_decoder = png::Decoder::new(Cursor::new(data));
-			let mut png_reader = png_decoder.read_info().ok()?;
-			let mut image_buffer = vec![0; png_reader.output_buffer_size()];
-			let width = png_reader.info().width;
-			let height = png_reader.info().height;
-			png_reader.next_frame(&mut image_buffer).ok()?;
+			// Images may be stored with any color type and bit depth, but are pasted as 8-bit RGBA, like stored textures.
+			let (dimensions, image_buffer) = decode_texture(&data).ok()?;
 
-			return Some(ClipboardData::Image { dimensions: [width, height], data: image_buffer });
+			return Some(ClipboardData::Image {
+				dimensions: dimensions.map(NonZeroU32::get),
+				data: image_buffer,
+			});
 		} else if clipboard_win::is_format_avail(CF_BITMAP) {
 			let mut data = Vec::new();
 			clipboard_win::raw::open().ok()?;
@@ -84,13 +80,13 @@ impl Clipboard {
 				}
 			}
 
-			// We premultiply RGB by transparency to prevent the 'white border' effect.
-			// For future reference: this might not be a good idea for the general case, but it works nicely
-			// for Windows' legacy snipping tool.
+			// The bitmap stores its channels as BGRA, so the colors are reordered.
+			// Textures hold straight alpha, so the colors aren't premultiplied here; doing so darkened the edges of translucent regions.
+			// The 'white border' that premultiplying used to hide came from filtering, which now weighs texels by their alpha.
 			for (chunk, pixel) in data.chunks_exact_mut(4).zip(bmp.pixels()) {
-				chunk[0] = (pixel.1.r() as f64 * chunk[3] as f64 / 255.) as u8;
-				chunk[1] = (pixel.1.g() as f64 * chunk[3] as f64 / 255.) as u8;
-				chunk[2] = (pixel.1.b() as f64 * chunk[3] as f64 / 255.) as u8;
+				chunk[0] = pixel.1.r();
+				chunk[1] = pixel.1.g();
+				chunk[2] = pixel.1.b();
 			}
 
 			return Some(ClipboardData::Image { dimensions: [width, height], data });
@@ -5,16 +5,118 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::fs::File;
+use std::{fs::File, path::PathBuf, time::Duration};
 
-use kdl::{KdlDocument, KdlValue};
+use kdl::{KdlDocument, KdlEntry, KdlNode, KdlValue};
+use winit::monitor::MonitorHandle;
 
-use crate::utility::{Srgb8, Vx};
+use crate::{
+	canvas::View,
+	file::ReplayExportSettings,
+	input::Key,
+	render::{grain::Grain, DEFAULT_SELECTION_COLOR},
+	tools::{ColorPickerPlacement, EraserMode, PenButtonTool, ToolKind, WheelMode},
+	utility::{Lx, Srgb8, Srgba8, Vex, Vx, Zoom},
+};
+
+// The most times a stroke may be repeated about the center under radial symmetry.
+const MAX_RADIAL_SYMMETRY_SEGMENTS: usize = 64;
 
 pub struct Config {
 	pub default_canvas_color: Srgb8,
 	pub default_stroke_color: Srgb8,
 	pub default_stroke_radius: Vx,
+	pub default_image_border_width: Vx,
+	pub default_image_border_color: Srgba8,
+	pub software_cursor: bool,
+	pub input_prediction: bool,
+	pub screen_width_pen: bool,
+	pub constant_pressure_pen: bool,
+	pub pressure_simulation: bool,
+	pub presentation_hides_cursor: bool,
+	pub linear_blending: bool,
+	pub round_caps: bool,
+	pub radial_symmetry_segments: usize,
+	// The number of objects on a canvas past which a warning is shown, or zero for no limit.
+	pub object_limit: usize,
+	// How many redo branches set aside by editing after undoing are kept per canvas.
+	pub redo_branches: usize,
+	pub point_density: f32,
+	// Switches the mode stack to the tool in use at launch.
+	// The key of the base tool in use on launch, e.g. "select".
+	pub default_tool: String,
+	pub open_canvas_on_launch: bool,
+	// Whether closing a tab or the window saves unsaved canvases that have a file, rather than asking first.
+	pub auto_save_on_close: bool,
+	pub eraser_mode: EraserMode,
+	pub page_size: Vex<2, Vx>,
+	// The view that new canvases start with.
+	pub new_canvas_view: View,
+	// The angle, in radians, to which the tilt snaps while orbiting with Control held.
+	pub tilt_snap_angle: f32,
+	// Strokes are drawn as flat ink without a grain.
+	pub stroke_grain: Option<Grain>,
+	// The font of interface text, as a path to a font file or the family name of an installed font, if not the bundled DejaVu Sans.
+	pub font: Option<String>,
+	pub font_size: f32,
+	pub edge_scroll: bool,
+	// Whether the view keeps gliding after a pan is released while moving.
+	pub pan_inertia: bool,
+	pub wheel_mode: WheelMode,
+	// The tools that the lower and upper barrel buttons of a pen switch to while held.
+	pub lower_pen_button: Option<PenButtonTool>,
+	pub upper_pen_button: Option<PenButtonTool>,
+	// How far the cursor is moved from where the pointer reports it, to make up for the parallax of pen displays.
+	pub pen_offset: Vex<2, Lx>,
+	// Whether undo and redo are also bound to Z and Shift-Z without Control.
+	pub bare_undo_keys: bool,
+	// The key held to pick a color, or with Shift to pick the background color.
+	pub color_picker_key: Key,
+	pub checkerboard: bool,
+	// Whether a dotted circle the size of the pen follows the cursor while drawing, including while the pen hovers.
+	pub brush_outline: bool,
+	// Whether snapping, like to rulers and baselines, is enabled at launch.
+	pub snapping: bool,
+	pub baselines: bool,
+	// The distance between baselines, and the fraction of the way that committed strokes are pulled onto the nearest one.
+	pub baseline_spacing: Vx,
+	pub baseline_snap: f32,
+	// How long the pen may be lifted before the current stroke is committed, so that brief lifts don't split it.
+	pub stroke_lift_timeout: Duration,
+	// Whether a stroke still being drawn is committed, rather than discarded, when the tool is switched.
+	pub commit_stroke_on_tool_switch: bool,
+	// How long after the last tablet packet touches and clicks not made by the pen are ignored, as they are likely from the palm.
+	pub palm_rejection: Duration,
+	// How soon after an adjustment, like a recolor or a move, another like it is merged into the same undo step.
+	pub undo_coalescing: Duration,
+	// Whether pasted images have their corners snapped to whole canvas units, so that screenshots stay crisp.
+	pub snap_pasted_images: bool,
+	// Whether pasted images are sampled without filtering.
+	pub pixelated_images: bool,
+	// The shortest time between frames drawn in response to input.
+	pub frame_interval: Duration,
+	// Whether new strokes are drawn along a spline through their points, rather than straight between them.
+	pub smooth_strokes: bool,
+	// The weight of the previous pressure in the moving average over pressure readings, where zero turns filtering off.
+	pub pressure_smoothing: f32,
+	// The raw tablet pressures, as fractions of the full range, that are taken to be no pressure and full pressure.
+	pub pressure_range: [f32; 2],
+	pub color_picker_placement: ColorPickerPlacement,
+	// Whether swatches of colors in harmony with the current one are shown beside the color picker.
+	pub color_harmonies: bool,
+	pub replay_export: ReplayExportSettings,
+	// The color schemes cycled through by name, in order.
+	pub color_schemes: Vec<ColorScheme>,
+	// The colors laid out in the swatch ring, clockwise from the top.
+	pub palette: Vec<Srgba8>,
+}
+
+// A named set of colors that can be switched to at once, e.g. for day and night.
+pub struct ColorScheme {
+	pub name: String,
+	pub background_color: Srgb8,
+	pub stroke_color: Srgb8,
+	pub selection_color: Srgb8,
 }
 
 impl Default for Config {
@@ -23,17 +125,89 @@ impl Default for Config {
 			default_canvas_color: Srgb8([0x12, 0x12, 0x12]),
 			default_stroke_color: Srgb8([0xff, 0xff, 0xff]),
 			default_stroke_radius: Vx(4.),
+			default_image_border_width: Vx(0.),
+			default_image_border_color: Srgba8([0xff, 0xff, 0xff, 0xff]),
+			software_cursor: false,
+			input_prediction: false,
+			screen_width_pen: false,
+			constant_pressure_pen: false,
+			pressure_simulation: false,
+			presentation_hides_cursor: true,
+			linear_blending: true,
+			round_caps: false,
+			radial_symmetry_segments: 6,
+			object_limit: 20000,
+			redo_branches: 0,
+			point_density: 1.,
+			default_tool: ToolKind::Draw.key().to_owned(),
+			open_canvas_on_launch: false,
+			auto_save_on_close: false,
+			eraser_mode: EraserMode::Object,
+			// A4 at 96 DPI.
+			page_size: Vex([Vx(794.), Vx(1123.)]),
+			new_canvas_view: View::new(),
+			tilt_snap_angle: std::f32::consts::FRAC_PI_4,
+			stroke_grain: None,
+			font: None,
+			font_size: 13.,
+			edge_scroll: true,
+			pan_inertia: false,
+			wheel_mode: WheelMode::Pan,
+			lower_pen_button: None,
+			upper_pen_button: None,
+			pen_offset: Vex([Lx(0.); 2]),
+			bare_undo_keys: true,
+			color_picker_key: Key::Tab,
+			checkerboard: false,
+			brush_outline: false,
+			snapping: true,
+			baselines: false,
+			baseline_spacing: Vx(32.),
+			baseline_snap: 0.,
+			stroke_lift_timeout: Duration::ZERO,
+			commit_stroke_on_tool_switch: false,
+			palm_rejection: Duration::ZERO,
+			undo_coalescing: Duration::ZERO,
+			snap_pasted_images: false,
+			pixelated_images: false,
+			frame_interval: Duration::from_secs(1) / 90,
+			smooth_strokes: false,
+			pressure_smoothing: 0.,
+			pressure_range: [0., 1.],
+			color_picker_placement: ColorPickerPlacement::Cursor,
+			color_harmonies: true,
+			replay_export: ReplayExportSettings { size: 720, speed: 1., frame_rate: 20. },
+			color_schemes: vec![
+				ColorScheme {
+					name: "dark".to_owned(),
+					background_color: Srgb8([0x12, 0x12, 0x12]),
+					stroke_color: Srgb8([0xff, 0xff, 0xff]),
+					selection_color: DEFAULT_SELECTION_COLOR,
+				},
+				ColorScheme {
+					name: "light".to_owned(),
+					background_color: Srgb8([0xfa, 0xfa, 0xfa]),
+					stroke_color: Srgb8([0x12, 0x12, 0x12]),
+					selection_color: Srgb8([0x00, 0x78, 0xd4]),
+				},
+			],
+			palette: vec![
+				Srgba8([0xff, 0xff, 0xff, 0xff]),
+				Srgba8([0xf0, 0x4a, 0x4a, 0xff]),
+				Srgba8([0xf5, 0x9e, 0x2f, 0xff]),
+				Srgba8([0xf2, 0xd6, 0x3a, 0xff]),
+				Srgba8([0x5c, 0xc9, 0x5c, 0xff]),
+				Srgba8([0x3d, 0x9b, 0xf0, 0xff]),
+				Srgba8([0x9b, 0x6b, 0xe8, 0xff]),
+				Srgba8([0x80, 0x80, 0x80, 0xff]),
+			],
 		}
 	}
 }
 
 impl Config {
 	pub fn load() -> Option<Self> {
-		let mut inksy_config_file_path = dirs::config_dir()?;
-		inksy_config_file_path.push("inksy");
-		if !inksy_config_file_path.exists() {
-			std::fs::create_dir(inksy_config_file_path.clone()).ok()?;
-		}
+		let mut inksy_config_file_path = inksy_config_directory_path()?;
 		inksy_config_file_path.push("inksy.kdl");
 		if !inksy_config_file_path.exists() {
 			File::create(inksy_config_file_path).ok()?;
@@ -44,17 +218,286 @@ impl Config {
 
 		let inksy_config_document = inksy_config_file_data.parse::<KdlDocument>().ok()?;
 
+		Some(Self::from_document(&inksy_config_document))
+	}
+
+	// Reads each setting from the document, falling back to its default where it is missing or invalid.
+	fn from_document(inksy_config_document: &KdlDocument) -> Self {
 		let default = Self::default();
 
 		let default_canvas_color = parse_kdl_integer_array(inksy_config_document.get_args("default-canvas-color")).map(Srgb8).unwrap_or(default.default_canvas_color);
 		let default_stroke_color = parse_kdl_integer_array(inksy_config_document.get_args("default-stroke-color")).map(Srgb8).unwrap_or(default.default_stroke_color);
 		let default_stroke_radius = parse_kdl_f64(inksy_config_document.get_args("default-stroke-radius")).map(|x| Vx(x as _)).unwrap_or(default.default_stroke_radius);
-		Some(Config {
+		let default_image_border_width = parse_kdl_f64(inksy_config_document.get_args("default-image-border-width")).map(|x| Vx(x as _)).unwrap_or(default.default_image_border_width);
+		let default_image_border_color = parse_kdl_integer_array(inksy_config_document.get_args("default-image-border-color"))
+			.map(|[r, g, b]| Srgba8([r, g, b, 0xff]))
+			.unwrap_or(default.default_image_border_color);
+		let software_cursor = parse_kdl_bool(inksy_config_document.get_args("software-cursor")).unwrap_or(default.software_cursor);
+		let input_prediction = parse_kdl_bool(inksy_config_document.get_args("input-prediction")).unwrap_or(default.input_prediction);
+		let screen_width_pen = parse_kdl_bool(inksy_config_document.get_args("screen-width-pen")).unwrap_or(default.screen_width_pen);
+		let constant_pressure_pen = parse_kdl_bool(inksy_config_document.get_args("constant-pressure-pen")).unwrap_or(default.constant_pressure_pen);
+		let pressure_simulation = parse_kdl_bool(inksy_config_document.get_args("pressure-simulation")).unwrap_or(default.pressure_simulation);
+		let presentation_hides_cursor = parse_kdl_bool(inksy_config_document.get_args("presentation-hides-cursor")).unwrap_or(default.presentation_hides_cursor);
+		let linear_blending = parse_kdl_bool(inksy_config_document.get_args("linear-blending")).unwrap_or(default.linear_blending);
+		let round_caps = parse_kdl_bool(inksy_config_document.get_args("round-caps")).unwrap_or(default.round_caps);
+		// Very dense strokes take a lot of memory and time to render, so the density is capped.
+		let point_density = parse_kdl_f64(inksy_config_document.get_args("point-density")).filter(|x| x.is_finite()).map_or(default.point_density, |x| (x as f32).clamp(0.25, 16.));
+		// Each segment repeats every stroke, so the segment count is capped like the density.
+		let radial_symmetry_segments = parse_kdl_integer_array(inksy_config_document.get_args("radial-symmetry-segments"))
+			.map(|[n]: [usize; 1]| n)
+			.filter(|n| *n >= 2)
+			.map_or(default.radial_symmetry_segments, |n| n.min(MAX_RADIAL_SYMMETRY_SEGMENTS));
+		let object_limit = parse_kdl_integer_array(inksy_config_document.get_args("object-limit")).map_or(default.object_limit, |[n]| n);
+		let redo_branches = parse_kdl_integer_array(inksy_config_document.get_args("redo-branches")).map_or(default.redo_branches, |[n]| n);
+		// The tool is switched to by name on launch, where unknown names fall back to the draw tool.
+		let default_tool = parse_kdl_string(inksy_config_document.get_args("default-tool")).map_or(default.default_tool, str::to_owned);
+		let open_canvas_on_launch = parse_kdl_bool(inksy_config_document.get_args("open-canvas-on-launch")).unwrap_or(default.open_canvas_on_launch);
+		let auto_save_on_close = parse_kdl_bool(inksy_config_document.get_args("auto-save-on-close")).unwrap_or(default.auto_save_on_close);
+		let eraser_mode = parse_kdl_string(inksy_config_document.get_args("eraser-mode"))
+			.and_then(|name| match name {
+				"object" => Some(EraserMode::Object),
+				"color" => Some(EraserMode::Color),
+				"segment" => Some(EraserMode::Segment),
+				_ => None,
+			})
+			.unwrap_or(default.eraser_mode);
+		let page_size = parse_kdl_f64_array(inksy_config_document.get_args("page-size"))
+			.filter(|dimensions| dimensions.iter().all(|x| *x > 0.))
+			.map(|dimensions| Vex(dimensions.map(|x| Vx(x as _))))
+			.unwrap_or(default.page_size);
+		let new_canvas_view = View {
+			position: parse_kdl_f64_array(inksy_config_document.get_args("new-canvas-position"))
+				.filter(|position| position.iter().all(|x| x.is_finite()))
+				.map_or(default.new_canvas_view.position, |position| Vex(position.map(|x| Vx(x as _)))),
+			zoom: parse_kdl_f64(inksy_config_document.get_args("new-canvas-zoom"))
+				.filter(|x| x.is_finite() && *x > 0.)
+				.map_or(default.new_canvas_view.zoom, |x| Zoom(x.clamp(0.01, 100.) as f32)),
+			..default.new_canvas_view
+		};
+		// The snap angle is configured in degrees.
+		let tilt_snap_angle = parse_kdl_f64(inksy_config_document.get_args("tilt-snap-angle"))
+			.filter(|x| *x > 0. && *x <= 180.)
+			.map(|x| (x as f32).to_radians())
+			.unwrap_or(default.tilt_snap_angle);
+		let stroke_grain = parse_kdl_string(inksy_config_document.get_args("stroke-grain"))
+			.and_then(|name| match name {
+				"none" => Some(None),
+				"pencil" => Some(Some(Grain::Pencil)),
+				"charcoal" => Some(Some(Grain::Charcoal)),
+				"canvas" => Some(Some(Grain::Canvas)),
+				_ => None,
+			})
+			.unwrap_or(default.stroke_grain);
+		let font = parse_kdl_string(inksy_config_document.get_args("font")).map_or(default.font, |font| (font != "default").then(|| font.to_owned()));
+		let font_size = parse_kdl_f64(inksy_config_document.get_args("font-size")).filter(|x| x.is_finite()).map_or(default.font_size, |x| (x as f32).clamp(6., 48.));
+		let edge_scroll = parse_kdl_bool(inksy_config_document.get_args("edge-scroll")).unwrap_or(default.edge_scroll);
+		let pan_inertia = parse_kdl_bool(inksy_config_document.get_args("pan-inertia")).unwrap_or(default.pan_inertia);
+		let wheel_mode = parse_kdl_string(inksy_config_document.get_args("wheel-mode"))
+			.and_then(|name| match name {
+				"pan" => Some(WheelMode::Pan),
+				"zoom" => Some(WheelMode::Zoom),
+				_ => None,
+			})
+			.unwrap_or(default.wheel_mode);
+		let lower_pen_button = parse_kdl_pen_button_tool(inksy_config_document.get_args("lower-pen-button")).unwrap_or(default.lower_pen_button);
+		let upper_pen_button = parse_kdl_pen_button_tool(inksy_config_document.get_args("upper-pen-button")).unwrap_or(default.upper_pen_button);
+		let pen_offset = parse_kdl_f64_array(inksy_config_document.get_args("pen-offset"))
+			.filter(|offset| offset.iter().all(|x| x.is_finite()))
+			.map_or(default.pen_offset, |offset| Vex(offset.map(|x| Lx(x as _))));
+		let bare_undo_keys = parse_kdl_bool(inksy_config_document.get_args("bare-undo-keys")).unwrap_or(default.bare_undo_keys);
+		// Modifiers can't be held on their own to pick a color.
+		let color_picker_key = parse_kdl_string(inksy_config_document.get_args("color-picker-key"))
+			.and_then(Key::from_name)
+			.filter(|key| !matches!(key, Key::Control | Key::Shift))
+			.unwrap_or(default.color_picker_key);
+		let checkerboard = parse_kdl_bool(inksy_config_document.get_args("checkerboard")).unwrap_or(default.checkerboard);
+		let brush_outline = parse_kdl_bool(inksy_config_document.get_args("brush-outline")).unwrap_or(default.brush_outline);
+		let snapping = parse_kdl_bool(inksy_config_document.get_args("snapping")).unwrap_or(default.snapping);
+		let baselines = parse_kdl_bool(inksy_config_document.get_args("baselines")).unwrap_or(default.baselines);
+		let baseline_spacing = parse_kdl_f64(inksy_config_document.get_args("baseline-spacing")).filter(|x| x.is_finite() && *x >= 1.).map_or(default.baseline_spacing, |x| Vx(x as f32));
+		let baseline_snap = parse_kdl_f64(inksy_config_document.get_args("baseline-snap")).filter(|x| x.is_finite()).map_or(default.baseline_snap, |x| x.clamp(0., 1.) as f32);
+		// The timeout is given in milliseconds, and is capped so that a stroke can't linger indefinitely.
+		let stroke_lift_timeout = parse_kdl_f64(inksy_config_document.get_args("stroke-lift-timeout"))
+			.filter(|x| x.is_finite())
+			.map_or(default.stroke_lift_timeout, |x| Duration::from_secs_f64(x.clamp(0., 1000.) / 1000.));
+		let commit_stroke_on_tool_switch = parse_kdl_bool(inksy_config_document.get_args("commit-stroke-on-tool-switch")).unwrap_or(default.commit_stroke_on_tool_switch);
+		let palm_rejection = parse_kdl_f64(inksy_config_document.get_args("palm-rejection"))
+			.filter(|x| x.is_finite())
+			.map(|x| Duration::from_secs_f64(x.clamp(0., 5000.) / 1000.))
+			.unwrap_or(default.palm_rejection);
+		let undo_coalescing = parse_kdl_f64(inksy_config_document.get_args("undo-coalescing"))
+			.filter(|x| x.is_finite())
+			.map(|x| Duration::from_secs_f64(x.clamp(0., 5000.) / 1000.))
+			.unwrap_or(default.undo_coalescing);
+		let snap_pasted_images = parse_kdl_bool(inksy_config_document.get_args("snap-pasted-images")).unwrap_or(default.snap_pasted_images);
+		let pixelated_images = parse_kdl_bool(inksy_config_document.get_args("pixelated-images")).unwrap_or(default.pixelated_images);
+		let frame_interval = parse_kdl_f64(inksy_config_document.get_args("max-frame-rate"))
+			.filter(|x| x.is_finite())
+			.map(|x| Duration::from_secs_f64(1. / x.clamp(1., 500.)))
+			.unwrap_or(default.frame_interval);
+		let smooth_strokes = parse_kdl_bool(inksy_config_document.get_args("smooth-strokes")).unwrap_or(default.smooth_strokes);
+		let pressure_smoothing = parse_kdl_f64(inksy_config_document.get_args("pressure-smoothing"))
+			.filter(|x| x.is_finite())
+			.map_or(default.pressure_smoothing, |x| x.clamp(0., 0.95) as f32);
+		// A range that is empty or inverted is ignored.
+		let pressure_range =
+			[("pressure-min", default.pressure_range[0]), ("pressure-max", default.pressure_range[1])].map(|(name, default)| parse_kdl_f64(inksy_config_document.get_args(name)).filter(|x| x.is_finite()).map_or(default, |x| x.clamp(0., 1.) as f32));
+		let pressure_range = if pressure_range[0] < pressure_range[1] { pressure_range } else { default.pressure_range };
+		let color_picker_placement = parse_kdl_string(inksy_config_document.get_args("color-picker-placement"))
+			.and_then(|name| match name {
+				"cursor" => Some(ColorPickerPlacement::Cursor),
+				"center" => Some(ColorPickerPlacement::Center),
+				"top-left" => Some(ColorPickerPlacement::Corner { is_right: false, is_bottom: false }),
+				"top-right" => Some(ColorPickerPlacement::Corner { is_right: true, is_bottom: false }),
+				"bottom-left" => Some(ColorPickerPlacement::Corner { is_right: false, is_bottom: true }),
+				"bottom-right" => Some(ColorPickerPlacement::Corner { is_right: true, is_bottom: true }),
+				_ => None,
+			})
+			.unwrap_or(default.color_picker_placement);
+		let color_harmonies = parse_kdl_bool(inksy_config_document.get_args("color-harmonies")).unwrap_or(default.color_harmonies);
+		let replay_export = ReplayExportSettings {
+			size: parse_kdl_f64(inksy_config_document.get_args("replay-export-size"))
+				.filter(|x| x.is_finite())
+				.map_or(default.replay_export.size, |x| x.clamp(16., 4096.) as u32),
+			speed: parse_kdl_f64(inksy_config_document.get_args("replay-export-speed"))
+				.filter(|x| x.is_finite())
+				.map_or(default.replay_export.speed, |x| x.clamp(0.1, 100.) as f32),
+			// GIF frames last a whole number of hundredths of a second, and many viewers slow down frames shorter than two.
+			frame_rate: parse_kdl_f64(inksy_config_document.get_args("replay-export-frame-rate"))
+				.filter(|x| x.is_finite())
+				.map_or(default.replay_export.frame_rate, |x| x.clamp(1., 50.) as f32),
+		};
+		// Configured color schemes replace the built-in ones.
+		let color_schemes: Vec<_> = inksy_config_document.nodes().iter().filter(|node| node.name().value() == "color-scheme").filter_map(parse_kdl_color_scheme).collect();
+		let color_schemes = if color_schemes.is_empty() { default.color_schemes } else { color_schemes };
+		let palette = inksy_config_document.get("palette").and_then(KdlNode::children).map(parse_kdl_palette).filter(|palette| !palette.is_empty()).unwrap_or(default.palette);
+		Config {
 			default_canvas_color,
 			default_stroke_color,
 			default_stroke_radius,
+			default_image_border_width,
+			default_image_border_color,
+			software_cursor,
+			input_prediction,
+			screen_width_pen,
+			constant_pressure_pen,
+			pressure_simulation,
+			presentation_hides_cursor,
+			linear_blending,
+			round_caps,
+			radial_symmetry_segments,
+			object_limit,
+			redo_branches,
+			point_density,
+			default_tool,
+			open_canvas_on_launch,
+			auto_save_on_close,
+			eraser_mode,
+			page_size,
+			new_canvas_view,
+			tilt_snap_angle,
+			stroke_grain,
+			font,
+			font_size,
+			edge_scroll,
+			pan_inertia,
+			wheel_mode,
+			lower_pen_button,
+			upper_pen_button,
+			pen_offset,
+			bare_undo_keys,
+			color_picker_key,
+			checkerboard,
+			brush_outline,
+			snapping,
+			baselines,
+			baseline_spacing,
+			baseline_snap,
+			stroke_lift_timeout,
+			commit_stroke_on_tool_switch,
+			palm_rejection,
+			undo_coalescing,
+			snap_pasted_images,
+			pixelated_images,
+			frame_interval,
+			smooth_strokes,
+			pressure_smoothing,
+			pressure_range,
+			color_picker_placement,
+			color_harmonies,
+			replay_export,
+			color_schemes,
+			palette,
+		}
+	}
+}
+
+// The last known geometry of the window, restored on launch.
+pub struct WindowGeometry {
+	pub position: [i32; 2],
+	pub size: [u32; 2],
+	pub is_maximized: bool,
+}
+
+impl WindowGeometry {
+	pub fn load() -> Option<Self> {
+		let mut window_geometry_file_path = inksy_config_directory_path()?;
+		window_geometry_file_path.push("window.kdl");
+
+		let window_geometry_file_data = std::fs::read_to_string(window_geometry_file_path).ok()?;
+
+		let window_geometry_document = window_geometry_file_data.parse::<KdlDocument>().ok()?;
+
+		Some(Self {
+			position: parse_kdl_integer_array(window_geometry_document.get_args("position"))?,
+			size: parse_kdl_integer_array(window_geometry_document.get_args("size"))?,
+			is_maximized: parse_kdl_bool(window_geometry_document.get_args("maximized"))?,
 		})
 	}
+
+	// Fits the window within the monitor containing its top-left corner, if there is one.
+	pub fn clamp_to_monitors(self, monitors: impl IntoIterator<Item = MonitorHandle>) -> Option<Self> {
+		let monitor = monitors.into_iter().find(|monitor| {
+			let (position, size) = (monitor.position(), monitor.size());
+			(position.x..position.x + size.width as i32).contains(&self.position[0]) && (position.y..position.y + size.height as i32).contains(&self.position[1])
+		})?;
+		let (monitor_position, monitor_size) = (monitor.position(), monitor.size());
+		let size = [self.size[0].min(monitor_size.width), self.size[1].min(monitor_size.height)];
+		let position = [
+			self.position[0].clamp(monitor_position.x, monitor_position.x + (monitor_size.width - size[0]) as i32),
+			self.position[1].clamp(monitor_position.y, monitor_position.y + (monitor_size.height - size[1]) as i32),
+		];
+		Some(Self { position, size, ..self })
+	}
+
+	pub fn save(&self) -> Option<()> {
+		let mut window_geometry_file_path = inksy_config_directory_path()?;
+		window_geometry_file_path.push("window.kdl");
+
+		let [x, y] = self.position;
+		let [width, height] = self.size;
+		let is_maximized = self.is_maximized;
+		std::fs::write(window_geometry_file_path, format!("position {x} {y}\nsize {width} {height}\nmaximized {is_maximized}\n")).ok()
+	}
+}
+
+fn inksy_config_directory_path() -> Option<PathBuf> {
+	let mut inksy_config_directory_path = dirs::config_dir()?;
+	inksy_config_directory_path.push("inksy");
+	if !inksy_config_directory_path.exists() {
+		std::fs::create_dir(inksy_config_directory_path.clone()).ok()?;
+	}
+	Some(inksy_config_directory_path)
+}
+
+// Stamps are kept as canvases in a directory beside the configuration.
+pub fn stamp_directory_path() -> Option<PathBuf> {
+	let mut stamp_directory_path = inksy_config_directory_path()?;
+	stamp_directory_path.push("stamps");
+	if !stamp_directory_path.exists() {
+		std::fs::create_dir(stamp_directory_path.clone()).ok()?;
+	}
+	Some(stamp_directory_path)
 }
 
 fn parse_kdl_f64<'a>(values: impl AsRef<[&'a KdlValue]>) -> Option<f64> {
@@ -62,6 +505,87 @@ fn parse_kdl_f64<'a>(values: impl AsRef<[&'a KdlValue]>) -> Option<f64> {
 	Some(n)
 }
 
+fn parse_kdl_f64_array<'a, const N: usize>(values: impl AsRef<[&'a KdlValue]>) -> Option<[f64; N]> {
+	<[_; N]>::try_from(values.as_ref()).ok()?.try_map(KdlValue::as_f64)
+}
+
+fn parse_kdl_bool<'a>(values: impl AsRef<[&'a KdlValue]>) -> Option<bool> {
+	let [b] = <[_; 1]>::try_from(values.as_ref()).ok()?.try_map(KdlValue::as_bool)?;
+	Some(b)
+}
+
+fn parse_kdl_string<'a>(values: impl AsRef<[&'a KdlValue]>) -> Option<&'a str> {
+	let [s] = <[_; 1]>::try_from(values.as_ref()).ok()?.try_map(KdlValue::as_string)?;
+	Some(s)
+}
+
+// Parses the name of a pen button's tool, where "none" leaves the button unbound.
+fn parse_kdl_pen_button_tool<'a>(values: impl AsRef<[&'a KdlValue]>) -> Option<Option<PenButtonTool>> {
+	match parse_kdl_string(values)? {
+		"none" => Some(None),
+		"erase" => Some(Some(PenButtonTool::Erase)),
+		"pan" => Some(Some(PenButtonTool::Pan)),
+		"zoom" => Some(Some(PenButtonTool::Zoom)),
+		"orbit" => Some(Some(PenButtonTool::Orbit)),
+		_ => None,
+	}
+}
+
+// Parses a named color scheme, whose selection color may be omitted.
+fn parse_kdl_color_scheme(node: &KdlNode) -> Option<ColorScheme> {
+	let name = node.get(0)?.value().as_string()?.to_owned();
+	let children = node.children()?;
+	Some(ColorScheme {
+		name,
+		background_color: parse_kdl_integer_array(children.get_args("background")).map(Srgb8)?,
+		stroke_color: parse_kdl_integer_array(children.get_args("stroke")).map(Srgb8)?,
+		selection_color: parse_kdl_integer_array(children.get_args("selection")).map(Srgb8).unwrap_or(DEFAULT_SELECTION_COLOR),
+	})
+}
+
+// Parses the colors of a palette, whose alphas may be omitted.
+fn parse_kdl_palette(document: &KdlDocument) -> Vec<Srgba8> {
+	document
+		.nodes()
+		.iter()
+		.filter(|node| node.name().value() == "color")
+		.filter_map(|node| {
+			let values = node.entries().iter().map(KdlEntry::value).collect::<Vec<_>>();
+			parse_kdl_integer_array::<u8, 4>(&values).or_else(|| parse_kdl_integer_array::<u8, 3>(&values).map(|[r, g, b]| [r, g, b, 0xff])).map(Srgba8)
+		})
+		.collect()
+}
+
 fn parse_kdl_integer_array<'a, T: TryFrom<i64>, const N: usize>(values: impl AsRef<[&'a KdlValue]>) -> Option<[T; N]> {
 	<[_; N]>::try_from(values.as_ref()).ok()?.try_map(KdlValue::as_i64)?.try_map(T::try_from).ok()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config(source: &str) -> Config {
+		let Ok(document) = source.parse::<KdlDocument>() else { panic!("the document failed to parse") };
+		Config::from_document(&document)
+	}
+
+	#[test]
+	fn settings_out_of_range_are_limited() {
+		let limited = config("radial-symmetry-segments 1000\npoint-density 100.0\nstroke-lift-timeout 5000.0");
+		assert_eq!(limited.radial_symmetry_segments, MAX_RADIAL_SYMMETRY_SEGMENTS);
+		assert_eq!(limited.point_density, 16.);
+		assert_eq!(limited.stroke_lift_timeout, Duration::from_secs(1));
+
+		// Too few segments fall back to the default rather than being raised.
+		assert_eq!(config("radial-symmetry-segments 1").radial_symmetry_segments, Config::default().radial_symmetry_segments);
+	}
+
+	#[test]
+	fn non_finite_settings_fall_back_to_defaults() {
+		// Literals too large for an f64 are read as infinite.
+		let config = config("point-density 1e400\nstroke-lift-timeout -1e400");
+		let default = Config::default();
+		assert_eq!(config.point_density, default.point_density);
+		assert_eq!(config.stroke_lift_timeout, default.stroke_lift_timeout);
+	}
+}
@@ -6,47 +6,138 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use std::{
-	fs::File,
-	io::{BufReader, BufWriter, Cursor, Read, Write},
+	collections::BTreeSet,
+	fmt,
+	fs::{self, File},
+	io::{self, BufWriter, Cursor, ErrorKind, Read, Write},
 	num::NonZero,
 	path::{Path, PathBuf},
 };
 
+use flate2::{write::ZlibEncoder, Compression};
+use pdf_writer::{Content, Filter, Name, Pdf, Rect, Ref};
+
 use crate::{
-	canvas::{Canvas, Image, Point, Stroke, View},
-	render::Renderer,
+	canvas::{aabb_of, replay_progress, Aabb, Canvas, Image, Page, Point, Stroke, View},
+	render::{texture::Texture, Renderer},
 	utility::{Srgb8, Srgba8, Tracked, Vex, Vx, Zoom},
 };
 
 const MAGIC_NUMBERS: [u8; 8] = [b'I', b'N', b'K', b'S', b'Y', 0, 0, 0];
 
-pub fn save_canvas_to_file(canvas: &Canvas, renderer: &Renderer, file_path: &Path) -> Option<()> {
+// Limits on what a file may contain, so that a corrupt file fails to open rather than exhausting memory.
+const MAX_OBJECT_COUNT: u64 = 1 << 24;
+const MAX_POINT_COUNT: u64 = 1 << 24;
+const MAX_TEXTURE_SIDE: u32 = 1 << 14;
+// No coordinate or factor comes near this, and keeping below it leaves room for arithmetic without overflowing.
+const MAX_FLOAT_MAGNITUDE: f32 = 1e9;
+// The range that the zoom of a loaded view is kept within.
+const MIN_LOADED_ZOOM: f32 = 1e-4;
+const MAX_LOADED_ZOOM: f32 = 1e4;
+
+pub enum SaveError {
+	Io(io::Error),
+	TextureReadback,
+	TextureEncode,
+	AnimationEncode,
+	// There is nothing on the canvas to export.
+	EmptyCanvas,
+	// The save failed, and the previous contents of the file could not be restored.
+	Rollback(io::Error),
+}
+
+impl From<io::Error> for SaveError {
+	fn from(error: io::Error) -> Self {
+		Self::Io(error)
+	}
+}
+
+impl fmt::Display for SaveError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Io(error) => write!(f, "Failed to save: {error}"),
+			Self::TextureReadback => write!(f, "Failed to save: could not read an image back from the GPU"),
+			Self::TextureEncode => write!(f, "Failed to save: could not encode an image"),
+			Self::AnimationEncode => write!(f, "Failed to save: could not encode the animation"),
+			Self::EmptyCanvas => write!(f, "Failed to save: the canvas is empty"),
+			Self::Rollback(error) => write!(f, "Failed to save, and the original file could not be restored: {error}"),
+		}
+	}
+}
+
+pub enum LoadError {
+	Io(io::Error),
+	BadMagicNumbers,
+	UnsupportedVersion(u64),
+	Truncated,
+	TextureDecode,
+	Malformed,
+	// A number in the file is not finite, or far too large.
+	OutOfRange,
+	// The file holds more objects, points, or pixels than can be opened.
+	TooLarge,
+}
+
+impl From<io::Error> for LoadError {
+	fn from(error: io::Error) -> Self {
+		match error.kind() {
+			ErrorKind::UnexpectedEof => Self::Truncated,
+			_ => Self::Io(error),
+		}
+	}
+}
+
+impl fmt::Display for LoadError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Io(error) => write!(f, "Failed to open: {error}"),
+			Self::BadMagicNumbers => write!(f, "Failed to open: not an Inksy file"),
+			Self::UnsupportedVersion(version) => write!(f, "Failed to open: unsupported file version {version}"),
+			Self::Truncated => write!(f, "Failed to open: the file is truncated"),
+			Self::TextureDecode => write!(f, "Failed to open: could not decode an image"),
+			Self::Malformed => write!(f, "Failed to open: the file is malformed"),
+			Self::OutOfRange => write!(f, "Failed to open: the file contains a number that is out of range"),
+			Self::TooLarge => write!(f, "Failed to open: the file is too large"),
+		}
+	}
+}
+
+pub fn save_canvas_to_file(canvas: &Canvas, renderer: &Renderer, file_path: &Path) -> Result<(), SaveError> {
 	let old_file = if file_path.exists() {
 		let mut buffer = Vec::new();
-		let mut file = File::open(file_path).ok()?;
-		file.read_to_end(&mut buffer).ok()?;
+		let mut file = File::open(file_path)?;
+		file.read_to_end(&mut buffer)?;
 		Some(buffer)
 	} else {
 		None
 	};
 
-	if save_canvas_to_file_inner(canvas, renderer, file_path).is_none() {
+	if let Err(error) = save_canvas_to_file_inner(canvas, renderer, file_path) {
 		if let Some(old_file) = old_file {
-			let mut file = File::create(file_path).ok()?;
-			// TODO: Return a descriptive error saying that we messed up. Badly.
-			file.write_all(&old_file).ok()?;
+			File::create(file_path).and_then(|mut file| file.write_all(&old_file)).map_err(SaveError::Rollback)?;
 		}
-		return None;
+		return Err(error);
 	}
 
-	Some(())
+	Ok(())
+}
+
+fn save_canvas_to_file_inner(canvas: &Canvas, renderer: &Renderer, file_path: &Path) -> Result<(), SaveError> {
+	// The canvas is serialized before the file is opened, so that failing to read back a texture leaves the file untouched.
+	let data = serialize_canvas(canvas, renderer)?;
+	Ok(File::create(file_path)?.write_all(&data)?)
 }
 
-fn save_canvas_to_file_inner(canvas: &Canvas, renderer: &Renderer, file_path: &Path) -> Option<()> {
-	let mut file = BufWriter::new(File::create(file_path).ok()?);
+pub fn serialize_canvas(canvas: &Canvas, renderer: &Renderer) -> Result<Vec<u8>, SaveError> {
+	let mut buffer = Vec::new();
+	write_canvas(canvas, |texture| renderer.read_texture(texture), &mut buffer)?;
+	Ok(buffer)
+}
 
-	file.write_all(&MAGIC_NUMBERS).ok()?;
-	file.write_all(&1u64.to_le_bytes()).ok()?;
+// Textures that haven't been encoded yet are read back with the given function, which only the renderer can provide.
+fn write_canvas(canvas: &Canvas, read_texture: impl Fn(&Texture) -> Option<Vec<u8>>, file: &mut impl Write) -> Result<(), SaveError> {
+	file.write_all(&MAGIC_NUMBERS)?;
+	file.write_all(&8u64.to_le_bytes())?;
 
 	let background_color: [u8; 3] = canvas.background_color.0;
 	let stroke_color: [u8; 3] = canvas.stroke_color.to_srgb().to_srgb8().0;
@@ -54,20 +145,26 @@ fn save_canvas_to_file_inner(canvas: &Canvas, renderer: &Renderer, file_path: &P
 	let position: [f32; 2] = [canvas.view.position[0].0, canvas.view.position[1].0];
 	let tilt: f32 = canvas.view.tilt;
 	let zoom: f32 = canvas.view.zoom.0;
-	let stroke_count: u64 = u64::try_from(canvas.strokes.len()).ok()?;
-	let image_count: u64 = u64::try_from(canvas.images.len()).ok()?;
-	let texture_count: u64 = u64::try_from(canvas.textures.len()).ok()?;
-
-	file.write_all(&background_color).ok()?;
-	file.write_all(&stroke_color).ok()?;
-	file.write_all(&stroke_radius.to_le_bytes()).ok()?;
-	file.write_all(&position[0].to_le_bytes()).ok()?;
-	file.write_all(&position[1].to_le_bytes()).ok()?;
-	file.write_all(&tilt.to_le_bytes()).ok()?;
-	file.write_all(&zoom.to_le_bytes()).ok()?;
-	file.write_all(&stroke_count.to_le_bytes()).ok()?;
-	file.write_all(&image_count.to_le_bytes()).ok()?;
-	file.write_all(&texture_count.to_le_bytes()).ok()?;
+	let stroke_count: u64 = canvas.strokes.len() as u64;
+	let image_count: u64 = canvas.images.len() as u64;
+	let texture_count: u64 = canvas.textures.len() as u64;
+
+	file.write_all(&background_color)?;
+	file.write_all(&stroke_color)?;
+	file.write_all(&stroke_radius.to_le_bytes())?;
+	file.write_all(&position[0].to_le_bytes())?;
+	file.write_all(&position[1].to_le_bytes())?;
+	file.write_all(&tilt.to_le_bytes())?;
+	file.write_all(&zoom.to_le_bytes())?;
+	file.write_all(&[canvas.page.is_some() as u8])?;
+	if let Some(page) = canvas.page {
+		for value in [page.position[0], page.position[1], page.dimensions[0], page.dimensions[1]] {
+			file.write_all(&value.0.to_le_bytes())?;
+		}
+	}
+	file.write_all(&stroke_count.to_le_bytes())?;
+	file.write_all(&image_count.to_le_bytes())?;
+	file.write_all(&texture_count.to_le_bytes())?;
 
 	for stroke in canvas.strokes.iter() {
 		let position: [f32; 2] = [stroke.position[0].0, stroke.position[1].0];
@@ -75,24 +172,30 @@ fn save_canvas_to_file_inner(canvas: &Canvas, renderer: &Renderer, file_path: &P
 		let dilation: f32 = stroke.dilation;
 		let color: [u8; 4] = stroke.color.0;
 		let stroke_radius: f32 = stroke.stroke_radius.0;
-		let point_count: u64 = u64::try_from(stroke.points.len()).ok()?;
+		let point_count: u64 = stroke.points.len() as u64;
 
-		file.write_all(&position[0].to_le_bytes()).ok()?;
-		file.write_all(&position[1].to_le_bytes()).ok()?;
-		file.write_all(&orientation.to_le_bytes()).ok()?;
-		file.write_all(&dilation.to_le_bytes()).ok()?;
-		file.write_all(&color).ok()?;
-		file.write_all(&stroke_radius.to_le_bytes()).ok()?;
-		file.write_all(&point_count.to_le_bytes()).ok()?;
+		file.write_all(&position[0].to_le_bytes())?;
+		file.write_all(&position[1].to_le_bytes())?;
+		file.write_all(&orientation.to_le_bytes())?;
+		file.write_all(&dilation.to_le_bytes())?;
+		file.write_all(&color)?;
+		file.write_all(&stroke_radius.to_le_bytes())?;
+		file.write_all(&point_count.to_le_bytes())?;
 
 		for point in stroke.points.iter() {
 			let position: [f32; 2] = [point.position[0].0, point.position[1].0];
 			let pressure: f32 = point.pressure;
+			let timestamp: u32 = point.timestamp;
 
-			file.write_all(&position[0].to_le_bytes()).ok()?;
-			file.write_all(&position[1].to_le_bytes()).ok()?;
-			file.write_all(&pressure.to_le_bytes()).ok()?;
+			file.write_all(&position[0].to_le_bytes())?;
+			file.write_all(&position[1].to_le_bytes())?;
+			file.write_all(&pressure.to_le_bytes())?;
+			file.write_all(&timestamp.to_le_bytes())?;
 		}
+
+		write_note(file, &stroke.note)?;
+		file.write_all(&[stroke.is_smooth as u8])?;
+		file.write_all(&[stroke.is_visible as u8])?;
 	}
 
 	let mut is_texture_referenced_array = vec![false; canvas.textures.len()];
@@ -102,223 +205,988 @@ fn save_canvas_to_file_inner(canvas: &Canvas, renderer: &Renderer, file_path: &P
 		let orientation: f32 = image.orientation;
 		let dilation: f32 = image.dilation;
 		is_texture_referenced_array[image.texture_index] = true;
-		let texture_index: u64 = u64::try_from(image.texture_index).ok()?;
+		let texture_index: u64 = image.texture_index as u64;
 		let dimensions: [f32; 2] = [image.dimensions[0].0, image.dimensions[1].0];
 
-		file.write_all(&position[0].to_le_bytes()).ok()?;
-		file.write_all(&position[1].to_le_bytes()).ok()?;
-		file.write_all(&orientation.to_le_bytes()).ok()?;
-		file.write_all(&dilation.to_le_bytes()).ok()?;
-		file.write_all(&texture_index.to_le_bytes()).ok()?;
-		file.write_all(&dimensions[0].to_le_bytes()).ok()?;
-		file.write_all(&dimensions[1].to_le_bytes()).ok()?;
-	}
+		file.write_all(&position[0].to_le_bytes())?;
+		file.write_all(&position[1].to_le_bytes())?;
+		file.write_all(&orientation.to_le_bytes())?;
+		file.write_all(&dilation.to_le_bytes())?;
+		file.write_all(&texture_index.to_le_bytes())?;
+		file.write_all(&dimensions[0].to_le_bytes())?;
+		file.write_all(&dimensions[1].to_le_bytes())?;
 
-	let mut data = vec![];
-	let mut compressed_data = vec![];
-	for ((texture_index, texture), is_texture_referenced) in canvas.textures.iter().enumerate().zip(is_texture_referenced_array) {
-		if is_texture_referenced {
-			compressed_data.clear();
-			data.reserve(texture.extent.width as usize * texture.extent.height as usize * 4);
-			data.clear();
-
-			// Set up the encoder.
-			let mut encoder = png::Encoder::new(&mut compressed_data, texture.extent.width, texture.extent.height);
-			encoder.set_color(png::ColorType::Rgba);
-			encoder.set_depth(png::BitDepth::Eight);
-			let mut writer = encoder.write_header().unwrap();
-
-			// Fetch and map texture from device.
-			let (buffer, bytes_per_row) = renderer.fetch_texture(canvas.textures.get(texture_index)?)?;
-			let buffer_slice = buffer.slice(..);
-			let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
-			buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
-				tx.send(result).unwrap();
-			});
-			renderer.device.poll(wgpu::Maintain::Wait);
-			pollster::block_on(rx.receive()).unwrap().unwrap();
-
-			// Read the texture row-by-row (each an initial slice of a mapped chunk).
-			for chunk in buffer.slice(..).get_mapped_range().chunks(bytes_per_row) {
-				data.extend(&chunk[..texture.extent.width as usize * 4])
-			}
+		write_note(file, &image.note)?;
 
-			// Unmap the texture buffer.
-			buffer.unmap();
+		let border_width: f32 = image.border_width.0;
+		let border_color: [u8; 4] = image.border_color.0;
+
+		file.write_all(&border_width.to_le_bytes())?;
+		file.write_all(&border_color)?;
+		file.write_all(&[image.is_pixelated as u8])?;
+		file.write_all(&[image.is_visible as u8])?;
+	}
 
-			writer.write_image_data(&data).ok()?;
-			writer.finish().ok()?;
+	for (texture, is_texture_referenced) in canvas.textures.iter().zip(is_texture_referenced_array) {
+		if let (Some(texture), true) = (texture, is_texture_referenced) {
+			let compressed_data = match texture.encoded_png.get() {
+				Some(compressed_data) => compressed_data,
+				None => {
+					// Read the texture back from the device.
+					let data = read_texture(texture).ok_or(SaveError::TextureReadback)?;
+					let compressed_data = encode_texture([texture.extent.width, texture.extent.height], &data)?;
+
+					texture.encoded_png.get_or_init(|| compressed_data)
+				},
+			};
 
 			let texture_flag: u64 = compressed_data.len() as u64;
 
-			file.write_all(&texture_flag.to_le_bytes()).ok()?;
-			file.write_all(&compressed_data).ok()?;
+			file.write_all(&texture_flag.to_le_bytes())?;
+			file.write_all(compressed_data)?;
 		} else {
 			let texture_flag: u64 = 0;
 
-			file.write_all(&texture_flag.to_le_bytes()).ok()?;
+			file.write_all(&texture_flag.to_le_bytes())?;
+		}
+	}
+
+	Ok(())
+}
+
+// Canvas units are treated as pixels at 96 DPI, and a PDF point is 1/72 of an inch.
+const PDF_POINTS_PER_UNIT: f32 = 0.75;
+
+pub fn export_canvas_to_pdf(canvas: &Canvas, renderer: &Renderer, file_path: &Path) -> Result<(), SaveError> {
+	let data = write_pdf(canvas, renderer)?;
+	let mut file = BufWriter::new(File::create(file_path)?);
+	file.write_all(&data)?;
+	Ok(file.flush()?)
+}
+
+fn write_pdf(canvas: &Canvas, renderer: &Renderer) -> Result<Vec<u8>, SaveError> {
+	// Export the page if there is one, and otherwise everything on the canvas.
+	let (min, max) = match canvas.page {
+		Some(page) => (page.position, page.position + page.dimensions),
+		None => content_bounds(canvas).ok_or(SaveError::EmptyCanvas)?,
+	};
+	let [width, height] = [(max[0] - min[0]).0 * PDF_POINTS_PER_UNIT, (max[1] - min[1]).0 * PDF_POINTS_PER_UNIT];
+	// PDF coordinates have their origin at the bottom-left corner of the page, with the y-axis pointing upwards.
+	let to_pdf = |point: Vex<2, Vx>| [(point[0] - min[0]).0 * PDF_POINTS_PER_UNIT, (max[1] - point[1]).0 * PDF_POINTS_PER_UNIT];
+
+	let mut pdf = Pdf::new();
+	let mut next_id = Ref::new(1);
+	let catalog_id = next_id.bump();
+	let page_tree_id = next_id.bump();
+	let page_id = next_id.bump();
+	let content_id = next_id.bump();
+
+	let mut content = Content::new();
+	let mut opacities = BTreeSet::new();
+
+	// Clip everything to the page, and fill it with the background color.
+	content.rect(0., 0., width, height).clip_nonzero().end_path();
+	let [r, g, b] = canvas.background_color.0.map(|c| c as f32 / 255.);
+	content.set_fill_rgb(r, g, b).rect(0., 0., width, height).fill_nonzero();
+
+	// Images are drawn beneath strokes, as on screen.
+	let mut image_ids = vec![None; canvas.textures.len()];
+	for image in canvas.images.iter().filter(|image| image.is_visible) {
+		// Embed each referenced texture once, with its alpha channel as a soft mask.
+		let image_id = match image_ids[image.texture_index] {
+			Some(image_id) => image_id,
+			None => {
+				let texture = canvas.textures[image.texture_index].as_ref().ok_or(SaveError::TextureReadback)?;
+				let data = renderer.read_texture(texture).ok_or(SaveError::TextureReadback)?;
+				let colors = data.chunks_exact(4).flat_map(|pixel| &pixel[..3]).copied().collect::<Vec<u8>>();
+				let alphas = data.chunks_exact(4).map(|pixel| pixel[3]).collect::<Vec<u8>>();
+				let [texture_width, texture_height] = [texture.extent.width as i32, texture.extent.height as i32];
+
+				let image_id = next_id.bump();
+				let mask_id = next_id.bump();
+				let colors = deflate(&colors)?;
+				let mut xobject = pdf.image_xobject(image_id, &colors);
+				xobject.width(texture_width).height(texture_height).bits_per_component(8).s_mask(mask_id).filter(Filter::FlateDecode);
+				xobject.color_space().device_rgb();
+				drop(xobject);
+				let alphas = deflate(&alphas)?;
+				let mut xobject = pdf.image_xobject(mask_id, &alphas);
+				xobject.width(texture_width).height(texture_height).bits_per_component(8).filter(Filter::FlateDecode);
+				xobject.color_space().device_gray();
+				drop(xobject);
+
+				image_ids[image.texture_index] = Some(image_id);
+				image_id
+			},
+		};
+
+		let to_canvas = |local: Vex<2, Vx>| image.position + local.rotate(image.orientation) * image.dilation;
+		let semidimensions = image.dimensions * 0.5;
+
+		// The frame fills the ring between the framed and unframed bounds of the image.
+		if image.border_width > Vx(0.) {
+			content.save_state();
+			set_pdf_fill_color(&mut content, image.border_color, &mut opacities);
+			for semidimensions in [semidimensions + Vex([image.border_width; 2]), semidimensions] {
+				let [first, rest @ ..] = [[-1., -1.], [1., -1.], [1., 1.], [-1., 1.]].map(|[x, y]| to_pdf(to_canvas(Vex([semidimensions[0] * x, semidimensions[1] * y]))));
+				content.move_to(first[0], first[1]);
+				for corner in rest {
+					content.line_to(corner[0], corner[1]);
+				}
+				content.close_path();
+			}
+			content.fill_even_odd();
+			content.restore_state();
+		}
+
+		// Map the unit square of the image onto its corners, with the first row of the texture at the top.
+		let origin = to_pdf(to_canvas(Vex([-semidimensions[0], semidimensions[1]])));
+		let right = to_pdf(to_canvas(semidimensions));
+		let top = to_pdf(to_canvas(-semidimensions));
+		content.save_state();
+		content.transform([right[0] - origin[0], right[1] - origin[1], top[0] - origin[0], top[1] - origin[1], origin[0], origin[1]]);
+		content.x_object(Name(format!("I{}", image_id.get()).as_bytes()));
+		content.restore_state();
+	}
+
+	for stroke in canvas.strokes.iter() {
+		if !stroke.is_visible || stroke.relative_indices.is_empty() {
+			continue;
+		}
+
+		let vertices = stroke.vertices.iter().map(|(vertex, _)| to_pdf(stroke.position + vertex.rotate(stroke.orientation) * stroke.dilation)).collect::<Vec<_>>();
+
+		content.save_state();
+		set_pdf_fill_color(&mut content, stroke.color, &mut opacities);
+		for triangle in stroke.body_indices().chunks_exact(3) {
+			let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| vertices[index as usize]);
+			// Wind every triangle the same way, so that overlapping triangles don't cancel out when filled.
+			let [b, c] = if (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]) < 0. { [c, b] } else { [b, c] };
+			content.move_to(a[0], a[1]).line_to(b[0], b[1]).line_to(c[0], c[1]).close_path();
+		}
+		content.fill_nonzero();
+		content.restore_state();
+	}
+
+	let content = deflate(&content.finish())?;
+	pdf.stream(content_id, &content).filter(Filter::FlateDecode);
+
+	// Translucent fills each need a graphics state with their opacity.
+	let opacity_ids = opacities.into_iter().map(|opacity| (opacity, next_id.bump())).collect::<Vec<_>>();
+	for &(opacity, opacity_id) in opacity_ids.iter() {
+		pdf.ext_graphics(opacity_id).non_stroking_alpha(opacity as f32 / 255.);
+	}
+
+	pdf.catalog(catalog_id).pages(page_tree_id);
+	pdf.pages(page_tree_id).kids([page_id]).count(1);
+	let mut page = pdf.page(page_id);
+	page.parent(page_tree_id).media_box(Rect::new(0., 0., width, height)).contents(content_id);
+	let mut resources = page.resources();
+	let mut xobjects = resources.x_objects();
+	for image_id in image_ids.into_iter().flatten() {
+		xobjects.pair(Name(format!("I{}", image_id.get()).as_bytes()), image_id);
+	}
+	drop(xobjects);
+	let mut ext_graphics_states = resources.ext_g_states();
+	for (opacity, opacity_id) in opacity_ids {
+		ext_graphics_states.pair(Name(format!("A{opacity}").as_bytes()), opacity_id);
+	}
+	drop(ext_graphics_states);
+	drop(resources);
+	drop(page);
+
+	Ok(pdf.finish())
+}
+
+// How a replay of the canvas is exported as an animation.
+#[derive(Clone, Copy)]
+pub struct ReplayExportSettings {
+	// The length, in pixels, of the longer side of the animation.
+	pub size: u32,
+	// How many times faster than it was drawn the canvas is redrawn.
+	pub speed: f32,
+	pub frame_rate: f32,
+}
+
+// The last frame of an exported replay is held for a while before it loops.
+const REPLAY_EXPORT_FINAL_DELAY: u16 = 200;
+
+// Exports the replay of the page, or of everything if there is no page, as an animated GIF.
+// Frames are drawn on the CPU, without anti-aliasing, from the same triangles as the PDF export; images are shown throughout, as in a replay.
+pub fn export_replay_to_gif(canvas: &Canvas, renderer: &Renderer, file_path: &Path, settings: ReplayExportSettings) -> Result<(), SaveError> {
+	let (min, max) = match canvas.page {
+		Some(page) => (page.position, page.position + page.dimensions),
+		None => content_bounds(canvas).ok_or(SaveError::EmptyCanvas)?,
+	};
+	let canvas_dimensions = [max[0] - min[0], max[1] - min[1]].map(|x| x.0.max(1.));
+	let pixels_per_unit = settings.size as f32 / canvas_dimensions[0].max(canvas_dimensions[1]);
+	let [width, height] = canvas_dimensions.map(|x| ((x * pixels_per_unit).round() as u16).max(1));
+	let to_pixel = |point: Vex<2, Vx>| [(point[0] - min[0]).0 * pixels_per_unit, (point[1] - min[1]).0 * pixels_per_unit];
+	let to_canvas = |[x, y]: [f32; 2]| min + Vex([x, y].map(|x| Vx(x / pixels_per_unit)));
+
+	// Fill the background and draw the images once, since they stay put while the strokes are redrawn.
+	let mut background = Raster::new(width as usize, height as usize, canvas.background_color);
+	for image in canvas.images.iter().filter(|image| image.is_visible) {
+		let texture = canvas.textures[image.texture_index].as_ref().ok_or(SaveError::TextureReadback)?;
+		let data = renderer.read_texture(texture).ok_or(SaveError::TextureReadback)?;
+		background.draw_image(image, &data, [texture.extent.width as usize, texture.extent.height as usize], to_pixel, to_canvas);
+	}
+
+	let mut file = BufWriter::new(File::create(file_path)?);
+	let mut encoder = gif::Encoder::new(&mut file, width, height, &[]).map_err(|_| SaveError::AnimationEncode)?;
+	encoder.set_repeat(gif::Repeat::Infinite).map_err(|_| SaveError::AnimationEncode)?;
+
+	// GIF delays are in hundredths of a second, and frames that would look the same as the last are merged into it.
+	let frame_delay = (100. / settings.frame_rate).round().max(1.) as u16;
+	let mut drawn = background.clone();
+	let mut drawn_stroke_count = 0;
+	let mut pending_frame: Option<(gif::Frame, (usize, usize))> = None;
+	for frame_index in 0u32.. {
+		let elapsed = (frame_index as f32 * frame_delay as f32 * 10. * settings.speed).min(u32::MAX as f32) as u32;
+		let progress = replay_progress(&canvas.strokes, elapsed);
+		let is_over = progress.is_none();
+		let (complete_stroke_count, partial_stroke) = progress.unwrap_or((canvas.strokes.len(), None));
+		let progress_key = (complete_stroke_count, partial_stroke.as_ref().map_or(0, |stroke| stroke.points.len()));
+
+		if let Some((frame, _)) = pending_frame.as_mut().filter(|(_, key)| *key == progress_key) {
+			frame.delay = frame.delay.saturating_add(frame_delay);
+		} else {
+			if let Some((frame, _)) = pending_frame.take() {
+				encoder.write_frame(&frame).map_err(|_| SaveError::AnimationEncode)?;
+			}
+			for stroke in canvas.strokes.iter().take(complete_stroke_count).skip(drawn_stroke_count).filter(|stroke| stroke.is_visible) {
+				drawn.draw_stroke(stroke, to_pixel);
+			}
+			drawn_stroke_count = drawn_stroke_count.max(complete_stroke_count);
+			let mut frame_raster = drawn.clone();
+			if let Some(stroke) = &partial_stroke {
+				frame_raster.draw_stroke(stroke, to_pixel);
+			}
+			let mut frame = gif::Frame::from_rgb_speed(width, height, &frame_raster.pixels, 10);
+			frame.delay = frame_delay;
+			pending_frame = Some((frame, progress_key));
+		}
+
+		if is_over {
+			break;
+		}
+	}
+	if let Some((mut frame, _)) = pending_frame {
+		frame.delay = frame.delay.max(REPLAY_EXPORT_FINAL_DELAY);
+		encoder.write_frame(&frame).map_err(|_| SaveError::AnimationEncode)?;
+	}
+
+	drop(encoder);
+	Ok(file.flush()?)
+}
+
+// An RGB image that strokes and images can be drawn into, for exporting frames.
+#[derive(Clone)]
+struct Raster {
+	width: usize,
+	height: usize,
+	pixels: Vec<u8>,
+}
+
+impl Raster {
+	fn new(width: usize, height: usize, color: Srgb8) -> Self {
+		Self {
+			width,
+			height,
+			pixels: color.0.repeat(width * height),
+		}
+	}
+
+	fn blend(&mut self, x: usize, y: usize, [r, g, b, a]: [u8; 4]) {
+		let index = (y * self.width + x) * 3;
+		let alpha = a as f32 / 255.;
+		for (channel, color) in self.pixels[index..index + 3].iter_mut().zip([r, g, b]) {
+			*channel = (*channel as f32 + (color as f32 - *channel as f32) * alpha).round() as u8;
+		}
+	}
+
+	// The range of pixels whose centers may lie within some bounds.
+	fn pixel_range(&self, points: impl IntoIterator<Item = [f32; 2]>) -> Option<[std::ops::Range<usize>; 2]> {
+		let (min, max) = points.into_iter().fold(([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]), |(min, max), [x, y]| ([min[0].min(x), min[1].min(y)], [max[0].max(x), max[1].max(y)]));
+		let ranges = [0, 1].map(|i| {
+			let extent = [self.width, self.height][i];
+			(min[i] - 0.5).ceil().clamp(0., extent as f32) as usize..((max[i] - 0.5).floor() + 1.).clamp(0., extent as f32) as usize
+		});
+		ranges.iter().all(|range| !range.is_empty()).then_some(ranges)
+	}
+
+	// Covers the triangles of a stroke, then blends its color once into each covered pixel, so that overlapping triangles don't darken translucent strokes.
+	fn draw_stroke(&mut self, stroke: &Stroke, to_pixel: impl Fn(Vex<2, Vx>) -> [f32; 2]) {
+		let vertices = stroke.vertices.iter().map(|(vertex, _)| to_pixel(stroke.position + vertex.rotate(stroke.orientation) * stroke.dilation)).collect::<Vec<_>>();
+		let Some([x_range, y_range]) = self.pixel_range(vertices.iter().copied()) else { return };
+		let mut is_covered = vec![false; x_range.len() * y_range.len()];
+
+		for triangle in stroke.body_indices().chunks_exact(3) {
+			let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| vertices[index as usize]);
+			let edge = |p: [f32; 2], q: [f32; 2], r: [f32; 2]| (q[0] - p[0]) * (r[1] - p[1]) - (q[1] - p[1]) * (r[0] - p[0]);
+			let area = edge(a, b, c);
+			if area == 0. {
+				continue;
+			}
+			let Some([triangle_x_range, triangle_y_range]) = self.pixel_range([a, b, c]) else { continue };
+			for y in triangle_y_range {
+				for x in triangle_x_range.clone() {
+					let center = [x as f32 + 0.5, y as f32 + 0.5];
+					// The center is inside if it is on the same side of every edge as the triangle is wound.
+					if [edge(a, b, center), edge(b, c, center), edge(c, a, center)].into_iter().all(|side| side * area >= 0.) {
+						is_covered[(y - y_range.start) * x_range.len() + (x - x_range.start)] = true;
+					}
+				}
+			}
+		}
+
+		for y in y_range.clone() {
+			for x in x_range.clone() {
+				if is_covered[(y - y_range.start) * x_range.len() + (x - x_range.start)] {
+					self.blend(x, y, stroke.color.0);
+				}
+			}
+		}
+	}
+
+	// Samples the nearest texel of an image, or its frame, for each pixel it covers.
+	fn draw_image(&mut self, image: &Image, data: &[u8], [texture_width, texture_height]: [usize; 2], to_pixel: impl Fn(Vex<2, Vx>) -> [f32; 2], to_canvas: impl Fn([f32; 2]) -> Vex<2, Vx>) {
+		let (min, max) = image.world_aabb();
+		let Some([x_range, y_range]) = self.pixel_range([to_pixel(min), to_pixel(max)]) else { return };
+		let semidimensions = image.dimensions * 0.5;
+
+		for y in y_range {
+			for x in x_range.clone() {
+				let local = (to_canvas([x as f32 + 0.5, y as f32 + 0.5]) - image.position).rotate(-image.orientation) / image.dilation;
+				let [u, v] = [0, 1].map(|i| local[i].0 / image.dimensions[i].0 + 0.5);
+				if (0. ..1.).contains(&u) && (0. ..1.).contains(&v) {
+					let index = ((v * texture_height as f32) as usize * texture_width + (u * texture_width as f32) as usize) * 4;
+					if let Some(texel) = data.get(index..index + 4) {
+						self.blend(x, y, [texel[0], texel[1], texel[2], texel[3]]);
+					}
+				} else if [0, 1].into_iter().all(|i| local[i].0.abs() <= (semidimensions[i] + image.border_width).0) {
+					self.blend(x, y, image.border_color.0);
+				}
+			}
 		}
 	}
+}
+
+// Computes the bounds of the strokes and framed images on the canvas, if there are any.
+fn content_bounds(canvas: &Canvas) -> Option<Aabb> {
+	let stroke_bounds = canvas.strokes.iter().filter(|stroke| stroke.is_visible && !stroke.vertices.is_empty()).map(|stroke| stroke.world_aabb());
+	let image_bounds = canvas.images.iter().filter(|image| image.is_visible).map(|image| image.world_aabb());
 
-	Some(())
+	aabb_of(stroke_bounds.chain(image_bounds).flat_map(|(min, max)| [min, max]))
 }
 
-pub fn load_canvas_from_file(renderer: &mut Renderer, file_path: PathBuf) -> Option<Canvas> {
-	let mut file = BufReader::new(File::open(file_path.clone()).ok()?);
+// Writes the filled outline of a stroke as the `d` attribute of an SVG path in canvas coordinates.
+// The outline runs along one side of the stroke and back along the other, following its pressure and smoothing, with a round cap at each end as on the canvas.
+// Where the stroke crosses itself, so does its outline, which is wound the same way throughout, so it must be filled with the nonzero rule.
+pub fn stroke_to_svg_path(stroke: &Stroke) -> String {
+	let vertices = stroke.vertices.iter().map(|(vertex, _)| stroke.position + vertex.rotate(stroke.orientation) * stroke.dilation).collect::<Vec<_>>();
+	let point = |point: Vex<2, Vx>| format!("{} {}", svg_number(point[0]), svg_number(point[1]));
+
+	// A lone point is drawn as a circle through the first vertex around its center.
+	if let ([_], [center, start, ..]) = (stroke.points.as_slice(), vertices.as_slice()) {
+		let radius = svg_number((*start - *center).norm());
+		let opposite = *center * 2. - *start;
+		return format!("M{}A{radius} {radius} 0 1 1 {}A{radius} {radius} 0 1 1 {}Z", point(*start), point(opposite), point(*start));
+	}
+
+	// Each segment has a vertex on either side of each of its ends, and the caps follow the segments.
+	let segment_count = vertices.len().saturating_sub(8) / 4;
+	if segment_count == 0 {
+		return String::new();
+	}
+	let segments = vertices.chunks_exact(4).take(segment_count).collect::<Vec<_>>();
+
+	// A half circle from one side of an end of the stroke to the other, bulging forward.
+	let cap = |from: Vex<2, Vx>, to: Vex<2, Vx>, forward: Vex<2, Vx>| {
+		let radius = svg_number((to - from).norm() * 0.5);
+		let offset = from - (from + to) * 0.5;
+		let sweep = ((offset[0] * forward[1]) - (offset[1] * forward[0])).0 > 0.;
+		format!("A{radius} {radius} 0 0 {} {}", sweep as u8, point(to))
+	};
+	let midpoint = |[a, b]: [Vex<2, Vx>; 2]| (a + b) * 0.5;
+
+	// Where segments meet without turning, the end of one side of a segment is the start of the next, so it is written once.
+	let mut left = segments.iter().flat_map(|segment| [segment[0], segment[2]]).map(point).collect::<Vec<_>>();
+	let mut right = segments.iter().rev().flat_map(|segment| [segment[3], segment[1]]).map(point).collect::<Vec<_>>();
+	left.dedup();
+	right.dedup();
+
+	let [first, last] = [segments[0], segments[segment_count - 1]];
+	let mut path = format!("M{}", left[0]);
+	left[1..].iter().for_each(|point| path.push_str(&format!("L{point}")));
+	path.push_str(&cap(last[2], last[3], midpoint([last[2], last[3]]) - midpoint([last[0], last[1]])));
+	right[1..].iter().for_each(|point| path.push_str(&format!("L{point}")));
+	path.push_str(&cap(first[1], first[0], midpoint([first[0], first[1]]) - midpoint([first[2], first[3]])));
+	path.push('Z');
+	path
+}
 
+// Writes standalone SVG markup for strokes, framed by their bounds, or nothing if none of them have any geometry.
+pub fn strokes_to_svg<'a>(strokes: impl IntoIterator<Item = &'a Stroke>) -> Option<String> {
+	let strokes = strokes.into_iter().filter(|stroke| !stroke.relative_indices.is_empty()).collect::<Vec<_>>();
+	let (min, max) = aabb_of(strokes.iter().flat_map(|stroke| {
+		let (min, max) = stroke.world_aabb();
+		[min, max]
+	}))?;
+	let [width, height] = [max[0] - min[0], max[1] - min[1]].map(svg_number);
+
+	let mut svg = format!(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {width} {height}" width="{width}" height="{height}">"#, svg_number(min[0]), svg_number(min[1]));
+	for stroke in strokes {
+		let [r, g, b, a] = stroke.color.0;
+		let opacity = if a < u8::MAX { format!(r#" fill-opacity="{}""#, svg_number(Vx(a as f32 / 255.))) } else { String::new() };
+		svg.push_str(&format!(r##"<path d="{}" fill="#{r:02x}{g:02x}{b:02x}"{opacity}/>"##, stroke_to_svg_path(stroke)));
+	}
+	svg.push_str("</svg>");
+	Some(svg)
+}
+
+// Formats a coordinate to a hundredth of a unit, without trailing zeros.
+fn svg_number(x: Vx) -> String {
+	let text = format!("{:.2}", x.0);
+	let text = text.trim_end_matches('0').trim_end_matches('.');
+	if text == "-0" {
+		"0".to_owned()
+	} else {
+		text.to_owned()
+	}
+}
+
+// Sets the fill color of a PDF content stream, recording any translucency so that its graphics state can be written.
+fn set_pdf_fill_color(content: &mut Content, color: Srgba8, opacities: &mut BTreeSet<u8>) {
+	let [r, g, b, a] = color.0;
+	content.set_fill_rgb(r as f32 / 255., g as f32 / 255., b as f32 / 255.);
+	if a < u8::MAX {
+		opacities.insert(a);
+		content.set_parameters(Name(format!("A{a}").as_bytes()));
+	}
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>, SaveError> {
+	let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+	encoder.write_all(data)?;
+	Ok(encoder.finish()?)
+}
+
+pub fn load_canvas_from_file(renderer: &mut Renderer, file_path: PathBuf) -> Result<Canvas, LoadError> {
+	let mut canvas = deserialize_canvas(renderer, &fs::read(&file_path)?)?;
+	canvas.file_path = Some(file_path).into();
+	Ok(canvas)
+}
+
+pub fn deserialize_canvas(renderer: &mut Renderer, bytes: &[u8]) -> Result<Canvas, LoadError> {
+	read_canvas(&mut Cursor::new(bytes), |dimensions, data| renderer.create_texture(dimensions, data))
+}
+
+// Textures are created from their decoded pixels with the given function, which only the renderer can provide.
+fn read_canvas(file: &mut impl Read, mut create_texture: impl FnMut([NonZero<u32>; 2], Vec<u8>) -> Texture) -> Result<Canvas, LoadError> {
 	let mut magic_numbers = [0; 8];
-	file.read_exact(&mut magic_numbers).ok()?;
+	file.read_exact(&mut magic_numbers)?;
 	if magic_numbers != MAGIC_NUMBERS {
-		return None;
+		return Err(LoadError::BadMagicNumbers);
 	}
 
-	let [discriminator] = read_u64s(&mut file)?;
-	if !(discriminator == 0 || discriminator == 1) {
-		return None;
+	let [discriminator] = read_u64s(file)?;
+	if !(0..=8).contains(&discriminator) {
+		return Err(LoadError::UnsupportedVersion(discriminator));
 	}
 
-	let background_color = read_u8s::<3>(&mut file)?;
-	let stroke_color = read_u8s::<3>(&mut file)?;
-	let [stroke_radius] = read_f32s::<1>(&mut file)?;
-	let position = read_f32s::<2>(&mut file)?;
-	let [tilt, zoom] = read_f32s(&mut file)?;
-	let [stroke_count, image_count, texture_count] = read_u64s(&mut file)?;
+	let background_color = read_u8s::<3>(file)?;
+	let stroke_color = read_u8s::<3>(file)?;
+	let [stroke_radius] = read_f32s::<1>(file)?;
+	let position = read_f32s::<2>(file)?;
+	let [tilt, zoom] = read_f32s(file)?;
+	let page = if discriminator >= 5 && read_u8s::<1>(file)? == [1] {
+		let [x, y, width, height] = read_f32s(file)?;
+		Some(Page {
+			position: Vex([Vx(x), Vx(y)]),
+			dimensions: Vex([Vx(width), Vx(height)]),
+		})
+	} else {
+		None
+	};
+	let [stroke_count, image_count, texture_count] = read_u64s(file)?;
+	if [stroke_count, image_count, texture_count].into_iter().any(|count| count > MAX_OBJECT_COUNT) {
+		return Err(LoadError::TooLarge);
+	}
 
 	let mut strokes = Vec::with_capacity((stroke_count as usize).min(2048));
 	for _ in 0..stroke_count {
-		let position = read_f32s::<2>(&mut file)?;
-		let [orientation, dilation] = read_f32s(&mut file)?;
-		let color = read_u8s::<4>(&mut file)?;
-		let [stroke_radius] = read_f32s(&mut file)?;
-		let [point_count] = read_u64s(&mut file)?;
+		let position = read_f32s::<2>(file)?;
+		let [orientation, dilation] = read_f32s(file)?;
+		let color = read_u8s::<4>(file)?;
+		let [stroke_radius] = read_f32s(file)?;
+		let [point_count] = read_u64s(file)?;
+		if point_count > MAX_POINT_COUNT {
+			return Err(LoadError::TooLarge);
+		}
+		if dilation <= 0. {
+			return Err(LoadError::Malformed);
+		}
 
 		let mut points = Vec::with_capacity((point_count as usize).min(2048));
 		for _ in 0..point_count {
-			let position = read_f32s::<2>(&mut file)?;
-			let [pressure] = read_f32s(&mut file)?;
+			let position = read_f32s::<2>(file)?;
+			let [pressure] = read_f32s(file)?;
+			let [timestamp] = if discriminator >= 4 { read_u32s(file)? } else { [0] };
 
-			points.push(Point { position: Vex(position.map(Vx)), pressure })
+			points.push(Point {
+				position: Vex(position.map(Vx)),
+				pressure: pressure.clamp(0., 1.),
+				timestamp,
+			})
 		}
 
-		strokes.push(Stroke::new(Srgba8(color), Vx(stroke_radius), points, Vex(position.map(Vx)), orientation, dilation).into());
+		let note = if discriminator >= 2 { read_note(file)? } else { None };
+		let is_smooth = discriminator >= 7 && read_u8s::<1>(file)? == [1];
+		let is_visible = discriminator < 8 || read_u8s::<1>(file)? == [1];
+
+		strokes.push(
+			Stroke {
+				is_visible,
+				note,
+				..Stroke::new(Srgba8(color), Vx(stroke_radius.max(0.)), points, is_smooth, Vex(position.map(Vx)), orientation, dilation)
+			}
+			.into(),
+		);
 	}
 
 	let mut images = Vec::with_capacity((image_count as usize).min(128));
 	for _ in 0..image_count {
-		let position = read_f32s::<2>(&mut file)?;
-		let [orientation, dilation] = read_f32s(&mut file)?;
-		let [texture_index] = read_u64s(&mut file)?;
-		let dimensions = read_f32s::<2>(&mut file)?;
+		let position = read_f32s::<2>(file)?;
+		let [orientation, dilation] = read_f32s(file)?;
+		let [texture_index] = read_u64s(file)?;
+		let dimensions = read_f32s::<2>(file)?;
+		let note = if discriminator >= 2 { read_note(file)? } else { None };
+		let (border_width, border_color) = if discriminator >= 3 { (read_f32s::<1>(file)?[0], read_u8s::<4>(file)?) } else { (0., [0; 4]) };
+		let is_pixelated = discriminator >= 6 && read_u8s::<1>(file)? == [1];
+		let is_visible = discriminator < 8 || read_u8s::<1>(file)? == [1];
+		if dilation <= 0. || dimensions.into_iter().any(|x| x < 0.) {
+			return Err(LoadError::Malformed);
+		}
 
 		images.push(
 			Image {
-				texture_index: usize::try_from(texture_index).ok()?,
+				texture_index: usize::try_from(texture_index).map_err(|_| LoadError::Malformed)?,
 				dimensions: Vex(dimensions.map(Vx)),
 				position: Vex(position.map(Vx)),
 				orientation,
 				dilation,
 				is_selected: false,
+				is_visible,
+				note,
+				border_width: Vx(border_width.max(0.)),
+				border_color: Srgba8(border_color),
+				is_pixelated,
+				world_aabb_cache: Default::default(),
 			}
 			.into(),
 		);
 	}
 
-	let mut revised_texture_index_array = Vec::with_capacity(texture_count as usize);
-	let mut revised_texture_index = 0;
+	// Empty texture slots are dropped, so the slots that held a texture are renumbered, and the rest are marked as absent.
+	let mut revised_texture_index_array = Vec::with_capacity((texture_count as usize).min(128));
 	let mut textures = Vec::with_capacity((texture_count as usize).min(128));
 	let mut compressed_data = vec![];
 	for _ in 0..texture_count {
-		revised_texture_index_array.push(revised_texture_index);
+		let revised_texture_index = textures.len();
 		match discriminator {
 			0 => {
-				let [width, height] = read_u32s(&mut file)?;
+				let [width, height] = read_u32s(file)?;
+				if width.max(height) > MAX_TEXTURE_SIDE {
+					return Err(LoadError::TooLarge);
+				}
 				// If either dimension are zero, no texture was saved.
 				if let [Ok(width), Ok(height)] = [width, height].map(NonZero::try_from) {
-					let mut buffer = vec![0; width.get() as usize * 4 * height.get() as usize];
-					file.read_exact(&mut buffer).ok()?;
-					textures.push(renderer.create_texture([width, height], buffer));
-					revised_texture_index += 1;
+					// As with compressed textures, the pixels are read as they arrive, so a truncated file can't claim an absurd allocation.
+					let length = u64::from(width.get()) * u64::from(height.get()) * 4;
+					let mut buffer = vec![];
+					file.take(length).read_to_end(&mut buffer)?;
+					if buffer.len() as u64 != length {
+						return Err(LoadError::Truncated);
+					}
+					textures.push(create_texture([width, height], buffer));
 				}
 			},
-			1 => {
-				let [texture_flag] = read_u64s(&mut file)?;
+			1..=8 => {
+				let [texture_flag] = read_u64s(file)?;
 				match texture_flag {
 					0 => {},
 					i => {
+						// The data is read as it arrives, rather than into a buffer of the claimed length, which may be absurd.
 						compressed_data.clear();
-						compressed_data.resize(i as usize, 0);
-						file.read_exact(&mut compressed_data).ok()?;
-
-						let png_decoder = png::Decoder::new(Cursor::new(&compressed_data));
-						let mut png_reader = png_decoder.read_info().ok()?;
-						let mut buffer = vec![0; png_reader.output_buffer_size()];
-						let width = png_reader.info().width;
-						let height = png_reader.info().height;
-						png_reader.next_frame(&mut buffer).ok()?;
-						png_reader.finish().ok()?;
-
-						textures.push(renderer.create_texture([NonZero::new(width)?, NonZero::new(height)?], buffer));
-						revised_texture_index += 1;
+						file.take(i).read_to_end(&mut compressed_data)?;
+						if compressed_data.len() as u64 != i {
+							return Err(LoadError::Truncated);
+						}
+
+						let (dimensions, buffer) = decode_texture(&compressed_data)?;
+						let texture = create_texture(dimensions, buffer);
+						// Reuse the stored encoding when saving, rather than encoding the texture again.
+						let _ = texture.encoded_png.set(compressed_data.clone());
+						textures.push(texture);
 					},
 				}
 			},
-			_ => return None,
+			_ => return Err(LoadError::UnsupportedVersion(discriminator)),
 		}
+		revised_texture_index_array.push((textures.len() > revised_texture_index).then_some(revised_texture_index));
 	}
 
-	// Rebase the image texture indices.
+	// Rebase the image texture indices; an image can't refer to a slot that is missing or empty.
 	for image in images.iter_mut().map(Tracked::as_mut) {
-		image.texture_index = revised_texture_index_array[image.texture_index];
+		image.texture_index = revised_texture_index_array.get(image.texture_index).copied().flatten().ok_or(LoadError::Malformed)?;
 	}
 
-	Some(Canvas::from_file(
-		file_path,
+	Ok(Canvas::from_file(
+		None,
 		Srgb8(background_color),
 		Srgb8(stroke_color),
 		Vx(stroke_radius),
 		View {
 			position: Vex(position.map(Vx)),
 			tilt,
-			zoom: Zoom(zoom),
+			zoom: Zoom(zoom.clamp(MIN_LOADED_ZOOM, MAX_LOADED_ZOOM)),
 		},
+		page,
 		images,
 		strokes,
 		textures,
 	))
 }
 
-fn read_u64s<const N: usize>(file: &mut impl Read) -> Option<[u64; N]> {
+// Encodes the straight 8-bit RGBA of a texture as is, without premultiplying its colors.
+fn encode_texture([width, height]: [u32; 2], data: &[u8]) -> Result<Vec<u8>, SaveError> {
+	let mut compressed_data = vec![];
+	let mut encoder = png::Encoder::new(&mut compressed_data, width, height);
+	encoder.set_color(png::ColorType::Rgba);
+	encoder.set_depth(png::BitDepth::Eight);
+	let mut writer = encoder.write_header().map_err(|_| SaveError::TextureEncode)?;
+	writer.write_image_data(data).map_err(|_| SaveError::TextureEncode)?;
+	writer.finish().map_err(|_| SaveError::TextureEncode)?;
+	Ok(compressed_data)
+}
+
+// Decodes a stored or pasted PNG as 8-bit RGBA, whatever color type and bit depth it was encoded with.
+pub fn decode_texture(data: &[u8]) -> Result<([NonZero<u32>; 2], Vec<u8>), LoadError> {
+	let mut png_decoder = png::Decoder::new(Cursor::new(data));
+	png_decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16 | png::Transformations::ALPHA);
+	let mut png_reader = png_decoder.read_info().map_err(|_| LoadError::TextureDecode)?;
+	if png_reader.info().width.max(png_reader.info().height) > MAX_TEXTURE_SIDE {
+		return Err(LoadError::TooLarge);
+	}
+	let mut buffer = vec![0; png_reader.output_buffer_size()];
+	let width = png_reader.info().width;
+	let height = png_reader.info().height;
+	png_reader.next_frame(&mut buffer).map_err(|_| LoadError::TextureDecode)?;
+	png_reader.finish().map_err(|_| LoadError::TextureDecode)?;
+
+	let (Some(width), Some(height)) = (NonZero::new(width), NonZero::new(height)) else {
+		return Err(LoadError::TextureDecode);
+	};
+	buffer.truncate(width.get() as usize * height.get() as usize * png_reader.output_color_type().0.samples());
+
+	// Grayscale is only expanded to gray and alpha, so the gray is copied into each color channel.
+	let buffer = match png_reader.output_color_type() {
+		(png::ColorType::Rgba, png::BitDepth::Eight) => buffer,
+		(png::ColorType::GrayscaleAlpha, png::BitDepth::Eight) => buffer.chunks_exact(2).flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]]).collect(),
+		_ => return Err(LoadError::TextureDecode),
+	};
+
+	Ok(([width, height], buffer))
+}
+
+// Notes are stored as a length-prefixed UTF-8 string, where an empty string denotes no note.
+fn write_note(file: &mut impl Write, note: &Option<String>) -> Result<(), SaveError> {
+	let note = note.as_deref().unwrap_or_default();
+	file.write_all(&(note.len() as u64).to_le_bytes())?;
+	file.write_all(note.as_bytes())?;
+	Ok(())
+}
+
+fn read_note(file: &mut impl Read) -> Result<Option<String>, LoadError> {
+	let [length] = read_u64s(file)?;
+	if length == 0 {
+		return Ok(None);
+	}
+	let mut buffer = Vec::new();
+	file.take(length).read_to_end(&mut buffer)?;
+	if buffer.len() as u64 != length {
+		return Err(LoadError::Truncated);
+	}
+	String::from_utf8(buffer).map(Some).map_err(|_| LoadError::Malformed)
+}
+
+fn read_u64s<const N: usize>(file: &mut impl Read) -> Result<[u64; N], LoadError> {
 	let mut array = [0; N];
 	for element in &mut array {
 		let mut buffer = [0; 8];
-		file.read_exact(&mut buffer).ok()?;
+		file.read_exact(&mut buffer)?;
 		*element = u64::from_le_bytes(buffer);
 	}
-	Some(array)
+	Ok(array)
 }
 
-fn read_u32s<const N: usize>(file: &mut impl Read) -> Option<[u32; N]> {
+fn read_u32s<const N: usize>(file: &mut impl Read) -> Result<[u32; N], LoadError> {
 	let mut array = [0; N];
 	for element in &mut array {
 		let mut buffer = [0; 4];
-		file.read_exact(&mut buffer).ok()?;
+		file.read_exact(&mut buffer)?;
 		*element = u32::from_le_bytes(buffer);
 	}
-	Some(array)
+	Ok(array)
 }
 
-fn read_u8s<const N: usize>(file: &mut impl Read) -> Option<[u8; N]> {
+fn read_u8s<const N: usize>(file: &mut impl Read) -> Result<[u8; N], LoadError> {
 	let mut buffer = [0; N];
-	file.read_exact(&mut buffer).ok()?;
-	Some(buffer)
+	file.read_exact(&mut buffer)?;
+	Ok(buffer)
 }
 
-fn read_f32s<const N: usize>(file: &mut impl Read) -> Option<[f32; N]> {
+// Every float in a file is a coordinate or factor, so none may be infinite, not a number, or absurdly large.
+fn read_f32s<const N: usize>(file: &mut impl Read) -> Result<[f32; N], LoadError> {
 	let mut array = [0.; N];
 	for element in &mut array {
 		let mut buffer = [0; 4];
-		file.read_exact(&mut buffer).ok()?;
+		file.read_exact(&mut buffer)?;
 		*element = f32::from_le_bytes(buffer);
+		if !element.is_finite() || element.abs() > MAX_FLOAT_MAGNITUDE {
+			return Err(LoadError::OutOfRange);
+		}
+	}
+	Ok(array)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn test_canvas() -> Canvas {
+		let points = (0..5)
+			.map(|i| Point {
+				position: Vex([Vx(i as f32 * 3.), Vx(i as f32 * -2.)]),
+				pressure: i as f32 / 4.,
+				timestamp: i * 10,
+			})
+			.collect::<Vec<_>>();
+		let strokes = vec![
+			Stroke::new(Srgba8([10, 20, 30, 255]), Vx(2.), points.clone(), false, Vex([Vx(5.), Vx(-7.)]), 0.5, 1.5).into(),
+			Stroke {
+				note: Some("A note".to_owned()),
+				is_visible: false,
+				..Stroke::new(Srgba8([200, 100, 0, 128]), Vx(4.), points, true, Vex([Vx(-1.), Vx(2.)]), -1., 0.25)
+			}
+			.into(),
+		];
+		let view = View {
+			position: Vex([Vx(12.), Vx(-3.)]),
+			tilt: 0.75,
+			zoom: Zoom(2.),
+		};
+		let page = Page {
+			position: Vex([Vx(-10.), Vx(-20.)]),
+			dimensions: Vex([Vx(100.), Vx(200.)]),
+		};
+		Canvas::from_file(None, Srgb8([1, 2, 3]), Srgb8([4, 5, 6]), Vx(3.), view, Some(page), Vec::new(), strokes, Vec::new())
+	}
+
+	fn serialize(canvas: &Canvas) -> Vec<u8> {
+		let mut buffer = Vec::new();
+		assert!(write_canvas(canvas, |_| None, &mut buffer).is_ok());
+		buffer
+	}
+
+	// None of the canvases that are read here hold textures that decode, so none are ever created.
+	fn deserialize(bytes: &[u8]) -> Result<Canvas, LoadError> {
+		read_canvas(&mut Cursor::new(bytes), |_, _| unreachable!())
+	}
+
+	#[test]
+	fn canvases_round_trip_through_bytes() {
+		let canvas = test_canvas();
+		let Ok(loaded) = deserialize(&serialize(&canvas)) else { panic!("the canvas failed to load") };
+
+		assert_eq!(loaded.background_color.0, canvas.background_color.0);
+		assert_eq!(loaded.stroke_color.to_srgb().to_srgb8().0, canvas.stroke_color.to_srgb().to_srgb8().0);
+		assert_eq!(loaded.stroke_radius, canvas.stroke_radius);
+		assert_eq!(loaded.view.position, canvas.view.position);
+		assert_eq!(loaded.view.tilt, canvas.view.tilt);
+		assert_eq!(loaded.view.zoom.0, canvas.view.zoom.0);
+		assert_eq!(loaded.page.map(|page| (page.position, page.dimensions)), canvas.page.map(|page| (page.position, page.dimensions)));
+
+		assert_eq!(loaded.strokes.len(), canvas.strokes.len());
+		for (loaded, stroke) in loaded.strokes.iter().zip(canvas.strokes.iter()) {
+			assert_eq!(loaded.position, stroke.position);
+			assert_eq!(loaded.orientation, stroke.orientation);
+			assert_eq!(loaded.dilation, stroke.dilation);
+			assert_eq!(loaded.color.0, stroke.color.0);
+			assert_eq!(loaded.stroke_radius, stroke.stroke_radius);
+			assert_eq!(loaded.note, stroke.note);
+			assert_eq!(loaded.is_smooth, stroke.is_smooth);
+			assert_eq!(loaded.is_visible, stroke.is_visible);
+			assert_eq!(loaded.points.len(), stroke.points.len());
+			for (loaded, point) in loaded.points.iter().zip(&stroke.points) {
+				assert_eq!(loaded.position, point.position);
+				assert_eq!(loaded.pressure, point.pressure);
+				assert_eq!(loaded.timestamp, point.timestamp);
+			}
+		}
+
+		// Saving the loaded canvas again reproduces the same bytes.
+		assert_eq!(serialize(&loaded), serialize(&canvas));
+	}
+
+	fn stroke(positions: &[[f32; 2]], position: [f32; 2]) -> Stroke {
+		let points = positions
+			.iter()
+			.map(|&position| Point {
+				position: Vex(position.map(Vx)),
+				pressure: 1.,
+				timestamp: 0,
+			})
+			.collect();
+		Stroke::new(Srgba8([0, 0, 0, 255]), Vx(2.), points, false, Vex(position.map(Vx)), 0., 1.)
+	}
+
+	#[test]
+	fn strokes_are_written_as_outlines() {
+		assert_eq!(stroke_to_svg_path(&stroke(&[[0., 0.], [10., 0.]], [0., 0.])), "M0 -2L10 -2A2 2 0 0 1 10 2L0 2A2 2 0 0 1 0 -2Z");
+		// Where segments meet in a straight line, their shared corners are written once.
+		assert_eq!(stroke_to_svg_path(&stroke(&[[0., 0.], [10., 0.], [20., 0.]], [5., 5.])), "M5 3L15 3L25 3A2 2 0 0 1 25 7L15 7L5 7A2 2 0 0 1 5 3Z");
+		assert_eq!(stroke_to_svg_path(&stroke(&[[0., 0.]], [5., 5.])), "M7 5A2 2 0 1 1 3 5A2 2 0 1 1 7 5Z");
+	}
+
+	fn encode_png(dimensions: [u32; 2], color_type: png::ColorType, bit_depth: png::BitDepth, palette: Option<&[u8]>, data: &[u8]) -> Vec<u8> {
+		let mut encoded = Vec::new();
+		let mut encoder = png::Encoder::new(&mut encoded, dimensions[0], dimensions[1]);
+		encoder.set_color(color_type);
+		encoder.set_depth(bit_depth);
+		if let Some(palette) = palette {
+			encoder.set_palette(palette.to_vec());
+		}
+		let mut writer = encoder.write_header().unwrap();
+		writer.write_image_data(data).unwrap();
+		writer.finish().unwrap();
+		encoded
+	}
+
+	fn decode(data: &[u8]) -> Option<([u32; 2], Vec<u8>)> {
+		decode_texture(data).ok().map(|(dimensions, buffer)| (dimensions.map(NonZero::get), buffer))
+	}
+
+	#[test]
+	fn textures_decode_as_rgba8() {
+		use png::{BitDepth, ColorType};
+
+		let rgba = [255, 0, 0, 255, 0, 255, 0, 128];
+		assert_eq!(decode(&encode_png([2, 1], ColorType::Rgba, BitDepth::Eight, None, &rgba)), Some(([2, 1], rgba.to_vec())));
+		assert_eq!(decode(&encode_png([2, 1], ColorType::Rgb, BitDepth::Eight, None, &[255, 0, 0, 0, 0, 255])), Some(([2, 1], vec![255, 0, 0, 255, 0, 0, 255, 255])));
+		assert_eq!(decode(&encode_png([2, 1], ColorType::Grayscale, BitDepth::Eight, None, &[0, 200])), Some(([2, 1], vec![0, 0, 0, 255, 200, 200, 200, 255])));
+		assert_eq!(decode(&encode_png([1, 1], ColorType::GrayscaleAlpha, BitDepth::Eight, None, &[50, 100])), Some(([1, 1], vec![50, 50, 50, 100])));
+		assert_eq!(decode(&encode_png([1, 1], ColorType::Rgba, BitDepth::Sixteen, None, &[255, 0, 128, 0, 0, 0, 255, 255])), Some(([1, 1], vec![255, 128, 0, 255])));
+		assert_eq!(
+			decode(&encode_png([3, 1], ColorType::Grayscale, BitDepth::One, None, &[0b1010_0000])),
+			Some(([3, 1], vec![255, 255, 255, 255, 0, 0, 0, 255, 255, 255, 255, 255]))
+		);
+		assert_eq!(
+			decode(&encode_png([2, 1], ColorType::Indexed, BitDepth::Eight, Some(&[10, 20, 30, 40, 50, 60]), &[1, 0])),
+			Some(([2, 1], vec![40, 50, 60, 255, 10, 20, 30, 255]))
+		);
+	}
+
+	// A small deterministic generator, so that failures can be reproduced.
+	fn pseudorandom_bytes(seed: u64, length: usize) -> Vec<u8> {
+		let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+		(0..length)
+			.map(|_| {
+				state ^= state << 13;
+				state ^= state >> 7;
+				state ^= state << 17;
+				state as u8
+			})
+			.collect()
+	}
+
+	#[test]
+	fn truncated_or_random_textures_fail_to_decode() {
+		let encoded = encode_png([4, 4], png::ColorType::Rgba, png::BitDepth::Eight, None, &pseudorandom_bytes(0, 64));
+		for length in 0..encoded.len() {
+			assert!(decode(&encoded[..length]).is_none());
+		}
+		for seed in 0..256 {
+			let mut data = encoded[..16].to_vec();
+			data.extend(pseudorandom_bytes(seed, seed as usize * 4));
+			assert!(decode(&data).is_none());
+			assert!(decode(&pseudorandom_bytes(seed, 64)).is_none());
+		}
+	}
+
+	#[test]
+	fn truncated_or_random_files_fail_to_load() {
+		let bytes = serialize(&test_canvas());
+		for length in 0..bytes.len() {
+			assert!(deserialize(&bytes[..length]).is_err());
+		}
+
+		// Everything after the magic numbers and version is scrambled in turn, from single bytes to the entire rest of the file.
+		let header_length = MAGIC_NUMBERS.len() + 8;
+		for seed in 0..1024 {
+			let mut scrambled = bytes.clone();
+			let noise = pseudorandom_bytes(seed, scrambled.len());
+			let start = header_length + (seed as usize * 7) % (bytes.len() - header_length);
+			let length = if seed % 2 == 0 { 1 } else { bytes.len() - start };
+			scrambled[start..start + length].copy_from_slice(&noise[..length]);
+			let _ = deserialize(&scrambled);
+			let _ = deserialize(&[&bytes[..header_length], &noise].concat());
+		}
+
+		// Files of the first version store raw pixels, whose claimed size must not be trusted.
+		let mut version_zero = MAGIC_NUMBERS.to_vec();
+		version_zero.extend(0u64.to_le_bytes());
+		version_zero.extend([0; 6]);
+		version_zero.extend([1f32, 0., 0., 0., 1.].iter().flat_map(|x| x.to_le_bytes()));
+		version_zero.extend([0u64, 0, 1].iter().flat_map(|x| x.to_le_bytes()));
+		version_zero.extend([MAX_TEXTURE_SIDE, MAX_TEXTURE_SIDE].iter().flat_map(|x| x.to_le_bytes()));
+		version_zero.extend([0; 64]);
+		assert!(matches!(deserialize(&version_zero), Err(LoadError::Truncated)));
+	}
+
+	#[test]
+	fn translucent_texels_round_trip_straight() {
+		// Fully transparent texels keep their colors too, since filtering weighs them by alpha rather than relying on them being black.
+		let data = [200, 100, 50, 128, 255, 255, 255, 0, 10, 20, 30, 255, 90, 180, 240, 1];
+		let Ok(encoded) = encode_texture([2, 2], &data) else { panic!("the texture failed to encode") };
+		let Ok((dimensions, decoded)) = decode_texture(&encoded) else { panic!("the texture failed to decode") };
+		assert_eq!(dimensions.map(NonZero::get), [2, 2]);
+		assert_eq!(decoded, data);
+	}
+
+	#[test]
+	fn images_of_empty_texture_slots_fail_to_load() {
+		let image = Image {
+			position: Vex([Vx(0.); 2]),
+			orientation: 0.,
+			dilation: 1.,
+			is_selected: false,
+			is_visible: true,
+			note: None,
+			border_width: Vx(0.),
+			border_color: Srgba8([0, 0, 0, 255]),
+			is_pixelated: false,
+			texture_index: 0,
+			dimensions: Vex([Vx(4.); 2]),
+			world_aabb_cache: Default::default(),
+		};
+		// Whether or not the empty slot is the last one, the image has no texture to refer to.
+		for (slot_count, texture_index) in [(1, 0), (2, 0), (2, 1)] {
+			let mut canvas = test_canvas();
+			canvas.images.push(Image { texture_index, ..image.clone() }.into());
+			canvas.textures.extend((0..slot_count).map(|_| None));
+			assert!(matches!(deserialize(&serialize(&canvas)), Err(LoadError::Malformed)));
+		}
 	}
-	Some(array)
 }
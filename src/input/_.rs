@@ -69,12 +69,83 @@ pub enum Key {
 	Backspace,
 	Space,
 	Tab,
+	Semicolon,
 	Control,
 	Shift,
 	LeftArrow,
 	RightArrow,
 }
 
+impl Key {
+	// The key with the given name, as it is written in shortcuts, ignoring case.
+	pub fn from_name(name: &str) -> Option<Self> {
+		EnumSet::<Key>::all().iter().find(|key| key.name().eq_ignore_ascii_case(name))
+	}
+
+	// The name of the key as it is written in shortcuts.
+	pub fn name(self) -> &'static str {
+		match self {
+			Key::F1 => "F1",
+			Key::F2 => "F2",
+			Key::F3 => "F3",
+			Key::F4 => "F4",
+			Key::F5 => "F5",
+			Key::F6 => "F6",
+			Key::F7 => "F7",
+			Key::F8 => "F8",
+			Key::F9 => "F9",
+			Key::F10 => "F10",
+			Key::F11 => "F11",
+			Key::F12 => "F12",
+			Key::K0 => "0",
+			Key::K1 => "1",
+			Key::K2 => "2",
+			Key::K3 => "3",
+			Key::K4 => "4",
+			Key::K5 => "5",
+			Key::K6 => "6",
+			Key::K7 => "7",
+			Key::K8 => "8",
+			Key::K9 => "9",
+			Key::A => "A",
+			Key::B => "B",
+			Key::C => "C",
+			Key::D => "D",
+			Key::E => "E",
+			Key::F => "F",
+			Key::G => "G",
+			Key::H => "H",
+			Key::I => "I",
+			Key::J => "J",
+			Key::K => "K",
+			Key::L => "L",
+			Key::M => "M",
+			Key::N => "N",
+			Key::O => "O",
+			Key::P => "P",
+			Key::Q => "Q",
+			Key::R => "R",
+			Key::S => "S",
+			Key::T => "T",
+			Key::U => "U",
+			Key::V => "V",
+			Key::W => "W",
+			Key::X => "X",
+			Key::Y => "Y",
+			Key::Z => "Z",
+			Key::Escape => "Esc",
+			Key::Backspace => "Backspace",
+			Key::Space => "Space",
+			Key::Tab => "Tab",
+			Key::Semicolon => ";",
+			Key::Control => "Ctrl",
+			Key::Shift => "Shift",
+			Key::LeftArrow => "Left",
+			Key::RightArrow => "Right",
+		}
+	}
+}
+
 #[derive(EnumSetType)]
 pub enum Button {
 	Left,
@@ -162,6 +233,7 @@ impl InputMonitor {
 			KeyCode::Escape => Escape,
 			KeyCode::Space => Space,
 			KeyCode::Tab => Tab,
+			KeyCode::Semicolon => Semicolon,
 			KeyCode::ShiftLeft | KeyCode::ShiftRight => Shift,
 			KeyCode::ControlLeft | KeyCode::ControlRight => Control,
 			KeyCode::ArrowLeft => LeftArrow,
@@ -21,12 +21,27 @@ pub struct Keytest {
 	triggers: EnumSet<Key>,
 	is_repeatable: bool,
 	action: Action,
+	// What the binding does, and the category it is listed under, for showing to the user.
+	description: &'static str,
+	category: &'static str,
+	// The order in which the binding was inserted, which it is listed in.
+	index: usize,
 }
 
 pub struct Keymap {
 	keytests: HashMap<EnumSet<Key>, Keytest>,
 	#[allow(clippy::type_complexity)]
 	waiting_releases: Vec<(EnumSet<Key>, fn(&mut App))>,
+	// The keys of a discovery ended by a chord that extends them, which doesn't resume until they are released.
+	suppressed_discovery: EnumSet<Key>,
+	// The category that bindings are inserted under.
+	category: &'static str,
+}
+
+// The bindings listed under a category, as their keys and descriptions.
+pub struct BindingGroup {
+	pub category: &'static str,
+	pub bindings: Vec<(String, &'static str)>,
 }
 
 impl Keymap {
@@ -34,15 +49,71 @@ impl Keymap {
 		Self {
 			keytests: HashMap::new(),
 			waiting_releases: Vec::new(),
+			suppressed_discovery: EnumSet::EMPTY,
+			category: "",
 		}
 	}
 
-	pub fn insert(&mut self, modifiers: impl Into<EnumSet<Key>>, triggers: impl Into<EnumSet<Key>>, is_repeatable: bool, action: Action) {
+	// Files the bindings inserted after this under a category.
+	pub fn set_category(&mut self, category: &'static str) {
+		self.category = category;
+	}
+
+	pub fn insert(&mut self, modifiers: impl Into<EnumSet<Key>>, triggers: impl Into<EnumSet<Key>>, is_repeatable: bool, description: &'static str, action: Action) {
 		let (modifiers, triggers) = (modifiers.into(), triggers.into());
-		self.keytests.insert(modifiers.union(triggers), Keytest { triggers, is_repeatable, action });
+		let index = self.keytests.len();
+		self.keytests.insert(
+			modifiers.union(triggers),
+			Keytest {
+				triggers,
+				is_repeatable,
+				action,
+				description,
+				category: self.category,
+				index,
+			},
+		);
+	}
+
+	// Lists every binding by category, in the order they were inserted.
+	pub fn binding_groups(&self) -> Vec<BindingGroup> {
+		let mut keytests = self.keytests.iter().collect::<Vec<_>>();
+		keytests.sort_by_key(|(_, keytest)| keytest.index);
+
+		let mut groups = Vec::<BindingGroup>::new();
+		for (keys, keytest) in keytests {
+			let binding = (shortcut_name(keys.difference(keytest.triggers), keytest.triggers), keytest.description);
+			match groups.iter_mut().find(|group| group.category == keytest.category) {
+				Some(group) => group.bindings.push(binding),
+				None => groups.push(BindingGroup {
+					category: keytest.category,
+					bindings: vec![binding],
+				}),
+			}
+		}
+		groups
 	}
 }
 
+// Writes a shortcut as its modifiers followed by its triggers, each led by Control and Shift, e.g. "Ctrl-Shift-S" or "Tab-R".
+fn shortcut_name(modifiers: EnumSet<Key>, triggers: EnumSet<Key>) -> String {
+	let rank = |key: &Key| match key {
+		Key::Control => 0,
+		Key::Shift => 1,
+		_ => 2,
+	};
+	[modifiers, triggers]
+		.into_iter()
+		.flat_map(|keys| {
+			let mut keys = keys.iter().collect::<Vec<_>>();
+			keys.sort_by_key(rank);
+			keys
+		})
+		.map(Key::name)
+		.collect::<Vec<_>>()
+		.join("-")
+}
+
 pub fn execute_keymap(app: &mut App, active_keys: EnumSet<Key>, fresh_keys: EnumSet<Key>, different_keys: EnumSet<Key>) {
 	let mut release_indices = vec![];
 
@@ -57,14 +128,27 @@ pub fn execute_keymap(app: &mut App, active_keys: EnumSet<Key>, fresh_keys: Enum
 		app.keymap.waiting_releases.remove(*i);
 	}
 
+	if !app.keymap.suppressed_discovery.is_subset(active_keys) {
+		app.keymap.suppressed_discovery = EnumSet::EMPTY;
+	}
+
 	if let Some(keytest) = app.keymap.keytests.get(&active_keys) {
 		match keytest.action {
 			Action::Trigger { on_trigger } => {
 				if !keytest.triggers.intersection(if keytest.is_repeatable { fresh_keys } else { different_keys }).is_empty() {
+					// A chord that extends a held discovery, like Tab-R while Tab picks a color, ends the discovery first, so that the chord doesn't also act as it.
+					while let Some(i) = app.keymap.waiting_releases.iter().position(|(detriggers, _)| detriggers.is_subset(active_keys) && *detriggers != active_keys) {
+						let (detriggers, on_release) = app.keymap.waiting_releases.remove(i);
+						on_release(app);
+						app.keymap.suppressed_discovery = detriggers;
+					}
 					on_trigger(app);
 				}
 			},
 			Action::Discovery { on_press, on_release } => {
+				if active_keys == app.keymap.suppressed_discovery {
+					return;
+				}
 				if !keytest.triggers.intersection(if keytest.is_repeatable { fresh_keys } else { different_keys }).is_empty() || !active_keys.complement().intersection(different_keys).is_empty() {
 					on_press(app);
 					app.keymap.waiting_releases.push((active_keys, on_release));
@@ -7,9 +7,15 @@
 
 #[repr(C)]
 pub struct Packet {
+	pub buttons: u32,
 	pub normal_pressure: u32,
 }
 
+impl Packet {
+	pub const LOWER_BARREL_BUTTON: u32 = 1 << 1;
+	pub const UPPER_BARREL_BUTTON: u32 = 1 << 2;
+}
+
 pub struct TabletContext {}
 
 impl TabletContext {
@@ -109,13 +109,18 @@ impl LogicalContext {
 	}
 }
 
+// Fields are laid out in the order of their flags.
 #[repr(C)]
 pub struct Packet {
+	pub buttons: c_ulong,
 	pub normal_pressure: c_uint,
 }
 
 impl Packet {
-	const DATA: PacketFields = PacketFields::NORMAL_PRESSURE;
+	const DATA: PacketFields = PacketFields::BUTTONS.union(PacketFields::NORMAL_PRESSURE);
+	// The tip of the pen is its first button, followed by its lower and upper barrel buttons.
+	pub const LOWER_BARREL_BUTTON: c_ulong = 1 << 1;
+	pub const UPPER_BARREL_BUTTON: c_ulong = 1 << 2;
 }
 
 macro_rules! impl_interface {
@@ -15,6 +15,7 @@
 mod actions;
 mod app;
 mod canvas;
+#[path = "clipboard/_.rs"]
 mod clipboard;
 mod config;
 mod file;
@@ -30,6 +31,7 @@ mod utility;
 mod windows;
 
 use app::App;
+use config::WindowGeometry;
 use winit::event_loop::EventLoopBuilder;
 
 pub const APP_NAME_CAPITALIZED: &str = "Inksy";
@@ -52,10 +54,16 @@ fn main() {
 		crate::windows::set_window_icon(crate::windows::window_hwnd(&window).into());
 	}
 
-	// Resize the window to a reasonable size.
-	let monitor_size = window.current_monitor().unwrap().size();
-	let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(monitor_size.width as f64 / 1.5, monitor_size.height as f64 / 1.5));
-	window.set_outer_position(winit::dpi::PhysicalPosition::new(monitor_size.width as f64 / 6., monitor_size.height as f64 / 6.));
+	// Restore the window to its last geometry, or otherwise resize the window to a reasonable size.
+	if let Some(WindowGeometry { position, size, is_maximized }) = WindowGeometry::load().and_then(|window_geometry| window_geometry.clamp_to_monitors(window.available_monitors())) {
+		let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(size[0], size[1]));
+		window.set_outer_position(winit::dpi::PhysicalPosition::new(position[0], position[1]));
+		window.set_maximized(is_maximized);
+	} else {
+		let monitor_size = window.current_monitor().unwrap().size();
+		let _ = window.request_inner_size(winit::dpi::PhysicalSize::new(monitor_size.width as f64 / 1.5, monitor_size.height as f64 / 1.5));
+		window.set_outer_position(winit::dpi::PhysicalPosition::new(monitor_size.width as f64 / 6., monitor_size.height as f64 / 6.));
+	}
 
 	// Initialize the app at the event loop.
 	let app = App::new(&window);
@@ -7,6 +7,7 @@
 
 mod dynamic_buffer;
 mod dynamic_storage_buffer;
+pub mod grain;
 mod instance_renderer;
 mod renderer;
 pub mod stroke_renderer;
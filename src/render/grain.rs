@@ -0,0 +1,72 @@
+// Copyright (C) 2023 Aaron Yeoh Cruz <zeyonaut@gmail.com>
+// SPDX-License-Identifier: MPL-2.0
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::f32::consts::TAU;
+
+// The side length of a grain texture, which tiles across the canvas with one texel per canvas unit.
+pub const GRAIN_TEXTURE_SIZE: u32 = 256;
+
+// A texture that modulates the opacity of strokes, like paper beneath a dry medium.
+#[derive(Clone, Copy)]
+pub enum Grain {
+	Pencil,
+	Charcoal,
+	Canvas,
+}
+
+impl Grain {
+	// Generates the RGBA data of the grain texture, with the grain stored in the alpha channel so that it is not gamma-decoded.
+	pub fn generate(self) -> Vec<u8> {
+		let mut data = Vec::with_capacity((GRAIN_TEXTURE_SIZE * GRAIN_TEXTURE_SIZE * 4) as usize);
+		for y in 0..GRAIN_TEXTURE_SIZE {
+			for x in 0..GRAIN_TEXTURE_SIZE {
+				let opacity = match self {
+					// Fine, even tooth.
+					Self::Pencil => 0.55 + 0.45 * (0.5 * value_noise(x, y, 1, 0) + 0.5 * value_noise(x, y, 4, 1)),
+					// Coarse, high-contrast patches that leave gaps.
+					Self::Charcoal => {
+						let noise = 0.35 * value_noise(x, y, 2, 0) + 0.65 * value_noise(x, y, 16, 1);
+						let t = ((noise - 0.25) / 0.5).clamp(0., 1.);
+						0.15 + 0.85 * t * t * (3. - 2. * t)
+					},
+					// A woven pattern of threads, roughened by noise.
+					Self::Canvas => {
+						let weave = ((x as f32 * TAU / 8.).sin() * (y as f32 * TAU / 8.).sin()).abs();
+						0.5 + 0.35 * weave + 0.15 * value_noise(x, y, 2, 0)
+					},
+				};
+				data.extend([0xff, 0xff, 0xff, (opacity.clamp(0., 1.) * 255.).round() as u8]);
+			}
+		}
+		data
+	}
+}
+
+// Hashes lattice coordinates to a value between 0 and 1.
+fn hash(x: u32, y: u32, seed: u32) -> f32 {
+	let mut h = x.wrapping_mul(0x8da6_b343) ^ y.wrapping_mul(0xd816_3841) ^ seed.wrapping_mul(0xcb1a_b31f);
+	h ^= h >> 15;
+	h = h.wrapping_mul(0x2c1b_3c6d);
+	h ^= h >> 12;
+	h = h.wrapping_mul(0x297a_2d39);
+	h ^= h >> 15;
+	h as f32 / u32::MAX as f32
+}
+
+// Smoothly interpolates hashed values on a lattice of cells, wrapping around so that the texture tiles.
+fn value_noise(x: u32, y: u32, cell_size: u32, seed: u32) -> f32 {
+	let cell_count = GRAIN_TEXTURE_SIZE / cell_size;
+	let [cell_x, cell_y] = [x / cell_size, y / cell_size];
+	let [t_x, t_y] = [x % cell_size, y % cell_size].map(|t| {
+		let t = t as f32 / cell_size as f32;
+		t * t * (3. - 2. * t)
+	});
+	let corner = |i: u32, j: u32| hash((cell_x + i) % cell_count, (cell_y + j) % cell_count, seed);
+	let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * t_x;
+	let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * t_x;
+	top + (bottom - top) * t_y
+}
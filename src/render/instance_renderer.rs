@@ -98,6 +98,10 @@ impl<Instance> InstanceRenderer<Instance> {
 		self.instance_buffer.write(device, queue, offset, instances);
 	}
 
+	pub fn buffer_size(&self) -> u64 {
+		self.instance_buffer.buffer.size()
+	}
+
 	pub fn render<'r>(&'r self, render_pass: &mut wgpu::RenderPass<'r>, instance_range: Range<u32>) {
 		render_pass.set_pipeline(&self.render_pipeline);
 		render_pass.set_vertex_buffer(0, self.instance_buffer.buffer.slice(..));
@@ -11,6 +11,7 @@ use fast_srgb8::srgb8_to_f32;
 use pollster::FutureExt;
 
 use super::{
+	grain::Grain,
 	instance_renderer::InstanceRenderer,
 	stroke_renderer::CanvasRenderer,
 	text_renderer::{Align, TextInstance, TextRenderer},
@@ -21,17 +22,35 @@ use super::{
 use crate::{
 	canvas::{Canvas, IncompleteStroke},
 	config::Config,
-	utility::{Px, Vex, Vx},
+	utility::{Px, Srgb8, Vex, Vx},
 };
 
 const SHOULD_MULTISAMPLE: bool = false;
 
+// The light and dark cells of the checkerboard that stands in for a transparent background.
+const CHECKERBOARD_COLORS: [[u8; 4]; 2] = [[0xcc, 0xcc, 0xcc, 0xff], [0x99, 0x99, 0x99, 0xff]];
+
+// The color that selected objects are tinted towards, unless a color scheme says otherwise.
+pub const DEFAULT_SELECTION_COLOR: Srgb8 = Srgb8([0x28, 0xc2, 0xff]);
+
 pub enum DrawCommand<'a> {
 	Text { text: Cow<'a, str>, align: Option<Align>, position: Vex<2, Px>, anchors: [f32; 2] },
 	Card { position: Vex<2, Px>, dimensions: Vex<2, Px>, color: [u8; 4], radius: Px },
 	ColorSelector { position: Vex<2, Px>, hsv: [f32; 3], trigon_radius: Px, hole_radius: Px, ring_width: Px },
 }
 
+// What the last frame uploaded and drew, for showing in debug mode.
+#[derive(Clone, Copy, Default)]
+pub struct RenderStatistics {
+	// The total size of the growable buffers of the canvas and interface, in bytes.
+	pub canvas_buffer_size: u64,
+	pub interface_buffer_size: u64,
+	pub uploaded_stroke_count: usize,
+	pub uploaded_vertex_count: usize,
+	pub uploaded_index_count: usize,
+	pub draw_call_count: usize,
+}
+
 pub enum RenderCommand {
 	Card(Range<u32>),
 	ColorRing(Range<u32>),
@@ -58,6 +77,15 @@ pub struct ViewportUniform {
 	pub size: [f32; 2],
 	pub scale: f32,
 	pub tilt: f32,
+	// Whether colors must be gamma-encoded by shaders, i.e. when blending happens in gamma space.
+	pub is_gamma_encoded: f32,
+	// Whether the caps at the ends of strokes are rounded rather than cut flat.
+	pub is_round_capped: f32,
+	// The linear color that selected objects are tinted towards.
+	pub selection_color: [f32; 4],
+	// The number of physical pixels per logical pixel, independent of zoom.
+	pub scale_factor: f32,
+	pub padding: [f32; 3],
 }
 
 #[repr(C)]
@@ -98,6 +126,20 @@ impl VertexAttributes<3> for ColorTrigonInstance {
 	const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32, 2 => Float32];
 }
 
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CheckerboardInstance {
+	pub position: [f32; 2],
+	pub dimensions: [f32; 2],
+	pub cell_size: f32,
+	pub light_color: [f32; 4],
+	pub dark_color: [f32; 4],
+}
+
+impl VertexAttributes<5> for CheckerboardInstance {
+	const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32, 3 => Float32x4, 4 => Float32x4];
+}
+
 // This struct stores the current state of the WGPU renderer.
 pub struct Renderer<'window> {
 	// Rendering machinery.
@@ -107,8 +149,14 @@ pub struct Renderer<'window> {
 	// Properties.
 	pub config: wgpu::SurfaceConfiguration,
 	surface_format: wgpu::TextureFormat,
+	is_blending_linear: bool,
+	is_round_capped: bool,
 	pub scale_factor: f32,
+	// The size of interface text, in logical pixels.
+	font_size: f32,
 	pub is_pending_resize: bool,
+	// The selection color last written to the viewport buffer.
+	selection_color: Srgb8,
 	// Text rendering.
 	pub text_renderer: TextRenderer,
 	pub info_text: TextInstance,
@@ -117,15 +165,18 @@ pub struct Renderer<'window> {
 	pub card_renderer: InstanceRenderer<CardInstance>,
 	pub color_ring_renderer: InstanceRenderer<ColorRingInstance>,
 	pub color_trigon_renderer: InstanceRenderer<ColorTrigonInstance>,
+	pub checkerboard_renderer: InstanceRenderer<CheckerboardInstance>,
 	// Other resource handles.
 	pub viewport_buffer: UniformBuffer<ViewportUniform>,
+	pub statistics: RenderStatistics,
 	texture_bind_group_layout: wgpu::BindGroupLayout,
 	multisample_texture: Option<wgpu::Texture>,
 }
 
 impl<'window> Renderer<'window> {
 	// Create an instance of the renderer.
-	pub fn new<W>(window: &'window W, width: u32, height: u32, scale_factor: f32) -> Self
+	#[allow(clippy::too_many_arguments)]
+	pub fn new<W>(window: &'window W, width: u32, height: u32, scale_factor: f32, is_blending_linear: bool, is_round_capped: bool, grain: Option<Grain>, font: Option<&str>, font_size: f32) -> Self
 	where
 		W: wgpu::rwh::HasWindowHandle + wgpu::rwh::HasDisplayHandle + Sync,
 	{
@@ -165,7 +216,9 @@ impl<'window> Renderer<'window> {
 		// FIXME: Ensure dimensions are nonzero.
 		let surface_capabilities = surface.get_capabilities(&adapter);
 
-		let surface_format = surface_capabilities.formats.iter().find(|f| f.is_srgb()).copied().unwrap_or(*surface_capabilities.formats.first().unwrap());
+		// Blending happens in linear space on sRGB surfaces, and in gamma space otherwise.
+		let surface_format = surface_capabilities.formats.iter().find(|f| f.is_srgb() == is_blending_linear).copied().unwrap_or(*surface_capabilities.formats.first().unwrap());
+		let is_blending_linear = surface_format.is_srgb();
 
 		let config = wgpu::SurfaceConfiguration {
 			usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_DST,
@@ -195,12 +248,12 @@ impl<'window> Renderer<'window> {
 		};
 		let sample_count = multisample_texture.as_ref().map_or(1, |_| 4);
 
-		let mut text_renderer = TextRenderer::new(&device, &queue, surface_format, sample_count);
+		let mut text_renderer = TextRenderer::new(&device, &queue, surface_format, is_blending_linear, sample_count, font);
 
 		let info_text = TextInstance::new(
 			&mut text_renderer,
 			"Press Ctrl + N to open a new canvas or Ctrl + O to load an existing canvas.",
-			13.,
+			font_size,
 			1.25,
 			Some(Align::Center),
 			Vex([width as f32 / 2., height as f32 / 2.].map(Px)),
@@ -217,15 +270,21 @@ impl<'window> Renderer<'window> {
 				size: [width as f32, height as f32],
 				scale: scale_factor,
 				tilt: 0.,
+				is_gamma_encoded: if is_blending_linear { 0. } else { 1. },
+				is_round_capped: if is_round_capped { 1. } else { 0. },
+				selection_color: DEFAULT_SELECTION_COLOR.opaque().to_lrgba().0,
+				scale_factor,
+				padding: [0.; 3],
 			},
 		);
 
 		let sample_count = multisample_texture.as_ref().map_or(1, |_| 4);
 
-		let canvas_renderer = CanvasRenderer::new(&device, config.format, &viewport_buffer, sample_count);
+		let canvas_renderer = CanvasRenderer::new(&device, config.format, &viewport_buffer, sample_count, grain);
 		let card_renderer = InstanceRenderer::new(&device, config.format, include_str!("shaders/round_rectangle.wgsl"), "vs_main", "fs_main", &[&viewport_buffer.bind_group_layout], sample_count);
 		let color_ring_renderer = InstanceRenderer::new(&device, config.format, include_str!("shaders/color_picker_ring.wgsl"), "vs_main", "fs_main", &[&viewport_buffer.bind_group_layout], sample_count);
 		let color_trigon_renderer = InstanceRenderer::new(&device, config.format, include_str!("shaders/color_picker_trigon.wgsl"), "vs_main", "fs_main", &[&viewport_buffer.bind_group_layout], sample_count);
+		let checkerboard_renderer = InstanceRenderer::new(&device, config.format, include_str!("shaders/checkerboard.wgsl"), "vs_main", "fs_main", &[&viewport_buffer.bind_group_layout], sample_count);
 
 		// We return a new instance of our renderer state.
 		Self {
@@ -234,8 +293,11 @@ impl<'window> Renderer<'window> {
 			queue,
 			config,
 			scale_factor,
+			font_size,
 			is_pending_resize: false,
+			selection_color: DEFAULT_SELECTION_COLOR,
 			viewport_buffer,
+			statistics: RenderStatistics::default(),
 			texture_bind_group_layout,
 			text_renderer,
 			info_text,
@@ -243,11 +305,20 @@ impl<'window> Renderer<'window> {
 			card_renderer,
 			color_ring_renderer,
 			color_trigon_renderer,
+			checkerboard_renderer,
 			multisample_texture,
 			surface_format,
+			is_blending_linear,
+			is_round_capped,
 		}
 	}
 
+	// Converts a background color to the clear color of the surface.
+	pub fn background_color(&self, color: Srgb8) -> wgpu::Color {
+		let [r, g, b, a] = if self.is_blending_linear { color.opaque().to_lrgba().0 } else { color.opaque().0.map(|x| f32::from(x) / 255.) }.map(f64::from);
+		wgpu::Color { r, g, b, a }
+	}
+
 	// Resize the renderer to a requested size.
 	pub fn resize(&mut self, width: u32, height: u32, scale_factor: f32) {
 		// We ensure the requested size has nonzero dimensions before applying it.
@@ -278,6 +349,9 @@ pub struct Prerender<'a> {
 	pub canvas: Option<&'a mut Canvas>,
 	pub current_stroke: Option<&'a IncompleteStroke>,
 	pub draw_commands: Vec<DrawCommand<'a>>,
+	// If set, a screen-fixed checkerboard with cells of this size is drawn in place of the background color.
+	pub checkerboard_cell_size: Option<Px>,
+	pub selection_color: Srgb8,
 }
 
 impl<'a> Prerender<'a> {
@@ -286,15 +360,18 @@ impl<'a> Prerender<'a> {
 			canvas: None,
 			current_stroke: None,
 			draw_commands: Vec::new(),
+			checkerboard_cell_size: None,
+			selection_color: DEFAULT_SELECTION_COLOR,
 		}
 	}
 }
 
 impl<'window> Renderer<'window> {
 	pub fn render(&mut self, config: &Config, mut prerender: Prerender) -> Result<(), wgpu::SurfaceError> {
+		let selection_color = prerender.selection_color;
 		if let Some(canvas) = prerender.canvas.as_mut() {
-			if let Some(view) = canvas.view.read_if_with_is_dirty(|is_dirty| is_dirty || self.is_pending_resize) {
-				// We write the new size to the viewport buffer.
+			if let Some(view) = canvas.view.read_if_with_is_dirty(|is_dirty| is_dirty || self.is_pending_resize || selection_color.0 != self.selection_color.0) {
+				// We write the new size and selection color to the viewport buffer.
 				self.viewport_buffer.write(
 					&self.queue,
 					ViewportUniform {
@@ -302,23 +379,27 @@ impl<'window> Renderer<'window> {
 						size: [self.config.width as f32, self.config.height as f32],
 						scale: view.zoom.0 * self.scale_factor,
 						tilt: view.tilt,
+						is_gamma_encoded: if self.is_blending_linear { 0. } else { 1. },
+						is_round_capped: if self.is_round_capped { 1. } else { 0. },
+						selection_color: selection_color.opaque().to_lrgba().0,
+						scale_factor: self.scale_factor,
+						padding: [0.; 3],
 					},
 				);
 				self.is_pending_resize = false;
+				self.selection_color = selection_color;
 			}
 
-			for texture in canvas.textures.iter_mut() {
+			for texture in canvas.textures.iter_mut().flatten() {
 				texture.prepare(&self.queue);
 			}
 		}
 
-		let canvas_render_key = prerender.canvas.as_mut().map(|canvas| self.canvas_renderer.prepare(&self.device, &self.queue, canvas, prerender.current_stroke));
+		let mut statistics = RenderStatistics::default();
+		let canvas_render_key = prerender.canvas.as_mut().map(|canvas| self.canvas_renderer.prepare(&self.device, &self.queue, canvas, prerender.current_stroke, &mut statistics));
 
 		// We compute the background color of the canvas.
-		let background_color = {
-			let [r, g, b, a] = prerender.canvas.as_ref().map_or(config.default_canvas_color, |canvas| canvas.background_color).opaque().to_lrgba().0.map(|x| x as f64);
-			wgpu::Color { r, g, b, a }
-		};
+		let background_color = self.background_color(prerender.canvas.as_ref().map_or(config.default_canvas_color, |canvas| canvas.background_color));
 
 		let mut card_instances: Vec<CardInstance> = vec![];
 		let mut color_ring_instances: Vec<ColorRingInstance> = vec![];
@@ -329,7 +410,7 @@ impl<'window> Renderer<'window> {
 
 		for draw_command in prerender.draw_commands {
 			match draw_command {
-				DrawCommand::Text { text, align, position, anchors } => text_instances.push(TextInstance::new(&mut self.text_renderer, &text, 13., 1.25, align, position, anchors)),
+				DrawCommand::Text { text, align, position, anchors } => text_instances.push(TextInstance::new(&mut self.text_renderer, &text, self.font_size, 1.25, align, position, anchors)),
 				DrawCommand::Card { position, dimensions, color, radius } => {
 					let instance_start = card_instances.len() as u32;
 					card_instances.push(CardInstance {
@@ -382,6 +463,21 @@ impl<'window> Renderer<'window> {
 		self.card_renderer.prepare(&self.device, &self.queue, 0, &card_instances);
 		self.color_ring_renderer.prepare(&self.device, &self.queue, 0, &color_ring_instances);
 		self.color_trigon_renderer.prepare(&self.device, &self.queue, 0, &color_trigon_instances);
+		if let Some(cell_size) = prerender.checkerboard_cell_size {
+			let [light_color, dark_color] = CHECKERBOARD_COLORS.map(|color| color.map(srgb8_to_f32));
+			self.checkerboard_renderer.prepare(
+				&self.device,
+				&self.queue,
+				0,
+				&[CheckerboardInstance {
+					position: [0., 0.],
+					dimensions: [self.config.width as f32, self.config.height as f32],
+					cell_size: cell_size.0,
+					light_color,
+					dark_color,
+				}],
+			);
+		}
 
 		// Set up the surface texture we will later render to.
 		let output = self.surface.get_current_texture()?;
@@ -411,9 +507,20 @@ impl<'window> Renderer<'window> {
 
 		self.viewport_buffer.activate(&mut render_pass, 0);
 
+		if prerender.checkerboard_cell_size.is_some() {
+			self.checkerboard_renderer.render(&mut render_pass, 0..1);
+		}
+
 		if let (Some(canvas), Some(canvas_render_key)) = (prerender.canvas, canvas_render_key) {
+			// Each visible image with a texture is drawn on its own, followed by all strokes at once.
+			statistics.draw_call_count += canvas_render_key
+				.0
+				.iter()
+				.filter(|texture_index| matches!(texture_index.and_then(|texture_index| canvas.textures.get(texture_index)), Some(Some(_))))
+				.count() + 1;
 			self.canvas_renderer.render(&mut render_pass, &canvas.textures, canvas_render_key);
 		}
+		statistics.draw_call_count += usize::from(prerender.checkerboard_cell_size.is_some()) + render_commands.len() + 1;
 
 		for render_command in render_commands {
 			match render_command {
@@ -431,6 +538,10 @@ impl<'window> Renderer<'window> {
 		self.queue.submit(std::iter::once(encoder.finish()));
 		output.present();
 
+		statistics.canvas_buffer_size = self.canvas_renderer.buffer_sizes().iter().sum();
+		statistics.interface_buffer_size = self.card_renderer.buffer_size() + self.color_ring_renderer.buffer_size() + self.color_trigon_renderer.buffer_size() + self.checkerboard_renderer.buffer_size();
+		self.statistics = statistics;
+
 		// Return successfully.
 		Ok(())
 	}
@@ -511,4 +622,30 @@ impl<'window> Renderer<'window> {
 
 		Some((output_buffer, bytes_per_row as usize))
 	}
+
+	// Returns tightly packed RGBA data.
+	pub fn read_texture(&self, texture: &Texture) -> Option<Vec<u8>> {
+		let source_bytes_per_row = texture.extent.width as usize * 4;
+		let mut data = Vec::with_capacity(source_bytes_per_row * texture.extent.height as usize);
+
+		// Fetch and map texture from device.
+		let (buffer, bytes_per_row) = self.fetch_texture(texture)?;
+		let buffer_slice = buffer.slice(..);
+		let (tx, rx) = futures_intrusive::channel::shared::oneshot_channel();
+		buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+			tx.send(result).unwrap();
+		});
+		self.device.poll(wgpu::Maintain::Wait);
+		rx.receive().block_on()?.ok()?;
+
+		// Read the texture row-by-row (each an initial slice of a mapped chunk).
+		for chunk in buffer.slice(..).get_mapped_range().chunks(bytes_per_row) {
+			data.extend(&chunk[..source_bytes_per_row])
+		}
+
+		// Unmap the texture buffer.
+		buffer.unmap();
+
+		Some(data)
+	}
 }
@@ -10,6 +10,10 @@ struct ViewportUniform {
 	size: vec2f,
 	scale: f32,
 	tilt: f32,
+	is_gamma_encoded: f32,
+	is_round_capped: f32,
+	selection_color: vec4f,
+	scale_factor: f32,
 }
 
 struct SelectionTransformation {
@@ -17,6 +21,7 @@ struct SelectionTransformation {
 	center_of_transformation: vec2f,
 	rotation: f32,
 	dilation: f32,
+	stretch: vec2f,
 }
 
 @group(0) @binding(0) var<uniform> viewport: ViewportUniform;
@@ -32,6 +37,9 @@ struct Instance {
 	@location(4) sprite_position: vec2f,
 	@location(5) sprite_dimensions: vec2f,
 	@location(6) is_selected: f32,
+	@location(7) border_width: f32,
+	@location(8) border_color: vec4f,
+	@location(9) is_pixelated: f32,
 }
 
 struct ClipVertex {
@@ -44,6 +52,10 @@ struct ClipVertex {
 	@location(2) texture_coordinates_by_pixel: vec2f,
 	@location(3) texture_coordinates: vec2f,
 	@location(4) is_selected: f32,
+	// Sprite dimensions of the frame drawn around the image.
+	@location(5) border_dimensions: vec2f,
+	@location(6) border_color: vec4f,
+	@location(7) is_pixelated: f32,
 }
 
 var<private> vertices: array<vec2f, 4> = array<vec2f, 4>(
@@ -57,8 +69,8 @@ fn rotate(v: vec2f, angle: f32) -> vec2f {
 	return vec2(cos(angle) * v.x - sin(angle) * v.y, sin(angle) * v.x + cos(angle) * v.y);
 }
 
-fn conform_about(v: vec2f, center: vec2f, rotation: f32, dilation: f32) -> vec2f {
-	return center + rotate(v - center, rotation) * dilation;
+fn conform_about(v: vec2f, center: vec2f, rotation: f32, dilation: f32, stretch: vec2f) -> vec2f {
+	return center + rotate(v - center, rotation) * dilation * stretch;
 }
 
 // IEC 61966-2-1
@@ -71,9 +83,12 @@ fn vs_main(instance: Instance, @builtin(vertex_index) index: u32) -> ClipVertex
 	var out: ClipVertex;
 	let vertex = vertices[index] - vec2(0.5);
 	let vertex2 = vertex * 2;
+	// Extend the image by its frame in each direction.
+	let border_dimensions = instance.border_width * instance.sprite_dimensions / instance.dimensions;
+	let framed_sprite_dimensions = instance.sprite_dimensions + 2. * border_dimensions;
 	// Add a single physical pixel in each direction.
-	let transformed_position = instance.position + rotate(vertex * (instance.dimensions * instance.dilation) + vertex2 / viewport.scale, instance.orientation);
-	let selection_transformed_position = selection_transformation.translation + conform_about(transformed_position, selection_transformation.center_of_transformation, selection_transformation.rotation, selection_transformation.dilation);
+	let transformed_position = instance.position + rotate(vertex * ((instance.dimensions + 2. * instance.border_width) * instance.dilation) + vertex2 / viewport.scale, instance.orientation);
+	let selection_transformed_position = selection_transformation.translation + conform_about(transformed_position, selection_transformation.center_of_transformation, selection_transformation.rotation, selection_transformation.dilation, selection_transformation.stretch);
 
 	let position = (1. - instance.is_selected) * transformed_position + instance.is_selected * selection_transformed_position;
 
@@ -81,9 +96,12 @@ fn vs_main(instance: Instance, @builtin(vertex_index) index: u32) -> ClipVertex
 	// Add a single physical pixel in each direction.
 	out.blur_border_dimensions = vec2f(1., 1.) / (instance.dilation * viewport.scale);
 	out.sprite_semidimensions = 0.5 * instance.sprite_dimensions;
-	out.texture_coordinates_by_pixel = vertex * instance.sprite_dimensions + vertex2 / (instance.dilation * viewport.scale);
-	out.texture_coordinates = (instance.sprite_position + vertices[index] * instance.sprite_dimensions + vertex2 / (instance.dilation * viewport.scale)) / vec2f(textureDimensions(atlas_texture));
+	out.texture_coordinates_by_pixel = vertex * framed_sprite_dimensions + vertex2 / (instance.dilation * viewport.scale);
+	out.texture_coordinates = (instance.sprite_position - border_dimensions + vertices[index] * framed_sprite_dimensions + vertex2 / (instance.dilation * viewport.scale)) / vec2f(textureDimensions(atlas_texture));
 	out.is_selected = instance.is_selected;
+	out.border_dimensions = border_dimensions;
+	out.border_color = instance.border_color;
+	out.is_pixelated = instance.is_pixelated;
 	
 	return out;
 }
@@ -93,12 +111,30 @@ fn blurred_step(edge: f32, value: f32) -> f32 {
 	return smoothstep(edge - radius, edge + radius, value);
 }
 
+// IEC 61966-2-1
+fn linear_to_srgb(color: vec3f) -> vec3f {
+  return mix(1.055 * pow(color, vec3(1. / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Gamma-encodes the color if the surface blends in gamma space.
+fn encode(color: vec4f) -> vec4f {
+	return select(color, vec4f(linear_to_srgb(color.rgb), color.a), viewport.is_gamma_encoded > 0.5);
+}
+
+fn premultiply(color: vec4f) -> vec4f {
+	return vec4f(color.rgb * color.a, color.a);
+}
+
 @fragment
 fn fs_main(in: ClipVertex) -> @location(0) vec4f {
 	let d_tex_d_pix_x = length(dpdx(in.texture_coordinates_by_pixel));
 	let d_tex_d_pix_y = length(dpdy(in.texture_coordinates_by_pixel));
 	var texture_color: vec4f;
-	if max(d_tex_d_pix_x, d_tex_d_pix_y) >= 1. {
+	if in.is_pixelated > 0.5 {
+		// Pixelated images sample the center of the nearest texel, so that they are never filtered.
+		let texture_dimensions = vec2f(textureDimensions(atlas_texture));
+		texture_color = textureSample(atlas_texture, atlas_sampler, (floor(in.texture_coordinates * texture_dimensions) + 0.5) / texture_dimensions);
+	} else if max(d_tex_d_pix_x, d_tex_d_pix_y) >= 1. {
 		// Minification. (Uses sampler's minification).
 		texture_color = textureSample(atlas_texture, atlas_sampler, in.texture_coordinates);
 	} else {
@@ -109,10 +145,17 @@ fn fs_main(in: ClipVertex) -> @location(0) vec4f {
 		let b = textureSample(atlas_texture, atlas_sampler, in.texture_coordinates - factor * vec2f(d_tex_d_pix_x, 0.) / (in.sprite_semidimensions * 2.));
 		let c = textureSample(atlas_texture, atlas_sampler, in.texture_coordinates - factor * vec2f(0., d_tex_d_pix_y) / (in.sprite_semidimensions * 2.));
 		let d = textureSample(atlas_texture, atlas_sampler, in.texture_coordinates - factor * vec2f(d_tex_d_pix_x, d_tex_d_pix_y) / (in.sprite_semidimensions * 2.));
-		texture_color = mix(mix(a, b, 0.5), mix(c, d, 0.5), 0.5);
+		// Texels hold straight alpha, so they are mixed premultiplied, lest transparent texels bleed their color into the edges of opaque ones.
+		let premultiplied = mix(mix(premultiply(a), premultiply(b), 0.5), mix(premultiply(c), premultiply(d), 0.5), 0.5);
+		texture_color = vec4f(premultiplied.rgb / max(premultiplied.a, 1e-6), premultiplied.a);
 	};
 	// Each coordinate of frag_position ranges from 0 (center of image) to 1 (edge of image).
 	// We can use this for antialiasing image edges.
-	let dist_from_edge = in.sprite_semidimensions + in.blur_border_dimensions - abs(in.texture_coordinates_by_pixel);
-	return vec4f((1. - in.is_selected) * texture_color.rgb + in.is_selected * vec3f(0.5 * texture_color.rgb + 0.5 * srgb_to_linear(vec3f(0x28./0xff., 0xc2./0xff., 0xff./0xff.))), texture_color.a * min(blurred_step(in.blur_border_dimensions.x, dist_from_edge.x), blurred_step(in.blur_border_dimensions.y, dist_from_edge.y)));
+	let dist_from_edge = in.sprite_semidimensions + in.border_dimensions + in.blur_border_dimensions - abs(in.texture_coordinates_by_pixel);
+	let coverage = min(blurred_step(in.blur_border_dimensions.x, dist_from_edge.x), blurred_step(in.blur_border_dimensions.y, dist_from_edge.y));
+	// Blend the frame into the image along the image's own edge; unframed images are not blended.
+	let dist_from_image_edge = in.sprite_semidimensions + in.blur_border_dimensions - abs(in.texture_coordinates_by_pixel);
+	let image_coverage = select(1., min(blurred_step(in.blur_border_dimensions.x, dist_from_image_edge.x), blurred_step(in.blur_border_dimensions.y, dist_from_image_edge.y)), any(in.border_dimensions > vec2f(0.)));
+	let color = mix(in.border_color, texture_color, image_coverage);
+	return encode(vec4f((1. - in.is_selected) * color.rgb + in.is_selected * vec3f(0.5 * color.rgb + 0.5 * viewport.selection_color.rgb), color.a * coverage));
 }
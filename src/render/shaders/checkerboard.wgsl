@@ -0,0 +1,69 @@
+// Copyright (C) 2023 Aaron Yeoh Cruz <zeyonaut@gmail.com>
+// SPDX-License-Identifier: MPL-2.0
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+struct ViewportUniform {
+	position: vec2f,
+	size: vec2f,
+	scale: f32,
+	tilt: f32,
+	is_gamma_encoded: f32,
+	is_round_capped: f32,
+	selection_color: vec4f,
+	scale_factor: f32,
+}
+
+@group(0) @binding(0) var<uniform> viewport: ViewportUniform;
+
+struct Instance {
+	@location(0) position: vec2f,
+	@location(1) dimensions: vec2f,
+	@location(2) cell_size: f32,
+	@location(3) light_color: vec4f,
+	@location(4) dark_color: vec4f,
+}
+
+struct ClipVertex {
+	@builtin(position) position: vec4f,
+	@location(0) cell_size: f32,
+	@location(1) light_color: vec4f,
+	@location(2) dark_color: vec4f,
+}
+
+var<private> vertices: array<vec2f, 4> = array<vec2f, 4>(
+	vec2f(0., 0.),
+	vec2f(1., 0.),
+	vec2f(1., 1.),
+	vec2f(0., 1.),
+);
+
+@vertex
+fn vs_main(instance: Instance, @builtin(vertex_index) index: u32) -> ClipVertex {
+	var out: ClipVertex;
+	out.position = vec4f((vertices[index] * instance.dimensions + instance.position) / viewport.size * vec2f(2., -2.) + vec2f(-1., 1.), 0., 1.);
+	out.cell_size = instance.cell_size;
+	out.light_color = instance.light_color;
+	out.dark_color = instance.dark_color;
+	return out;
+}
+
+// IEC 61966-2-1
+fn linear_to_srgb(color: vec3f) -> vec3f {
+  return mix(1.055 * pow(color, vec3(1. / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Gamma-encodes the color if the surface blends in gamma space.
+fn encode(color: vec4f) -> vec4f {
+	return select(color, vec4f(linear_to_srgb(color.rgb), color.a), viewport.is_gamma_encoded > 0.5);
+}
+
+@fragment
+fn fs_main(in: ClipVertex) -> @location(0) vec4f {
+	// The cells are fixed to the screen, so that they never look like part of the canvas.
+	let cell = floor(in.position.xy / in.cell_size);
+	let is_dark = (i32(cell.x) + i32(cell.y)) % 2 != 0;
+	return encode(select(in.light_color, in.dark_color, is_dark));
+}
@@ -10,6 +10,10 @@ struct ViewportUniform {
 	size: vec2f,
 	scale: f32,
 	tilt: f32,
+	is_gamma_encoded: f32,
+	is_round_capped: f32,
+	selection_color: vec4f,
+	scale_factor: f32,
 }
 
 @group(0) @binding(0) var<uniform> viewport: ViewportUniform;
@@ -60,18 +64,32 @@ fn srgb_to_linear(color: vec3f) -> vec3f {
 
 const PI: f32 = 3.141592653589793238462643383279;
 
+// Steps across the edge over the width of a pixel, however the value is scaled, so that the edge is antialiased.
 fn blurred_step(edge: f32, value: f32) -> f32 {
-	let radius = 1./sqrt(2.) * length(vec2(dpdx(value), dpdy(value)));
+	let radius = 0.5 * fwidth(value);
 	return smoothstep(edge - radius, edge + radius, value);
 }
 
+// IEC 61966-2-1
+fn linear_to_srgb(color: vec3f) -> vec3f {
+  return mix(1.055 * pow(color, vec3(1. / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Gamma-encodes the color if the surface blends in gamma space.
+fn encode(color: vec4f) -> vec4f {
+	return select(color, vec4f(linear_to_srgb(color.rgb), color.a), viewport.is_gamma_encoded > 0.5);
+}
+
 @fragment
 fn fs_main(in: ClipVertex) -> @location(0) vec4f {
 	let vector = in.position.xy - in.origin;
 	let scaled_vector = vector / in.radius;
-	let s = (1. - 2. * scaled_vector.y) / (2. + sqrt(3.) * scaled_vector.x - scaled_vector.y);
-	let v = (2. + sqrt(3.) * scaled_vector.x - scaled_vector.y) / 3.;
+	// The antialiased edges extend slightly beyond the triangle, where saturation and value must be clamped to stay meaningful.
+	let s = saturate((1. - 2. * scaled_vector.y) / max(2. + sqrt(3.) * scaled_vector.x - scaled_vector.y, 1e-6));
+	let v = saturate((2. + sqrt(3.) * scaled_vector.x - scaled_vector.y) / 3.);
 	let color_hsv = vec3(in.hue, s, v);
 	let color = srgb_to_linear(hsv_to_srgb(color_hsv));
-	return vec4(color, (1. - blurred_step(in.radius / 2., vector.y * 2. / 2.)) * (1. - blurred_step(in.radius / 2., (-sqrt(3.) * vector.x - vector.y) / 2.)) * (1. - blurred_step(in.radius / 2., (sqrt(3.) * vector.x - vector.y) / 2.)));
+	// Each edge lies at half the radius from the center, and is measured by the distance along its outward normal.
+	let coverage = (1. - blurred_step(in.radius / 2., vector.y)) * (1. - blurred_step(in.radius / 2., (-sqrt(3.) * vector.x - vector.y) / 2.)) * (1. - blurred_step(in.radius / 2., (sqrt(3.) * vector.x - vector.y) / 2.));
+	return encode(vec4(color, coverage));
 }
@@ -10,6 +10,10 @@ struct ViewportUniform {
 	size: vec2f,
 	scale: f32,
 	tilt: f32,
+	is_gamma_encoded: f32,
+	is_round_capped: f32,
+	selection_color: vec4f,
+	scale_factor: f32,
 }
 
 @group(0) @binding(0) var<uniform> viewport: ViewportUniform;
@@ -53,10 +57,20 @@ fn blurred_step(edge: f32, value: f32) -> f32 {
 	return smoothstep(edge - radius, edge + radius, value);
 }
 
+// IEC 61966-2-1
+fn linear_to_srgb(color: vec3f) -> vec3f {
+  return mix(1.055 * pow(color, vec3(1. / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Gamma-encodes the color if the surface blends in gamma space.
+fn encode(color: vec4f) -> vec4f {
+	return select(color, vec4f(linear_to_srgb(color.rgb), color.a), viewport.is_gamma_encoded > 0.5);
+}
+
 @fragment
 fn fs_main(in: ClipVertex) -> @location(0) vec4f {
 	let rect_vertex = vec2(0.5, 0.5) * in.dimensions + vec2(-in.radius);
 	let rect_center = vec2(in.radius) + in.sposition + rect_vertex;
 	let frag_position = in.position.xy - rect_center;
-	return vec4(in.color.rgb, in.color.a * (1. - blurred_step(0., length(max(abs(frag_position), rect_vertex) - rect_vertex) - in.radius)));
+	return encode(vec4(in.color.rgb, in.color.a * (1. - blurred_step(0., length(max(abs(frag_position), rect_vertex) - rect_vertex) - in.radius))));
 }
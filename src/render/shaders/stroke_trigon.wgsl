@@ -10,6 +10,10 @@ struct ViewportUniform {
 	size: vec2f,
 	scale: f32,
 	tilt: f32,
+	is_gamma_encoded: f32,
+	is_round_capped: f32,
+	selection_color: vec4f,
+	scale_factor: f32,
 }
 
 struct SelectionTransformation {
@@ -17,39 +21,48 @@ struct SelectionTransformation {
 	center_of_transformation: vec2f,
 	rotation: f32,
 	dilation: f32,
+	stretch: vec2f,
 }
 
 struct Extension {
 	translation: vec2f,
 	rotation: f32,
 	dilation: f32,
-	color: vec3f,
+	color: vec4f,
 	is_selected: f32,
 }
 
 @group(0) @binding(0) var<uniform> viewport: ViewportUniform;
 @group(1) @binding(0) var<uniform> selection_transformation: SelectionTransformation;
 @group(2) @binding(0) var<storage> extensions: array<Extension>;
+@group(3) @binding(0) var grain_texture: texture_2d<f32>;
+@group(3) @binding(1) var grain_sampler: sampler;
 
 
 struct Vertex {
 	@location(0) position: vec2f,
 	@location(1) polarity: f32,
 	@location(2) extension_index: u32,
+	// How far past the end of the stroke the vertex lies, in stroke radii; zero outside its caps.
+	@location(3) cap: f32,
 }
 
 struct ClipVertex {
 	@builtin(position) position: vec4f,
-	@location(0) color: vec3f,
+	@location(0) color: vec4f,
 	@location(1) polarity: f32,
+	// Canvas coordinates of the vertex, at which the grain is sampled.
+	@location(2) canvas_position: vec2f,
+	@location(3) cap: f32,
+	@location(4) is_selected: f32,
 }
 
 fn rotate(v: vec2f, angle: f32) -> vec2f {
 	return vec2(cos(angle) * v.x - sin(angle) * v.y, sin(angle) * v.x + cos(angle) * v.y);
 }
 
-fn conform_about(v: vec2f, center: vec2f, rotation: f32, dilation: f32) -> vec2f {
-	return center + rotate(v - center, rotation) * dilation;
+fn conform_about(v: vec2f, center: vec2f, rotation: f32, dilation: f32, stretch: vec2f) -> vec2f {
+	return center + rotate(v - center, rotation) * dilation * stretch;
 }
 
 // IEC 61966-2-1
@@ -64,13 +77,16 @@ fn vs_main(vertex: Vertex) -> ClipVertex {
 	let extension = extensions[vertex.extension_index];
 
 	let transformed_position = extension.translation + rotate(vertex.position, extension.rotation) * extension.dilation;
-	let selection_transformed_position = selection_transformation.translation + conform_about(transformed_position, selection_transformation.center_of_transformation, selection_transformation.rotation, selection_transformation.dilation);
+	let selection_transformed_position = selection_transformation.translation + conform_about(transformed_position, selection_transformation.center_of_transformation, selection_transformation.rotation, selection_transformation.dilation, selection_transformation.stretch);
 	
 	let position = (1. - extension.is_selected) * transformed_position + extension.is_selected * selection_transformed_position;
 
 	out.position = vec4(rotate((position - viewport.position) * viewport.scale, -viewport.tilt) / viewport.size * vec2(2., -2.), 0., 1.);
-	out.color = (1. - extension.is_selected) * extension.color + extension.is_selected * (0.25 * extension.color + 0.75 * srgb_to_linear(vec3f(0x28./0xff., 0xc2./0xff., 0xff./0xff.)));
+	out.color = extension.color;
 	out.polarity = vertex.polarity;
+	out.canvas_position = position;
+	out.cap = vertex.cap;
+	out.is_selected = extension.is_selected;
 	
 	return out;
 }
@@ -85,7 +101,33 @@ fn blurred_step_positive(value: f32) -> f32 {
 	return smoothstep(1. - radius, 1., value);
 }
 
+// IEC 61966-2-1
+fn linear_to_srgb(color: vec3f) -> vec3f {
+  return mix(1.055 * pow(color, vec3(1. / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Gamma-encodes the color if the surface blends in gamma space.
+fn encode(color: vec4f) -> vec4f {
+	return select(color, vec4f(linear_to_srgb(color.rgb), color.a), viewport.is_gamma_encoded > 0.5);
+}
+
+// The width of the outline drawn inside the edges of selected strokes, in logical pixels.
+const SELECTION_OUTLINE_WIDTH: f32 = 2.;
+
 @fragment
 fn fs_main(in: ClipVertex) -> @location(0) vec4f {
-	return vec4f(in.color, blurred_step_negative(in.polarity) * (1. - blurred_step_positive(in.polarity)));
+	// The grain tiles across the canvas with one texel per canvas unit, and is stored in the alpha channel.
+	let grain = textureSample(grain_texture, grain_sampler, in.canvas_position / vec2f(textureDimensions(grain_texture))).a;
+	let edge_coverage = blurred_step_negative(in.polarity) * (1. - blurred_step_positive(in.polarity));
+	// Within a cap, the polarity and cap coordinates measure the offset from the end of the stroke in radii, so the cap is rounded by fading out beyond a unit distance.
+	// Flat caps are cut at the end of the stroke instead.
+	let cap_coverage = select(0., 1. - blurred_step_positive(length(vec2f(in.polarity, in.cap))), viewport.is_round_capped > 0.5);
+	let coverage = select(edge_coverage, cap_coverage, in.cap > 0.);
+	// The distance to the edge of the stroke is measured on screen, so that the outline of a selected stroke keeps the same width at every zoom.
+	let radial = select(abs(in.polarity), length(vec2f(in.polarity, in.cap)), in.cap > 0.);
+	let edge_distance = (1. - radial) / length(vec2(dpdx(radial), dpdy(radial)));
+	let outline_width = SELECTION_OUTLINE_WIDTH * viewport.scale_factor;
+	let outline = in.is_selected * (1. - smoothstep(outline_width - 1., outline_width, edge_distance));
+	let color = mix(in.color, vec4f(viewport.selection_color.rgb, 1.), outline);
+	return encode(vec4f(color.rgb, color.a * grain * coverage));
 }
@@ -10,6 +10,10 @@ struct ViewportUniform {
 	size: vec2f,
 	scale: f32,
 	tilt: f32,
+	is_gamma_encoded: f32,
+	is_round_capped: f32,
+	selection_color: vec4f,
+	scale_factor: f32,
 }
 
 @group(0) @binding(0) var<uniform> viewport: ViewportUniform;
@@ -49,7 +53,17 @@ fn blurred_step_positive(value: f32) -> f32 {
 	return smoothstep(1. - radius, 1., value);
 }
 
+// IEC 61966-2-1
+fn linear_to_srgb(color: vec3f) -> vec3f {
+  return mix(1.055 * pow(color, vec3(1. / 2.4)) - 0.055, color * 12.92, step(color, vec3(0.0031308)));
+}
+
+// Gamma-encodes the color if the surface blends in gamma space.
+fn encode(color: vec4f) -> vec4f {
+	return select(color, vec4f(linear_to_srgb(color.rgb), color.a), viewport.is_gamma_encoded > 0.5);
+}
+
 @fragment
 fn fs_main(in: ClipVertex) -> @location(0) vec4f {
-	return vec4f(in.color.rgb, in.color.a * blurred_step_negative(in.polarity) * (1. - blurred_step_positive(in.polarity)));
+	return encode(vec4f(in.color.rgb, in.color.a * blurred_step_negative(in.polarity) * (1. - blurred_step_positive(in.polarity))));
 }
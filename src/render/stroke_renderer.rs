@@ -5,9 +5,18 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::{borrow::Cow, ops::Range};
-
-use super::{dynamic_buffer::DynamicBuffer, dynamic_storage_buffer::DynamicStorageBuffer, instance_renderer::InstanceRenderer, texture::Texture, uniform_buffer::UniformBuffer, vertex_attributes::VertexAttributes, ViewportUniform};
+use std::{borrow::Cow, num::NonZeroU32, ops::Range, time::Instant};
+
+use super::{
+	dynamic_buffer::DynamicBuffer,
+	dynamic_storage_buffer::DynamicStorageBuffer,
+	grain::{Grain, GRAIN_TEXTURE_SIZE},
+	instance_renderer::InstanceRenderer,
+	texture::Texture,
+	uniform_buffer::UniformBuffer,
+	vertex_attributes::VertexAttributes,
+	RenderStatistics, ViewportUniform,
+};
 use crate::{
 	canvas::{Canvas, IncompleteStroke},
 	utility::{Tracked, Vex, Vx, Zero},
@@ -20,6 +29,8 @@ pub struct SelectionTransformation {
 	pub center_of_transformation: Vex<2, Vx>,
 	pub rotation: f32,
 	pub dilation: f32,
+	// Scales each axis separately, after rotation and dilation.
+	pub stretch: Vex<2, f32>,
 }
 
 impl Default for SelectionTransformation {
@@ -29,6 +40,7 @@ impl Default for SelectionTransformation {
 			center_of_transformation: Vex::ZERO,
 			rotation: 0.,
 			dilation: 1.,
+			stretch: Vex([1., 1.]),
 		}
 	}
 }
@@ -43,10 +55,13 @@ struct ImageInstance {
 	pub sprite_position: [f32; 2],
 	pub sprite_dimensions: [f32; 2],
 	pub is_selected: f32,
+	pub border_width: Vx,
+	pub border_color: [f32; 4],
+	pub is_pixelated: f32,
 }
 
-impl VertexAttributes<7> for ImageInstance {
-	const ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32, 2 => Float32, 3 => Float32x2, 4 => Float32x2, 5 => Float32x2, 6 => Float32,];
+impl VertexAttributes<10> for ImageInstance {
+	const ATTRIBUTES: [wgpu::VertexAttribute; 10] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32, 2 => Float32, 3 => Float32x2, 4 => Float32x2, 5 => Float32x2, 6 => Float32, 7 => Float32, 8 => Float32x4, 9 => Float32,];
 }
 
 pub struct CanvasRenderer {
@@ -57,7 +72,7 @@ pub struct CanvasRenderer {
 }
 
 impl CanvasRenderer {
-	pub fn new(device: &wgpu::Device, texture_format: wgpu::TextureFormat, viewport_buffer: &UniformBuffer<ViewportUniform>, sample_count: u32) -> Self {
+	pub fn new(device: &wgpu::Device, texture_format: wgpu::TextureFormat, viewport_buffer: &UniformBuffer<ViewportUniform>, sample_count: u32, grain: Option<Grain>) -> Self {
 		let selection_transformation_uniform_buffer = UniformBuffer::new(device, 0, Default::default());
 
 		Self {
@@ -79,13 +94,14 @@ impl CanvasRenderer {
 				viewport_buffer,
 				&selection_transformation_uniform_buffer,
 				sample_count,
+				grain,
 			),
 			selection_transformation_uniform_buffer,
 			image_instance_assembly: Vec::new(),
 		}
 	}
 
-	pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, canvas: &mut Canvas, current_stroke: Option<&IncompleteStroke>) -> (Vec<usize>, Range<u32>) {
+	pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, canvas: &mut Canvas, current_stroke: Option<&IncompleteStroke>, statistics: &mut RenderStatistics) -> (Vec<Option<usize>>, Range<u32>) {
 		// We update the selection transformation uniform if necessary.
 		if let Some(selection_transformation) = canvas.selection_transformation.read_if_dirty() {
 			self.selection_transformation_uniform_buffer.write(queue, *selection_transformation);
@@ -97,7 +113,7 @@ impl CanvasRenderer {
 		let instance_offset = canvas.base_dirty_image_index.min(canvas.images.len());
 		for (i, image) in canvas.images[0..instance_offset].iter_mut().enumerate() {
 			let image_texture_index = if let Some(image) = image.read_if_dirty() {
-				if let Some(texture) = canvas.textures.get(image.texture_index) {
+				if let Some(Some(texture)) = canvas.textures.get(image.texture_index) {
 					self.image_instance_renderer.prepare(
 						device,
 						queue,
@@ -110,12 +126,16 @@ impl CanvasRenderer {
 							sprite_position: [0.; 2],
 							sprite_dimensions: [texture.extent.width as f32, texture.extent.height as f32],
 							is_selected: if image.is_selected { 1. } else { 0. },
+							border_width: image.border_width,
+							border_color: image.border_color.to_lrgba().0,
+							is_pixelated: if image.is_pixelated { 1. } else { 0. },
 						}],
 					)
 				}
-				image.texture_index
+				image.is_visible.then_some(image.texture_index)
 			} else {
-				image.read().texture_index
+				let image = image.read();
+				image.is_visible.then_some(image.texture_index)
 			};
 			image_texture_indices.push(image_texture_index);
 		}
@@ -126,7 +146,11 @@ impl CanvasRenderer {
 		self.image_instance_assembly.clear();
 
 		for image in invalidated_images.iter_mut().map(Tracked::read) {
-			let texture = &canvas.textures[image.texture_index];
+			// An image whose texture is missing still takes up an instance, so that the instances stay in step with the images.
+			let sprite_dimensions = match canvas.textures.get(image.texture_index) {
+				Some(Some(texture)) => [texture.extent.width as f32, texture.extent.height as f32],
+				_ => [1.; 2],
+			};
 
 			self.image_instance_assembly.push(ImageInstance {
 				position: image.position,
@@ -134,11 +158,14 @@ impl CanvasRenderer {
 				dilation: image.dilation,
 				dimensions: image.dimensions,
 				sprite_position: [0.; 2],
-				sprite_dimensions: [texture.extent.width as f32, texture.extent.height as f32],
+				sprite_dimensions,
 				is_selected: image.is_selected as u8 as _,
+				border_width: image.border_width,
+				border_color: image.border_color.to_lrgba().0,
+				is_pixelated: image.is_pixelated as u8 as _,
 			});
 
-			image_texture_indices.push(image.texture_index);
+			image_texture_indices.push(image.is_visible.then_some(image.texture_index));
 		}
 
 		self.image_instance_renderer.prepare(device, queue, instance_offset, &self.image_instance_assembly);
@@ -147,16 +174,23 @@ impl CanvasRenderer {
 		canvas.base_dirty_image_index = canvas.images.len();
 
 		// Finally, we prepare the stroke renderer.
-		let stroke_index_range = self.stroke_renderer.prepare(device, queue, canvas, current_stroke);
+		let stroke_index_range = self.stroke_renderer.prepare(device, queue, canvas, current_stroke, statistics);
 
 		(image_texture_indices, stroke_index_range)
 	}
 
-	pub fn render<'r>(&'r self, render_pass: &mut wgpu::RenderPass<'r>, textures: &'r [Texture], (image_texture_indices, stroke_index_range): (Vec<usize>, Range<u32>)) {
+	// The sizes of the image instance buffer and the stroke vertex, index, and extension buffers, in bytes.
+	pub fn buffer_sizes(&self) -> [u64; 4] {
+		let [vertex_size, index_size, extension_size] = self.stroke_renderer.buffer_sizes();
+		[self.image_instance_renderer.buffer_size(), vertex_size, index_size, extension_size]
+	}
+
+	// Images without a texture index are hidden, and aren't drawn.
+	pub fn render<'r>(&'r self, render_pass: &mut wgpu::RenderPass<'r>, textures: &'r [Option<Texture>], (image_texture_indices, stroke_index_range): (Vec<Option<usize>>, Range<u32>)) {
 		self.selection_transformation_uniform_buffer.activate(render_pass, 1);
 
 		for (i, texture_index) in image_texture_indices.iter().copied().enumerate() {
-			if let Some(texture) = textures.get(texture_index) {
+			if let Some(Some(texture)) = texture_index.and_then(|texture_index| textures.get(texture_index)) {
 				texture.activate(render_pass, 2);
 				self.image_instance_renderer.render(render_pass, i as _..i as u32 + 1);
 			}
@@ -172,8 +206,10 @@ pub struct StrokeExtension {
 	pub translation: [Vx; 2],
 	pub rotation: f32,
 	pub dilation: f32,
-	pub color: [f32; 3],
+	pub color: [f32; 4],
 	pub is_selected: f32,
+	// Pads the extension to the 16-byte alignment of its shader counterpart.
+	pub padding: [f32; 3],
 }
 
 #[repr(C)]
@@ -182,10 +218,11 @@ pub struct StrokeVertex {
 	pub position: [Vx; 2],
 	pub polarity: f32,
 	pub extension_index: u32,
+	pub cap: f32,
 }
 
-impl VertexAttributes<3> for StrokeVertex {
-	const ATTRIBUTES: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32, 2 => Uint32];
+impl VertexAttributes<4> for StrokeVertex {
+	const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32, 2 => Uint32, 3 => Float32];
 }
 
 pub struct StrokeRenderer {
@@ -193,9 +230,20 @@ pub struct StrokeRenderer {
 	vertex_buffer: DynamicBuffer<StrokeVertex>,
 	index_buffer: DynamicBuffer<u32>,
 	extension_storage_buffer: DynamicStorageBuffer<StrokeExtension>,
+	grain_texture: Texture,
 	vertex_assembly: Vec<StrokeVertex>,
 	index_assembly: Vec<u32>,
 	extension_assembly: Vec<StrokeExtension>,
+	current_stroke_upload: Option<CurrentStrokeUpload>,
+}
+
+// Where the geometry of the current stroke was last written, and how much of its settled geometry was written there.
+struct CurrentStrokeUpload {
+	start: Instant,
+	vertex_base: usize,
+	index_base: usize,
+	vertex_count: usize,
+	index_count: usize,
 }
 
 impl StrokeRenderer {
@@ -209,11 +257,22 @@ impl StrokeRenderer {
 		viewport_buffer: &UniformBuffer<ViewportUniform>,
 		selection_transformation_uniform_buffer: &UniformBuffer<SelectionTransformation>,
 		sample_count: u32,
+		grain: Option<Grain>,
 	) -> Self {
 		let vertex_buffer = DynamicBuffer::<StrokeVertex>::new(device, wgpu::BufferUsages::VERTEX, 1 << 16);
 		let index_buffer = DynamicBuffer::<u32>::new(device, wgpu::BufferUsages::INDEX, 1 << 16);
 		let extension_storage_buffer = DynamicStorageBuffer::<StrokeExtension>::new(device, 1 << 16);
 
+		// Without a grain, strokes sample a single opaque texel, leaving them flat.
+		let grain_bind_group_layout = Texture::bind_group_layout(device);
+		let grain_texture = match grain {
+			Some(grain) => {
+				let size = NonZeroU32::new(GRAIN_TEXTURE_SIZE).unwrap();
+				Texture::new_tiled(device, [size; 2], grain.generate(), &grain_bind_group_layout)
+			},
+			None => Texture::new_tiled(device, [NonZeroU32::MIN; 2], vec![0xff; 4], &grain_bind_group_layout),
+		};
+
 		let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
 			label: None,
 			source: wgpu::ShaderSource::Wgsl(shader_source.into()),
@@ -221,7 +280,12 @@ impl StrokeRenderer {
 
 		let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
 			label: None,
-			bind_group_layouts: &[&viewport_buffer.bind_group_layout, &selection_transformation_uniform_buffer.bind_group_layout, &extension_storage_buffer.bind_group_layout],
+			bind_group_layouts: &[
+				&viewport_buffer.bind_group_layout,
+				&selection_transformation_uniform_buffer.bind_group_layout,
+				&extension_storage_buffer.bind_group_layout,
+				&grain_bind_group_layout,
+			],
 			push_constant_ranges: &[],
 		});
 
@@ -267,20 +331,24 @@ impl StrokeRenderer {
 			vertex_buffer,
 			index_buffer,
 			extension_storage_buffer,
+			grain_texture,
 			vertex_assembly: Vec::new(),
 			index_assembly: Vec::new(),
 			extension_assembly: Vec::new(),
+			current_stroke_upload: None,
 		}
 	}
 
-	pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, canvas: &mut Canvas, current_stroke: Option<&IncompleteStroke>) -> Range<u32> {
+	pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, canvas: &mut Canvas, current_stroke: Option<&IncompleteStroke>, statistics: &mut RenderStatistics) -> Range<u32> {
+		// The grain texture is only uploaded the first time.
+		self.grain_texture.prepare(queue);
+
 		// First, we iterate through the uninvalidated strokes and update their extensions if necessary.
 		let mut vertex_offset = 0;
 		let mut index_offset = 0;
 		for (i, stroke) in canvas.strokes[0..canvas.base_dirty_stroke_index].iter_mut().enumerate() {
 			if let Some(stroke) = stroke.read_if_dirty() {
-				let lrgba = stroke.color.to_lrgba();
-				let color = [0, 1, 2].map(|n: usize| lrgba.0[n]);
+				let color = stroke.color.to_lrgba().0;
 				self.extension_storage_buffer.write(
 					device,
 					queue,
@@ -291,11 +359,15 @@ impl StrokeRenderer {
 						dilation: stroke.dilation,
 						color,
 						is_selected: stroke.is_selected as u8 as _,
+						padding: [0.; 3],
 					}],
 				);
+				statistics.uploaded_stroke_count += 1;
+			}
+			if stroke.is_visible {
+				vertex_offset += stroke.vertices.len();
+				index_offset += stroke.relative_indices.len();
 			}
-			vertex_offset += stroke.vertices.len();
-			index_offset += stroke.relative_indices.len();
 		}
 
 		// Then, we iterate through the invalidated strokes and generate everything: vertices/indices/extensions.
@@ -311,61 +383,102 @@ impl StrokeRenderer {
 		for (i, invalidated_stroke) in invalidated_strokes.iter_mut().map(Tracked::read).enumerate() {
 			let current_extension_index = (extension_offset + i) as u32;
 			let current_index_base = (vertex_offset + self.vertex_assembly.len()) as u32;
-			let lrgba = invalidated_stroke.color.to_lrgba();
-			let color = [0, 1, 2].map(|n: usize| lrgba.0[n]);
-			self.vertex_assembly.extend(invalidated_stroke.vertices.iter().map(|(position, polarity)| StrokeVertex {
-				position: position.0,
-				polarity: *polarity,
-				extension_index: current_extension_index,
-			}));
-			self.index_assembly.extend(invalidated_stroke.relative_indices.iter().map(|n| current_index_base + n));
+			let color = invalidated_stroke.color.to_lrgba().0;
+			// Hidden strokes keep their extension, so that extensions stay in step with the strokes, but have no geometry.
+			if invalidated_stroke.is_visible {
+				self.vertex_assembly.extend(invalidated_stroke.vertices.iter().map(|(position, [polarity, cap])| StrokeVertex {
+					position: position.0,
+					polarity: *polarity,
+					extension_index: current_extension_index,
+					cap: *cap,
+				}));
+				self.index_assembly.extend(invalidated_stroke.relative_indices.iter().map(|n| current_index_base + n));
+			}
 			self.extension_assembly.push(StrokeExtension {
 				translation: invalidated_stroke.position.0,
 				rotation: invalidated_stroke.orientation,
 				dilation: invalidated_stroke.dilation,
 				color,
 				is_selected: if invalidated_stroke.is_selected { 1. } else { 0. },
+				padding: [0.; 3],
 			});
 		}
 
-		// In addition, we append the generated vertices/indices/extension of the current stroke to the assembly buffers.
+		// In addition, we append the extension of the current stroke to the assembly buffers.
+		let current_extension_index = (extension_offset + invalidated_strokes.len()) as u32;
 		if let Some(current_stroke) = current_stroke {
-			let stroke = current_stroke.preview();
-			let current_extension_index = (extension_offset + invalidated_strokes.len()) as u32;
-			let current_index_offset = (vertex_offset + self.vertex_assembly.len()) as u32;
-			let lrgba = stroke.color.to_lrgba();
-			let color = [0, 1, 2].map(|n: usize| lrgba.0[n]);
-			self.vertex_assembly.extend(stroke.vertices.iter().map(|(position, polarity)| StrokeVertex {
-				position: position.0,
-				polarity: *polarity,
-				extension_index: current_extension_index,
-			}));
-			self.index_assembly.extend(stroke.relative_indices.iter().map(|n| current_index_offset + n));
 			self.extension_assembly.push(StrokeExtension {
-				translation: stroke.position.0,
-				rotation: stroke.orientation,
-				dilation: stroke.dilation,
-				color,
-				is_selected: if stroke.is_selected { 1. } else { 0. },
+				translation: current_stroke.position.0,
+				rotation: 0.,
+				dilation: 1.,
+				color: current_stroke.color.to_lrgba().0,
+				is_selected: 0.,
+				padding: [0.; 3],
 			});
 		}
 
-		// Finally, we write the assembly buffers to the device buffers.
+		// We write the assembly buffers to the device buffers.
 		self.vertex_buffer.write(device, queue, vertex_offset, &self.vertex_assembly);
 		self.index_buffer.write(device, queue, index_offset, &self.index_assembly);
 		self.extension_storage_buffer.write(device, queue, extension_offset, &self.extension_assembly);
+		statistics.uploaded_stroke_count += invalidated_strokes.len();
+		statistics.uploaded_vertex_count += self.vertex_assembly.len();
+		statistics.uploaded_index_count += self.index_assembly.len();
 
 		// We mark the entire stroke array as uninvalidated.
 		canvas.base_dirty_stroke_index = canvas.strokes.len();
 
+		let index_count = index_offset + self.index_assembly.len();
+		let Some(current_stroke) = current_stroke else {
+			self.current_stroke_upload = None;
+			return 0..index_count as u32;
+		};
+
+		// Finally, we write the geometry of the current stroke after everything else.
+		// Its settled geometry only ever grows, so if it was written at the same place last frame, we only write what is new, followed by the predicted geometry.
+		let vertex_base = vertex_offset + self.vertex_assembly.len();
+		let index_base = index_count;
+		let (vertex_count, index_count) = match &self.current_stroke_upload {
+			Some(upload) if upload.start == current_stroke.start && upload.vertex_base == vertex_base && upload.index_base == index_base => (upload.vertex_count, upload.index_count),
+			_ => (0, 0),
+		};
+		let (predicted_vertices, predicted_indices) = current_stroke.predicted_geometry();
+
+		self.vertex_assembly.clear();
+		self.vertex_assembly.extend(current_stroke.vertices[vertex_count..].iter().chain(&predicted_vertices).map(|(position, [polarity, cap])| StrokeVertex {
+			position: position.0,
+			polarity: *polarity,
+			extension_index: current_extension_index,
+			cap: *cap,
+		}));
+		self.index_assembly.clear();
+		self.index_assembly.extend(current_stroke.relative_indices[index_count..].iter().chain(&predicted_indices).map(|n| vertex_base as u32 + n));
+		self.vertex_buffer.write(device, queue, vertex_base + vertex_count, &self.vertex_assembly);
+		self.index_buffer.write(device, queue, index_base + index_count, &self.index_assembly);
+		statistics.uploaded_vertex_count += self.vertex_assembly.len();
+		statistics.uploaded_index_count += self.index_assembly.len();
+
+		self.current_stroke_upload = Some(CurrentStrokeUpload {
+			start: current_stroke.start,
+			vertex_base,
+			index_base,
+			vertex_count: current_stroke.vertices.len(),
+			index_count: current_stroke.relative_indices.len(),
+		});
+
 		// We return the range of indices to be rendered.
-		0..(self.index_assembly.len() + index_offset) as u32
+		0..(index_base + current_stroke.relative_indices.len() + predicted_indices.len()) as u32
+	}
+
+	pub fn buffer_sizes(&self) -> [u64; 3] {
+		[self.vertex_buffer.buffer.size(), self.index_buffer.buffer.size(), self.extension_storage_buffer.buffer.size()]
 	}
 
 	// Precondition: bind group 0 is set to the viewport.
 	pub fn render<'r>(&'r self, render_pass: &mut wgpu::RenderPass<'r>, index_range: Range<u32>) {
 		render_pass.set_pipeline(&self.render_pipeline);
 		render_pass.set_bind_group(2, &self.extension_storage_buffer.bind_group, &[]);
+		self.grain_texture.activate(render_pass, 3);
 		render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
 		render_pass.set_index_buffer(self.index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint32);
 		render_pass.draw_indexed(index_range, 0, 0..1)
@@ -5,7 +5,7 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 use crate::utility::{Px, Vex};
 
@@ -31,11 +31,13 @@ pub enum Align {
 }
 
 impl TextRenderer {
-	pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, surface_format: wgpu::TextureFormat, sample_count: u32) -> Self {
+	pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, surface_format: wgpu::TextureFormat, is_blending_linear: bool, sample_count: u32, font: Option<&str>) -> Self {
 		let mut font_system = glyphon::FontSystem::new_with_fonts([glyphon::fontdb::Source::Binary(Arc::new(include_bytes!("../../ext/dejavu-sans-2.37/DejaVuSans.ttf").as_slice()))]);
-		font_system.db_mut().set_sans_serif_family("DejaVu Sans");
+		let family = font.and_then(|font| load_font_family(&mut font_system, font)).unwrap_or_else(|| "DejaVu Sans".to_owned());
+		font_system.db_mut().set_sans_serif_family(family);
 		let swash_cache = glyphon::SwashCache::new();
-		let mut text_atlas = glyphon::TextAtlas::new(device, queue, surface_format);
+		let color_mode = if is_blending_linear { glyphon::ColorMode::Accurate } else { glyphon::ColorMode::Web };
+		let mut text_atlas = glyphon::TextAtlas::with_color_mode(device, queue, surface_format, color_mode);
 		let text_renderer = glyphon::TextRenderer::new(
 			&mut text_atlas,
 			device,
@@ -88,6 +90,19 @@ impl TextRenderer {
 	}
 }
 
+// Loads a font from a file, or else finds an installed font of that family, returning the family name it can be selected by.
+fn load_font_family(font_system: &mut glyphon::FontSystem, font: &str) -> Option<String> {
+	let database = font_system.db_mut();
+	if Path::new(font).is_file() {
+		let data = std::fs::read(font).ok()?;
+		let ids = database.load_font_source(glyphon::fontdb::Source::Binary(Arc::new(data)));
+		Some(database.face(*ids.first()?)?.families.first()?.0.clone())
+	} else {
+		database.load_system_fonts();
+		database.faces().flat_map(|face| &face.families).map(|(family, _)| family).find(|family| family.eq_ignore_ascii_case(font)).cloned()
+	}
+}
+
 impl TextInstance {
 	pub fn new(renderer: &mut TextRenderer, text: &str, font_size: f32, line_height_factor: f32, align: Option<Align>, position: Vex<2, Px>, anchors: [f32; 2]) -> Self {
 		let line_height = line_height_factor * font_size;
@@ -5,15 +5,49 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::num::NonZeroU32;
+use std::{cell::OnceCell, num::NonZeroU32};
 
+// Textures hold straight (not premultiplied) 8-bit RGBA, with sRGB-encoded colors, as images are stored in PNG and pasted from the clipboard.
+// Sampling decodes their colors to linear, in which shaders filter and blend them; since alpha is straight, colors are weighted by it when texels are mixed.
 pub struct Texture {
 	rgba: Option<Vec<u8>>,
+	// Textures are immutable, so their PNG encoding is kept to be reused by later saves.
+	pub encoded_png: OnceCell<Vec<u8>>,
 	pub texture: wgpu::Texture,
 	pub extent: wgpu::Extent3d,
 	bind_group: wgpu::BindGroup,
 }
 
+// Shrinks an RGBA image by the smallest whole factor that fits both of its dimensions within a maximum, averaging each block of pixels.
+fn downscale_to_fit(dimensions: [NonZeroU32; 2], image: Vec<u8>, max_dimension: u32) -> ([NonZeroU32; 2], Vec<u8>) {
+	let [width, height] = dimensions.map(|x| x.get() as usize);
+	let factor = width.max(height).div_ceil(max_dimension.max(1) as usize);
+	if factor <= 1 {
+		return (dimensions, image);
+	}
+
+	let [scaled_width, scaled_height] = [width, height].map(|x| x.div_ceil(factor));
+	let mut scaled_image = Vec::with_capacity(scaled_width * scaled_height * 4);
+	for scaled_y in 0..scaled_height {
+		for scaled_x in 0..scaled_width {
+			let mut sum = [0u32; 4];
+			let mut count = 0;
+			for y in scaled_y * factor..((scaled_y + 1) * factor).min(height) {
+				for x in scaled_x * factor..((scaled_x + 1) * factor).min(width) {
+					let offset = (y * width + x) * 4;
+					for (channel, value) in sum.iter_mut().zip(&image[offset..offset + 4]) {
+						*channel += u32::from(*value);
+					}
+					count += 1;
+				}
+			}
+			scaled_image.extend(sum.map(|channel| (channel / count) as u8));
+		}
+	}
+
+	([scaled_width, scaled_height].map(|x| NonZeroU32::new(x as u32).unwrap()), scaled_image)
+}
+
 fn create_bind_group(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout, sampler: &wgpu::Sampler, dimensions: [NonZeroU32; 2]) -> (wgpu::Texture, wgpu::Extent3d, wgpu::BindGroup) {
 	let texture_size = wgpu::Extent3d {
 		width: dimensions[0].into(),
@@ -83,9 +117,30 @@ impl Texture {
 			mipmap_filter: wgpu::FilterMode::Linear,
 			..Default::default()
 		});
-		let (texture, texture_size, bind_group) = create_bind_group(device, bind_group_layout, &sampler, dimensions);
+		Self::with_sampler(device, dimensions, image, bind_group_layout, &sampler)
+	}
+
+	// Creates a texture that repeats in both directions and is smoothly magnified, for patterns that tile across the canvas.
+	pub fn new_tiled(device: &wgpu::Device, dimensions: [NonZeroU32; 2], image: Vec<u8>, bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+		let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+			address_mode_u: wgpu::AddressMode::Repeat,
+			address_mode_v: wgpu::AddressMode::Repeat,
+			address_mode_w: wgpu::AddressMode::Repeat,
+			mag_filter: wgpu::FilterMode::Linear,
+			min_filter: wgpu::FilterMode::Linear,
+			mipmap_filter: wgpu::FilterMode::Linear,
+			..Default::default()
+		});
+		Self::with_sampler(device, dimensions, image, bind_group_layout, &sampler)
+	}
+
+	fn with_sampler(device: &wgpu::Device, dimensions: [NonZeroU32; 2], image: Vec<u8>, bind_group_layout: &wgpu::BindGroupLayout, sampler: &wgpu::Sampler) -> Self {
+		// Images larger than the device allows are uploaded at a lower resolution, since texture coordinates don't depend on it.
+		let (dimensions, image) = downscale_to_fit(dimensions, image, device.limits().max_texture_dimension_2d);
+		let (texture, texture_size, bind_group) = create_bind_group(device, bind_group_layout, sampler, dimensions);
 		Self {
 			rgba: Some(image),
+			encoded_png: OnceCell::new(),
 			texture,
 			extent: texture_size,
 			bind_group,
@@ -31,7 +31,7 @@ impl<Uniform> UniformBuffer<Uniform> {
 			label: None,
 			entries: &[wgpu::BindGroupLayoutEntry {
 				binding,
-				visibility: wgpu::ShaderStages::VERTEX,
+				visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
 				ty: wgpu::BindingType::Buffer {
 					ty: wgpu::BufferBindingType::Uniform,
 					has_dynamic_offset: false,
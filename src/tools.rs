@@ -5,11 +5,18 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use std::time::Instant;
+
+use enumset::{EnumSet, EnumSetType};
+
 use crate::{canvas::*, utility::*};
 
 pub struct PanOrigin {
 	pub cursor: Vex<2, Vx>,
 	pub position: Vex<2, Vx>,
+	// The recent velocity of the view, in canvas units per second, and when and where it was last sampled.
+	pub velocity: Vex<2, Vx>,
+	pub last_sample: (Instant, Vex<2, Vx>),
 }
 
 pub struct ZoomOrigin {
@@ -30,11 +37,54 @@ pub struct RotateDraft {
 pub struct ResizeDraft {
 	pub center: Vex<2, Vx>,
 	pub initial_distance: Vx,
+	// The offset of the cursor from the center at the start of the drag, against which each axis is stretched.
+	pub initial_offset: Vex<2, Vx>,
+	// Whether the aspect ratio is kept, so that the selection is dilated rather than stretched.
+	pub is_aspect_locked: bool,
 }
 
+impl ResizeDraft {
+	// The factors by which each axis is stretched when the cursor is at an offset from the center.
+	// An axis along which the drag started too close to the center is left as it is, rather than stretched wildly.
+	pub fn stretch(&self, offset: Vex<2, Vx>, min_initial_offset: Vx) -> Vex<2, f32> {
+		Vex([0, 1].map(|i| if self.initial_offset[i].0.abs() >= min_initial_offset.0 { (offset[i] / self.initial_offset[i]).max(MIN_STRETCH) } else { 1. }))
+	}
+}
+
+// Keeps stretched objects from collapsing or being mirrored.
+const MIN_STRETCH: f32 = 0.01;
+
 pub enum ColorSelectionPart {
 	Hue,
 	SaturationValue,
+	Alpha,
+}
+
+// How the eraser treats the strokes it touches.
+#[derive(Clone, Copy)]
+pub enum EraserMode {
+	// Deletes whole strokes.
+	Object,
+	// Deletes whole strokes matching the color of the first stroke touched.
+	Color,
+	// Deletes only the touched parts of strokes, splitting them.
+	Segment,
+}
+
+// Where the color picker appears while it is held.
+#[derive(Clone, Copy)]
+pub enum ColorPickerPlacement {
+	// At the cursor, or at the center of the window if the cursor is elsewhere.
+	Cursor,
+	Center,
+	Corner { is_right: bool, is_bottom: bool },
+}
+
+#[derive(Clone, Copy)]
+pub enum ColorTarget {
+	Stroke,
+	// The background is edited as a draft, which is committed as a single operation upon release.
+	Background { original: Srgb8, draft: Hsva },
 }
 
 pub enum Tool {
@@ -46,14 +96,104 @@ pub enum Tool {
 	Move { origin: Option<Vex<2, Vx>> },
 	Rotate { origin: Option<RotateDraft> },
 	Resize { origin: Option<ResizeDraft> },
-	PickColor { cursor_physical_origin: Vex<2, Px>, part: Option<ColorSelectionPart> },
+	Erase { path: Option<Vec<Vex<2, Vx>>> },
+	PickColor { cursor_physical_origin: Vex<2, Px>, part: Option<ColorSelectionPart>, target: ColorTarget },
+	// The swatches of the palette are laid out in a ring around the center, and the one under the cursor is picked on release.
+	PickSwatch { center: Vex<2, Px>, hovered_index: Option<usize> },
+}
+
+impl Tool {
+	pub fn kind(&self) -> ToolKind {
+		match self {
+			Tool::Draw { .. } => ToolKind::Draw,
+			Tool::Select { .. } => ToolKind::Select,
+			Tool::Pan { .. } => ToolKind::Pan,
+			Tool::Zoom { .. } => ToolKind::Zoom,
+			Tool::Orbit { .. } => ToolKind::Orbit,
+			Tool::Move { .. } => ToolKind::Move,
+			Tool::Rotate { .. } => ToolKind::Rotate,
+			Tool::Resize { .. } => ToolKind::Resize,
+			Tool::Erase { .. } => ToolKind::Erase,
+			Tool::PickColor { .. } => ToolKind::PickColor,
+			Tool::PickSwatch { .. } => ToolKind::PickSwatch,
+		}
+	}
+}
+
+// A tool, without the state of whatever it is in the middle of.
+#[derive(EnumSetType, Debug)]
+pub enum ToolKind {
+	Draw,
+	Select,
+	Pan,
+	Zoom,
+	Orbit,
+	Move,
+	Rotate,
+	Resize,
+	Erase,
+	PickColor,
+	PickSwatch,
+}
+
+impl ToolKind {
+	// The tool with the given key.
+	pub fn from_key(key: &str) -> Option<Self> {
+		EnumSet::<ToolKind>::all().iter().find(|kind| kind.key() == key)
+	}
+
+	// The name by which the tool is referred to in configuration, and shown in the status bar.
+	pub fn key(self) -> &'static str {
+		match self {
+			ToolKind::Draw => "draw",
+			ToolKind::Select => "select",
+			ToolKind::Pan => "pan",
+			ToolKind::Zoom => "zoom",
+			ToolKind::Orbit => "orbit",
+			ToolKind::Move => "move",
+			ToolKind::Rotate => "rotate",
+			ToolKind::Resize => "resize",
+			ToolKind::Erase => "erase",
+			ToolKind::PickColor => "pick-color",
+			ToolKind::PickSwatch => "pick-swatch",
+		}
+	}
 }
 
 pub enum TransientModeSwitch {
 	Pan { should_pan: bool },
 	Zoom { should_zoom: bool },
 	Orbit { should_orbit: bool },
-	Color { center: Option<Vex<2, Px>> },
+	Erase { should_erase: bool },
+	Color { center: Option<(Vex<2, Px>, ColorTarget)> },
+	Swatch { center: Option<Vex<2, Px>> },
+}
+
+// What the mouse wheel does by itself; with Control held, it does the other.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum WheelMode {
+	Pan,
+	Zoom,
+}
+
+// A tool that a pen button switches to while it is held.
+#[derive(Clone, Copy)]
+pub enum PenButtonTool {
+	Erase,
+	Pan,
+	Zoom,
+	Orbit,
+}
+
+impl PenButtonTool {
+	pub fn switch(self, is_held: bool) -> TransientModeSwitch {
+		match self {
+			Self::Erase => TransientModeSwitch::Erase { should_erase: is_held },
+			Self::Pan => TransientModeSwitch::Pan { should_pan: is_held },
+			Self::Zoom => TransientModeSwitch::Zoom { should_zoom: is_held },
+			Self::Orbit => TransientModeSwitch::Orbit { should_orbit: is_held },
+		}
+	}
 }
 
 pub struct ModeStack {
@@ -108,15 +248,51 @@ impl ModeStack {
 					self.transient_mode = None;
 				}
 			},
+			TransientModeSwitch::Erase { should_erase } => {
+				if should_erase {
+					if !matches!(self.get(), &Tool::Erase { .. }) {
+						self.transient_mode = Some(Tool::Erase { path: None });
+					}
+				} else if matches!(self.get(), &Tool::Erase { .. }) {
+					self.transient_mode = None;
+				}
+			},
 			TransientModeSwitch::Color { center } => {
-				if let Some(center) = center {
+				if let Some((center, target)) = center {
 					if !matches!(self.get(), &Tool::PickColor { .. }) {
-						self.transient_mode = Some(Tool::PickColor { cursor_physical_origin: center, part: None });
+						self.transient_mode = Some(Tool::PickColor {
+							cursor_physical_origin: center,
+							part: None,
+							target,
+						});
 					}
 				} else if matches!(self.get(), &Tool::PickColor { .. }) {
 					self.transient_mode = None;
 				}
 			},
+			TransientModeSwitch::Swatch { center } => {
+				if let Some(center) = center {
+					if !matches!(self.get(), &Tool::PickSwatch { .. }) {
+						self.transient_mode = Some(Tool::PickSwatch { center, hovered_index: None });
+					}
+				} else if matches!(self.get(), &Tool::PickSwatch { .. }) {
+					self.transient_mode = None;
+				}
+			},
+		}
+	}
+
+	// Finds the switch to the given base tool.
+	// Panning, zooming, orbiting and picking colors or swatches are only held as transient tools, so they have none.
+	pub fn switch_for_kind(kind: ToolKind) -> Option<fn(&mut Self)> {
+		match kind {
+			ToolKind::Draw => Some(Self::switch_draw),
+			ToolKind::Select => Some(Self::switch_select),
+			ToolKind::Move => Some(Self::switch_move),
+			ToolKind::Rotate => Some(Self::switch_rotate),
+			ToolKind::Resize => Some(Self::switch_resize),
+			ToolKind::Erase => Some(Self::switch_erase),
+			ToolKind::Pan | ToolKind::Zoom | ToolKind::Orbit | ToolKind::PickColor | ToolKind::PickSwatch => None,
 		}
 	}
 
@@ -134,6 +310,13 @@ impl ModeStack {
 		}
 	}
 
+	pub fn switch_erase(&mut self) {
+		if !matches!(self.base_mode, Tool::Erase { .. }) {
+			self.invalidate_base_transformation_draft();
+			self.base_mode = Tool::Erase { path: None }
+		}
+	}
+
 	pub fn switch_move(&mut self) {
 		if !matches!(self.base_mode, Tool::Move { .. }) {
 			self.invalidate_base_transformation_draft();
@@ -169,6 +352,7 @@ impl ModeStack {
 			Tool::Move { origin } => origin.is_some(),
 			Tool::Rotate { origin } => origin.is_some(),
 			Tool::Resize { origin } => origin.is_some(),
+			Tool::Erase { path } => path.is_some(),
 			_ => false,
 		}
 	}
@@ -180,6 +364,7 @@ impl ModeStack {
 			Tool::Move { origin } => *origin = None,
 			Tool::Rotate { origin } => *origin = None,
 			Tool::Resize { origin } => *origin = None,
+			Tool::Erase { path } => *path = None,
 			_ => {},
 		}
 		self.invalidate_base_transformation_draft();
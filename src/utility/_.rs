@@ -6,9 +6,13 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 mod color;
+mod palette;
 mod pixel;
+mod trace;
 mod tracked;
 
 pub use color::*;
+pub use palette::*;
 pub use pixel::*;
+pub use trace::*;
 pub use tracked::*;
@@ -7,16 +7,23 @@
 
 #[repr(transparent)]
 #[derive(Clone, Copy, derive_more::Index, derive_more::IndexMut)]
-pub struct Hsv(pub [f32; 3]);
+pub struct Hsva(pub [f32; 4]);
 
-impl Hsv {
+impl Hsva {
+	// Converts the color to sRGB, discarding alpha.
 	pub fn to_srgb(self) -> Srgb {
-		let Self([h, s, v]) = self;
+		let Self([h, s, v, _]) = self;
 		fn hue(h: f32) -> [f32; 3] {
 			[(h * 6. - 3.).abs() - 1., 2. - (h * 6. - 2.).abs(), 2. - (h * 6. - 4.).abs()].map(|n| n.clamp(0., 1.))
 		}
 		Srgb(hue(h).map(|n: f32| ((n - 1.) * s + 1.) * v))
 	}
+
+	pub fn to_srgba8(self) -> Srgba8 {
+		let Srgb8([r, g, b]) = self.to_srgb().to_srgb8();
+		let a = self.0[3];
+		Srgba8([r, g, b, if a >= 1.0 { 255 } else { (a.max(0.) * 256.) as u8 }])
+	}
 }
 
 #[repr(transparent)]
@@ -35,16 +42,16 @@ impl Srgb {
 pub struct Srgb8(pub [u8; 3]);
 
 impl Srgb8 {
-	pub fn to_hsv(self) -> Hsv {
+	pub fn to_hsva(self, alpha: f32) -> Hsva {
 		let (argmax, max) = self.0.iter().copied().enumerate().max_by_key(|(_, x)| *x).unwrap();
 		let min = self.0.iter().copied().min().unwrap();
-		Hsv(if min == max {
-			[0., 0., f32::from(max) / 255.]
+		Hsva(if min == max {
+			[0., 0., f32::from(max) / 255., alpha]
 		} else {
 			let (max, min) = (f32::from(max) / 255., f32::from(min) / 255.);
 			let saturation = (max - min) / max;
 			let hue = ((f32::from(2 * argmax as u8) - (max - f32::from(self.0[(argmax + 1) % 3]) / 255.) / (max - min) + (max - f32::from(self.0[(argmax + 2) % 3]) / 255.) / (max - min)) / 6.).fract();
-			[hue, saturation, max]
+			[hue, saturation, max, alpha]
 		})
 	}
 
@@ -59,9 +66,11 @@ impl Srgb8 {
 pub struct Srgba8(pub [u8; 4]);
 
 impl Srgba8 {
+	// Converts the color to linear RGB; alpha is already linear, so it is only normalized.
 	pub fn to_lrgba(self) -> Lrgba {
-		let Self(srgba8) = self;
-		Lrgba(srgba8.map(fast_srgb8::srgb8_to_f32))
+		let Self([r, g, b, a]) = self;
+		let [r, g, b] = [r, g, b].map(fast_srgb8::srgb8_to_f32);
+		Lrgba([r, g, b, f32::from(a) / 255.])
 	}
 }
 
@@ -0,0 +1,78 @@
+// Copyright (C) 2024 Aaron Yeoh Cruz <zeyonaut@gmail.com>
+// SPDX-License-Identifier: MPL-2.0
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use super::Srgba8;
+
+// Parses the colors of a GIMP palette (.gpl), which are opaque.
+// After the header line, each color is a line of red, green, and blue components, optionally followed by a name; other lines, like the name and column count of the palette, and comments, are skipped.
+pub fn parse_gpl(text: &str) -> Option<Vec<Srgba8>> {
+	let mut lines = text.lines();
+	if lines.next()?.trim() != "GIMP Palette" {
+		return None;
+	}
+
+	Some(
+		lines
+			.filter_map(|line| {
+				let mut components = line.split_whitespace().map(str::parse::<u8>);
+				let [r, g, b] = [components.next()?.ok()?, components.next()?.ok()?, components.next()?.ok()?];
+				Some(Srgba8([r, g, b, 0xff]))
+			})
+			.collect(),
+	)
+}
+
+// Writes colors as a GIMP palette (.gpl), which can't hold their alphas, so they are dropped.
+pub fn write_gpl(name: &str, colors: &[Srgba8]) -> String {
+	let mut text = format!("GIMP Palette\nName: {name}\nColumns: 0\n#\n");
+	for Srgba8([r, g, b, _]) in colors {
+		text += &format!("{r:3} {g:3} {b:3}\t#{r:02x}{g:02x}{b:02x}\n");
+	}
+	text
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(text: &str) -> Option<Vec<[u8; 4]>> {
+		parse_gpl(text).map(|colors| colors.into_iter().map(|color| color.0).collect())
+	}
+
+	#[test]
+	fn headers_comments_and_names_are_skipped() {
+		let text = "GIMP Palette\r\nName: Bricks\r\nColumns: 4\r\n# A comment\r\n#\r\n200  60  40\tBrick Red\r\n 10 20 30\r\n";
+		assert_eq!(parse(text), Some(vec![[200, 60, 40, 255], [10, 20, 30, 255]]));
+	}
+
+	#[test]
+	fn malformed_rows_are_skipped() {
+		let text = "GIMP Palette\n1 2\n256 0 0\n-1 0 0\nred green blue\n\n1 2 3\n";
+		assert_eq!(parse(text), Some(vec![[1, 2, 3, 255]]));
+	}
+
+	#[test]
+	fn files_without_the_header_are_rejected() {
+		assert!(parse("").is_none());
+		assert!(parse("1 2 3\n").is_none());
+		assert!(parse("JASC-PAL\n0100\n1\n1 2 3\n").is_none());
+	}
+
+	#[test]
+	fn palettes_round_trip() {
+		let colors = [[0, 0, 0, 255], [255, 255, 255, 255], [18, 52, 86, 255]];
+		let text = write_gpl("Test", &colors.map(Srgba8));
+		assert!(text.contains("Name: Test\n"));
+		assert_eq!(parse(&text), Some(colors.to_vec()));
+	}
+
+	#[test]
+	fn alphas_are_dropped() {
+		let text = write_gpl("Translucent", &[Srgba8([1, 2, 3, 4])]);
+		assert_eq!(parse(&text), Some(vec![[1, 2, 3, 255]]));
+	}
+}
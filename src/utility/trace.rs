@@ -0,0 +1,196 @@
+// Copyright (C) 2023 Aaron Yeoh Cruz <zeyonaut@gmail.com>
+// SPDX-License-Identifier: MPL-2.0
+
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+// The corners of a cell between four adjacent pixel centers, clockwise from the top left.
+const CELL_CORNERS: [[usize; 2]; 4] = [[0, 0], [1, 0], [1, 1], [0, 1]];
+
+// The edges of a cell, as pairs of corners: top, right, bottom, and left.
+const CELL_EDGES: [[usize; 2]; 4] = [[0, 1], [1, 2], [2, 3], [3, 0]];
+
+// How far, in pixels, a simplified outline may stray from the traced one.
+const SIMPLIFICATION_TOLERANCE: f32 = 0.5;
+
+// Outlines with fewer points than this are taken to be specks and dropped.
+const MIN_OUTLINE_POINT_COUNT: usize = 8;
+
+// How inked a pixel is, from 0 (light or transparent) to 1 (dark and opaque).
+fn ink(pixel: &[u8]) -> f32 {
+	let [r, g, b, a] = [pixel[0], pixel[1], pixel[2], pixel[3]].map(|channel| channel as f32 / 255.);
+	(1. - (0.2126 * r + 0.7152 * g + 0.0722 * b)) * a
+}
+
+// Traces the outlines of the inked regions of an RGBA image with marching squares, where a pixel is inked if its ink exceeds a threshold.
+// Each outline is a closed loop of positions in pixels from the top left corner of the image, with its last point equal to its first.
+pub fn trace_outlines(width: usize, height: usize, rgba: &[u8], threshold: f32) -> Vec<Vec<[f32; 2]>> {
+	if width == 0 || height == 0 || rgba.len() < width * height * 4 {
+		return Vec::new();
+	}
+
+	// The image is padded with a border of blank pixels, so that every outline is closed.
+	let sample = |x: isize, y: isize| -> f32 {
+		if x < 0 || y < 0 || x >= width as isize || y >= height as isize {
+			0.
+		} else {
+			let index = (y as usize * width + x as usize) * 4;
+			ink(&rgba[index..index + 4])
+		}
+	};
+
+	// Each segment runs between points on two cell edges, keyed by twice their midpoints, with the ink on its left.
+	let mut segments = HashMap::<[isize; 2], ([isize; 2], [f32; 2])>::new();
+
+	for y in -1..height as isize {
+		for x in -1..width as isize {
+			let corners = CELL_CORNERS.map(|[dx, dy]| [x + dx as isize, y + dy as isize]);
+			let inks = corners.map(|[x, y]| sample(x, y));
+			let is_inked = inks.map(|ink| ink > threshold);
+
+			let crossed_edges = (0..4).filter(|&edge| is_inked[CELL_EDGES[edge][0]] != is_inked[CELL_EDGES[edge][1]]).collect::<Vec<_>>();
+
+			// Diagonally opposite inked corners are kept apart by cutting around each of them.
+			let edge_pairs = match crossed_edges.as_slice() {
+				[a, b] => vec![[*a, *b]],
+				[_, _, _, _] => (0..4).filter(|&corner| is_inked[corner]).map(|corner| [(corner + 3) % 4, corner]).collect(),
+				_ => Vec::new(),
+			};
+
+			for [a, b] in edge_pairs {
+				let [key_a, key_b] = [a, b].map(|edge| {
+					let [c0, c1] = CELL_EDGES[edge].map(|corner| corners[corner]);
+					[c0[0] + c1[0], c0[1] + c1[1]]
+				});
+
+				// Orient the segment by which side of it the inked corner of its first edge lies on.
+				let inked_corner = CELL_EDGES[a].into_iter().find(|&corner| is_inked[corner]).unwrap_or(CELL_EDGES[a][0]);
+				let corner_key = [corners[inked_corner][0] * 2, corners[inked_corner][1] * 2];
+				let cross = (key_b[0] - key_a[0]) * (corner_key[1] - key_a[1]) - (key_b[1] - key_a[1]) * (corner_key[0] - key_a[0]);
+				let (start_edge, [start_key, end_key]) = if cross < 0 { (a, [key_a, key_b]) } else { (b, [key_b, key_a]) };
+
+				// Place the starting point where the ink crosses the threshold along its edge.
+				let [c0, c1] = CELL_EDGES[start_edge];
+				let t = ((threshold - inks[c0]) / (inks[c1] - inks[c0])).clamp(0., 1.);
+				let position = [0, 1].map(|i| corners[c0][i] as f32 + (corners[c1][i] - corners[c0][i]) as f32 * t + 0.5);
+
+				segments.insert(start_key, (end_key, position));
+			}
+		}
+	}
+
+	// Follow the segments around each loop until it closes.
+	let mut outlines = Vec::new();
+	let start_keys = segments.keys().copied().collect::<Vec<_>>();
+	for mut key in start_keys {
+		let mut outline = Vec::new();
+		while let Some((next_key, position)) = segments.remove(&key) {
+			outline.push(position);
+			key = next_key;
+		}
+
+		if outline.len() >= MIN_OUTLINE_POINT_COUNT {
+			outline.push(outline[0]);
+			outlines.push(simplify(&outline, SIMPLIFICATION_TOLERANCE));
+		}
+	}
+
+	outlines
+}
+
+// Drops points of a path that lie within a tolerance of the path between their neighbors, with the Ramer-Douglas-Peucker algorithm.
+fn simplify(path: &[[f32; 2]], tolerance: f32) -> Vec<[f32; 2]> {
+	let mut is_kept = vec![false; path.len()];
+	let mut spans = vec![(0, path.len() - 1)];
+	is_kept[0] = true;
+	is_kept[path.len() - 1] = true;
+
+	// A closed path starts and ends at the same point, so the first span is split at the point farthest from it.
+	if path.len() > 2 && path[0] == path[path.len() - 1] {
+		let distance = |p: [f32; 2]| (p[0] - path[0][0]).hypot(p[1] - path[0][1]);
+		let farthest = (1..path.len() - 1).max_by(|&i, &j| distance(path[i]).total_cmp(&distance(path[j]))).unwrap_or(0);
+		is_kept[farthest] = true;
+		spans = vec![(0, farthest), (farthest, path.len() - 1)];
+	}
+
+	while let Some((start, end)) = spans.pop() {
+		let [a, b] = [path[start], path[end]];
+		let length = (b[0] - a[0]).hypot(b[1] - a[1]);
+		let distance = |p: [f32; 2]| {
+			if length > 0. {
+				((b[0] - a[0]) * (a[1] - p[1]) - (a[0] - p[0]) * (b[1] - a[1])).abs() / length
+			} else {
+				(p[0] - a[0]).hypot(p[1] - a[1])
+			}
+		};
+
+		if let Some(farthest) = (start + 1..end).max_by(|&i, &j| distance(path[i]).total_cmp(&distance(path[j]))) {
+			if distance(path[farthest]) > tolerance {
+				is_kept[farthest] = true;
+				spans.push((start, farthest));
+				spans.push((farthest, end));
+			}
+		}
+	}
+
+	path.iter().zip(is_kept).filter_map(|(point, is_kept)| is_kept.then_some(*point)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Traces an image drawn with '#' for opaque black pixels and anything else for transparent ones.
+	fn trace(rows: &[&str]) -> Vec<Vec<[f32; 2]>> {
+		let rgba = rows.iter().flat_map(|row| row.chars()).flat_map(|c| if c == '#' { [0, 0, 0, 255] } else { [0; 4] }).collect::<Vec<_>>();
+		trace_outlines(rows[0].len(), rows.len(), &rgba, 0.5)
+	}
+
+	fn signed_area(outline: &[[f32; 2]]) -> f32 {
+		outline.array_windows().map(|[a, b]| a[0] * b[1] - b[0] * a[1]).sum::<f32>() / 2.
+	}
+
+	fn bounds(outline: &[[f32; 2]]) -> [[f32; 2]; 2] {
+		outline.iter().fold([[f32::MAX; 2], [f32::MIN; 2]], |[min, max], point| [[0, 1].map(|i| min[i].min(point[i])), [0, 1].map(|i| max[i].max(point[i]))])
+	}
+
+	#[test]
+	fn single_pixels_are_dropped_as_specks() {
+		assert!(trace(&["...", ".#.", "..."]).is_empty());
+	}
+
+	#[test]
+	fn blobs_are_outlined_along_pixel_edges() {
+		let outlines = trace(&["......", ".####.", ".####.", ".####.", ".####.", "......"]);
+		let [outline] = outlines.as_slice() else { panic!("expected one outline, found {}", outlines.len()) };
+		assert_eq!(outline.first(), outline.last());
+		assert_eq!(bounds(outline), [[1., 1.], [5., 5.]]);
+		// The corners are cut diagonally, and simplifying may cut them further, but most of the block is covered.
+		assert!((12.0..16.).contains(&signed_area(outline).abs()));
+	}
+
+	#[test]
+	fn holes_are_outlined_the_other_way_around() {
+		let outlines = trace(&["#######", "#######", "##...##", "##...##", "##...##", "#######", "#######"]);
+		assert_eq!(outlines.len(), 2);
+		let [outer, hole] = if signed_area(&outlines[0]).abs() > signed_area(&outlines[1]).abs() {
+			[&outlines[0], &outlines[1]]
+		} else {
+			[&outlines[1], &outlines[0]]
+		};
+		assert_eq!(bounds(outer), [[0., 0.], [7., 7.]]);
+		assert_eq!(bounds(hole), [[2., 2.], [5., 5.]]);
+		assert!(signed_area(outer) * signed_area(hole) < 0.);
+	}
+
+	#[test]
+	fn disjoint_blobs_are_outlined_separately() {
+		let mut outlines = trace(&["###....", "###....", "###....", ".......", "....###", "....###", "....###"]);
+		outlines.sort_by(|a, b| bounds(a)[0][0].total_cmp(&bounds(b)[0][0]));
+		assert_eq!(outlines.iter().map(|outline| bounds(outline)).collect::<Vec<_>>(), vec![[[0., 0.], [3., 3.]], [[4., 4.], [7., 7.]]]);
+		assert!(signed_area(&outlines[0]) * signed_area(&outlines[1]) > 0.);
+	}
+}